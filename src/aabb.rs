@@ -0,0 +1,183 @@
+use crate::line::Line;
+use crate::mat4::Mat4;
+use crate::vector::Vector;
+
+// axis aligned bounding box, described by its minimum and maximum corners
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector,
+    pub max: Vector,
+}
+
+impl Aabb {
+    pub fn new(min: Vector, max: Vector) -> Aabb {
+        Aabb { min, max }
+    }
+
+    // box that contains nothing, the union with any other box gives that box
+    pub fn empty() -> Aabb {
+        Aabb::new(
+            Vector::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            Vector::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY))
+    }
+
+    // box that contains everything, used by unbounded shapes like infinite planes
+    pub fn infinite() -> Aabb {
+        Aabb::new(
+            Vector::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            Vector::new(f64::INFINITY, f64::INFINITY, f64::INFINITY))
+    }
+
+    // smallest box containing all the points
+    pub fn from_points(points: &[Vector]) -> Aabb {
+        let mut aabb = Aabb::empty();
+        for point in points {
+            aabb.expand(point);
+        }
+        aabb
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn is_finite(&self) -> bool {
+        [self.min.x, self.min.y, self.min.z, self.max.x, self.max.y, self.max.z].iter().all(|v| v.is_finite())
+    }
+
+    // grows the box so it contains the point
+    pub fn expand(&mut self, point: &Vector) {
+        self.min = self.min.min(point);
+        self.max = self.max.max(point);
+    }
+
+    // smallest box containing both boxes
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(self.min.min(&other.min), self.max.max(&other.max))
+    }
+
+    // box around this box after it is transformed by m, usually a bit bigger than the transformed box itself.
+    // Empty and infinite boxes stay what they are
+    pub fn transform(&self, m: &Mat4) -> Aabb {
+        if self.is_empty() || !self.is_finite() {
+            return *self;
+        }
+        let corners: Vec<Vector> = (0..8)
+            .map(|k| {
+                let pick = |bit: usize, min: f64, max: f64| if k & bit == 0 { min } else { max };
+                m.transform_point(&Vector::new(pick(1, self.min.x, self.max.x), pick(2, self.min.y, self.max.y), pick(4, self.min.z, self.max.z)))
+            })
+            .collect();
+        Aabb::from_points(&corners)
+    }
+
+    pub fn contains(&self, point: &Vector) -> bool {
+        point.x >= self.min.x && point.x <= self.max.x &&
+        point.y >= self.min.y && point.y <= self.max.y &&
+        point.z >= self.min.z && point.z <= self.max.z
+    }
+
+    pub fn size(&self) -> Vector {
+        self.max - self.min
+    }
+
+    pub fn centroid(&self) -> Vector {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let d = self.size();
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    // index of the longest axis, 0 for x, 1 for y and 2 for z
+    pub fn longest_axis(&self) -> usize {
+        let d = self.size();
+        if d.x >= d.y && d.x >= d.z {
+            0
+        } else if d.y >= d.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    // slab test, returns the (t_enter, t_exit) range of the line inside the box if it overlaps [t_min, t_max].
+    // see: https://tavianator.com/2022/ray_box_boundary.html
+    pub fn intersect(&self, line: &Line, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let origin = [line.point.x, line.point.y, line.point.z];
+        let direction = [line.direction.x, line.direction.y, line.direction.z];
+        let min = [self.min.x, self.min.y, self.min.z];
+        let max = [self.max.x, self.max.y, self.max.z];
+
+        let (mut t_enter, mut t_exit) = (t_min, t_max);
+        for axis in 0..3 {
+            let inv = 1.0 / direction[axis];
+            let mut t0 = (min[axis] - origin[axis]) * inv;
+            let mut t1 = (max[axis] - origin[axis]) * inv;
+            // a line parallel to the slab and starting on its boundary gives 0 * inf = NaN, treat it as inside
+            if t0.is_nan() || t1.is_nan() {
+                t0 = f64::NEG_INFINITY;
+                t1 = f64::INFINITY;
+            }
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_enter = t_enter.max(t0);
+            t_exit = t_exit.min(t1);
+            if t_enter > t_exit {
+                return None;
+            }
+        }
+        Some((t_enter, t_exit))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn unit_box() -> Aabb {
+        Aabb::new(Vector::new(-1.0, -1.0, -1.0), Vector::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn union_expand_test() {
+        let mut aabb = Aabb::empty();
+        assert!(aabb.is_empty());
+        aabb.expand(&Vector::new(1.0, 2.0, 3.0));
+        aabb.expand(&Vector::new(-1.0, 0.0, 5.0));
+        assert_eq!(aabb, Aabb::new(Vector::new(-1.0, 0.0, 3.0), Vector::new(1.0, 2.0, 5.0)));
+        assert_eq!(Aabb::empty().union(&unit_box()), unit_box());
+        assert_eq!(aabb.union(&unit_box()), Aabb::new(Vector::new(-1.0, -1.0, -1.0), Vector::new(1.0, 2.0, 5.0)));
+    }
+
+    #[test]
+    fn measure_test() {
+        let aabb = Aabb::new(Vector::new(0.0, 0.0, 0.0), Vector::new(1.0, 2.0, 3.0));
+        assert_eq!(aabb.centroid(), Vector::new(0.5, 1.0, 1.5));
+        assert_eq!(aabb.surface_area(), 22.0);
+        assert_eq!(aabb.longest_axis(), 2);
+        assert_eq!(Aabb::empty().surface_area(), 0.0);
+    }
+
+    #[test]
+    fn slab_test() {
+        let line = Line::new(Vector::new(0.0, 0.0, 10.0), Vector::new(0.0, 0.0, -1.0));
+        assert_eq!(unit_box().intersect(&line, 0.0, f64::INFINITY), Some((9.0, 11.0)));
+        // the box is further away than t_max
+        assert!(unit_box().intersect(&line, 0.0, 5.0).is_none());
+        // starting inside
+        let inside = Line::new(Vector::new(0.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        assert_eq!(unit_box().intersect(&inside, 0.0, f64::INFINITY), Some((0.0, 1.0)));
+        // parallel to a face and outside of it
+        let miss = Line::new(Vector::new(0.0, 2.0, 10.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(unit_box().intersect(&miss, 0.0, f64::INFINITY).is_none());
+        // parallel and lying exactly on a face
+        let edge = Line::new(Vector::new(1.0, 0.0, 10.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(unit_box().intersect(&edge, 0.0, f64::INFINITY).is_some());
+    }
+}
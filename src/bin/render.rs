@@ -0,0 +1,422 @@
+// headless renderer, renders the cube, the primitives showcase, a scene file or an OBJ, STL or PLY model with the same camera as the viewer
+// and writes a PNG or PPM image. It never opens a window, so it works on machines without a display or a GPU.
+//
+// usage: render [--scene cube|showcase|FILE|MODEL.obj|MODEL.stl|MODEL.ply] [--save-scene FILE] [--save-mesh FILE] [--smooth DEG] [--size W,H] [--pos X,Y,Z] [--rot X,Y,Z] [--fov DEG | --extent W,H] [--color R,G,B] [--culling] [--threads N] [--aa N] [--pattern regular|jittered|halton] [--filter box|tent|gaussian] [--trace] [--samples N] [--depth N] [--light point|directional|spot|area] [--ascii] [--out FILE]
+// without --fov the camera uses an orthographic projection covering --extent world units (60x60 by default),
+// so changing --size only changes the level of detail.
+// The image format is picked from the extension of --out, --ascii writes an ASCII PPM instead.
+// --threads sets how many threads render tiles of the image, by default every core is used.
+// --aa anti-aliases the image with N rays per pixel, placed by --pattern and combined by --filter.
+// --trace ray traces the scene in front of a wall with shadows, reflections and refractions
+// up to --depth bounces, instead of shading by the viewing angle. --light picks the kind of light above the scene.
+// --samples path traces the same scene with N samples per pixel instead, the image is written after 1, 2, 4, 8, ...
+// samples so the output can be watched while it converges.
+// A model is moved and scaled to fill the view like the cube does. --smooth gives it smooth vertex normals,
+// with edges between faces more than DEG degrees apart kept sharp, instead of the normals in its file.
+// A scene file brings its own objects, lights and background, and is rendered through its first camera
+// unless --size, --pos, --rot, --fov or --extent are given. --save-scene writes what is rendered, camera included,
+// to a scene file that --scene can load again. --save-mesh writes the objects as triangles to an OBJ or binary STL file
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::process;
+
+use math_for_graphics::camera::{Camera, Projection};
+use math_for_graphics::color::Color;
+use math_for_graphics::framebuffer::{Accumulator, Framebuffer};
+use math_for_graphics::light::Light;
+use math_for_graphics::material::Material;
+use math_for_graphics::math::as_radians;
+use math_for_graphics::mesh::{Mesh, NormalWeighting};
+use math_for_graphics::object::Object;
+use math_for_graphics::pathtracer::PathTracer;
+use math_for_graphics::quaternion::Quaternion;
+use math_for_graphics::raytracer::RayTracer;
+use math_for_graphics::scene::Scene;
+use math_for_graphics::sampling::{Filter, SamplePattern};
+use math_for_graphics::surface::Surface;
+use math_for_graphics::vector::Vector;
+
+// same background as the viewer
+const BG_COLOR: (u8, u8, u8) = (0, 172, 210);
+
+// pieces per full turn of the curved shapes written by --save-mesh
+const MESH_SEGMENTS: usize = 48;
+
+struct Options {
+    // cube, showcase or the path of a scene file or model
+    scene: String,
+    save_scene: Option<String>,
+    save_mesh: Option<String>,
+    // crease angle in degrees of the smooth normals computed for a model, None keeps its own normals
+    smooth: Option<f64>,
+    // true if the camera is set up on the command line
+    camera: bool,
+    width: i32,
+    height: i32,
+    position: Vector,
+    // camera rotation around x, y and z axes in degrees
+    rotation: Vector,
+    // vertical field of view in degrees, orthographic projection if None
+    fov: Option<f64>,
+    // world-space size of the orthographic viewport
+    extent: (f64, f64),
+    color: (u8, u8, u8),
+    backface_culling: bool,
+    // 0 uses every core
+    threads: usize,
+    // rays per pixel
+    aa: usize,
+    pattern: SamplePattern,
+    filter: Filter,
+    trace: bool,
+    // samples per pixel of the path tracer, 0 doesn't path trace
+    samples: u32,
+    depth: u32,
+    light: String,
+    ascii: bool,
+    out: String,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options {
+            scene: String::from("cube"),
+            save_scene: None,
+            save_mesh: None,
+            smooth: None,
+            camera: false,
+            width: 60,
+            height: 60,
+            position: Vector::new(0.0, 0.0, 50.0),
+            rotation: Vector::new(0.0, 0.0, 0.0),
+            fov: None,
+            extent: (60.0, 60.0),
+            color: (255, 0, 0),
+            backface_culling: false,
+            threads: 0,
+            aa: 1,
+            pattern: SamplePattern::Jittered,
+            filter: Filter::Box,
+            trace: false,
+            samples: 0,
+            depth: 5,
+            light: String::from("point"),
+            ascii: false,
+            out: String::from("render.png"),
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--culling" => options.backface_culling = true,
+                "--ascii" => options.ascii = true,
+                "--trace" => options.trace = true,
+                "--scene" | "--save-scene" | "--save-mesh" | "--smooth" | "--size" | "--pos" | "--rot" | "--fov" | "--extent" | "--color" | "--threads" | "--aa" | "--pattern" | "--filter" | "--samples" | "--depth" | "--light" | "--out" => {
+                    let value = args.next().ok_or(format!("missing value for {}", arg))?;
+                    match arg.as_str() {
+                        "--scene" => options.scene = value.clone(),
+                        "--save-scene" => options.save_scene = Some(value.clone()),
+                        "--save-mesh" => options.save_mesh = Some(value.clone()),
+                        "--smooth" => {
+                            let [degrees] = parse_list::<f64, 1>(value)?;
+                            options.smooth = Some(degrees);
+                        }
+                        "--size" => {
+                            options.camera = true;
+                            let [w, h] = parse_list::<i32, 2>(value)?;
+                            if w <= 0 || h <= 0 {
+                                return Err(format!("invalid size: {}", value));
+                            }
+                            options.width = w;
+                            options.height = h;
+                        }
+                        "--pos" => {
+                            options.camera = true;
+                            let [x, y, z] = parse_list::<f64, 3>(value)?;
+                            options.position = Vector::new(x, y, z);
+                        }
+                        "--rot" => {
+                            options.camera = true;
+                            let [x, y, z] = parse_list::<f64, 3>(value)?;
+                            options.rotation = Vector::new(x, y, z);
+                        }
+                        "--fov" => {
+                            options.camera = true;
+                            let [fov] = parse_list::<f64, 1>(value)?;
+                            if fov <= 0.0 || fov >= 180.0 {
+                                return Err(format!("field of view must be between 0 and 180 degrees, got: {}", value));
+                            }
+                            options.fov = Some(fov);
+                        }
+                        "--extent" => {
+                            options.camera = true;
+                            let [w, h] = parse_list::<f64, 2>(value)?;
+                            if w <= 0.0 || h <= 0.0 {
+                                return Err(format!("invalid extent: {}", value));
+                            }
+                            options.extent = (w, h);
+                        }
+                        "--color" => {
+                            let [r, g, b] = parse_list::<u8, 3>(value)?;
+                            options.color = (r, g, b);
+                        }
+                        "--threads" => {
+                            let [threads] = parse_list::<usize, 1>(value)?;
+                            options.threads = threads;
+                        }
+                        "--aa" => {
+                            let [aa] = parse_list::<usize, 1>(value)?;
+                            if aa == 0 {
+                                return Err(String::from("need at least one ray per pixel"));
+                            }
+                            options.aa = aa;
+                        }
+                        "--pattern" => {
+                            options.pattern = match value.as_str() {
+                                "regular" => SamplePattern::Regular,
+                                "jittered" => SamplePattern::Jittered,
+                                "halton" => SamplePattern::Halton,
+                                _ => return Err(format!("unknown sample pattern: {}", value)),
+                            };
+                        }
+                        "--filter" => {
+                            options.filter = match value.as_str() {
+                                "box" => Filter::Box,
+                                "tent" => Filter::Tent { radius: 1.0 },
+                                "gaussian" => Filter::Gaussian { radius: 1.5, sigma: 0.5 },
+                                _ => return Err(format!("unknown filter: {}", value)),
+                            };
+                        }
+                        "--light" => {
+                            if !["point", "directional", "spot", "area"].contains(&value.as_str()) {
+                                return Err(format!("unknown light: {}", value));
+                            }
+                            options.light = value.clone();
+                        }
+                        "--samples" => {
+                            let [samples] = parse_list::<u32, 1>(value)?;
+                            if samples == 0 {
+                                return Err(String::from("need at least one sample per pixel"));
+                            }
+                            options.samples = samples;
+                        }
+                        "--depth" => {
+                            let [depth] = parse_list::<u32, 1>(value)?;
+                            options.depth = depth;
+                        }
+                        _ => options.out = value.clone(),
+                    }
+                }
+                _ => return Err(format!("unknown argument: {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+// parses comma separated values, for example "1.0,2.5,-3"
+fn parse_list<T: std::str::FromStr, const N: usize>(value: &str) -> Result<[T; N], String> {
+    let parsed = value
+        .split(',')
+        .map(|v| v.trim().parse::<T>().map_err(|_| format!("invalid value: {}", value)))
+        .collect::<Result<Vec<T>, String>>()?;
+    parsed.try_into().map_err(|_| format!("expected {} comma separated values, got: {}", N, value))
+}
+
+fn save(options: &Options, fb: &Framebuffer) -> std::io::Result<()> {
+    if options.ascii {
+        let mut file = BufWriter::new(File::create(&options.out)?);
+        fb.write_ppm_ascii(&mut file)?;
+        file.flush()
+    } else {
+        fb.save(&options.out)
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match Options::parse(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("usage: render [--scene cube|showcase|FILE|MODEL.obj|MODEL.stl|MODEL.ply] [--save-scene FILE] [--save-mesh FILE] [--smooth DEG] [--size W,H] [--pos X,Y,Z] [--rot X,Y,Z] [--fov DEG | --extent W,H] [--color R,G,B] [--culling] [--threads N] [--aa N] [--pattern regular|jittered|halton] [--filter box|tent|gaussian] [--trace] [--samples N] [--depth N] [--light point|directional|spot|area] [--ascii] [--out FILE]");
+            process::exit(1);
+        }
+    };
+
+    let (r, g, b) = options.color;
+    let color = Color::from_srgb8(r, g, b);
+    let builtin = options.scene == "cube" || options.scene == "showcase";
+    let mut scene = if builtin {
+        let mut scene = Scene::new();
+        scene.background = Color::from_srgb8(BG_COLOR.0, BG_COLOR.1, BG_COLOR.2);
+        scene
+    } else {
+        let model = [".obj", ".stl", ".ply"].iter().any(|extension| options.scene.to_ascii_lowercase().ends_with(extension));
+        let loaded = if model { load_model(&options.scene, options.smooth) } else { Scene::load(&options.scene) };
+        match loaded {
+            Ok(scene) => scene,
+            Err(e) => {
+                eprintln!("could not load scene {}: {}", options.scene, e);
+                process::exit(1);
+            }
+        }
+    };
+    let background = scene.background;
+
+    let mut camera = if !builtin && !options.camera && !scene.cameras.is_empty() {
+        scene.cameras[0].clone()
+    } else {
+        let mut camera = Camera::new_orthographic(
+            options.position,
+            Vector::new(0.0, 0.0, -1.0),
+            options.width, options.height,
+            Vector::new(0.0, 1.0, 0.0),
+            Vector::new(1.0, 0.0, 0.0),
+            options.extent);
+
+        // rotations are applied in the same order as the viewer sliders
+        let mut q = Quaternion::identity();
+        q.rotate(as_radians(options.rotation.x), Vector::new(1.0, 0.0, 0.0));
+        q.rotate(as_radians(options.rotation.y), Vector::new(0.0, 1.0, 0.0));
+        q.rotate(as_radians(options.rotation.z), Vector::new(0.0, 0.0, 1.0));
+        camera.set_camera_rotation(&q);
+        camera.backface_culling = options.backface_culling;
+        camera.samples = options.aa;
+        camera.pattern = options.pattern;
+        camera.filter = options.filter;
+        if let Some(fov) = options.fov {
+            camera.projection = Projection::Perspective {
+                fov_y: as_radians(fov),
+                aspect: options.width as f64 / options.height as f64,
+            };
+        }
+        camera
+    };
+    camera.backface_culling |= options.backface_culling;
+    camera.threads = options.threads;
+    let (width, height) = (camera.render_width as usize, camera.render_height as usize);
+
+    let trace = options.trace || options.samples > 0;
+    if builtin {
+        let mut object = if options.scene == "showcase" { Object::showcase() } else { Object::cube(15.0) };
+        if trace && options.scene == "cube" {
+            object.set_material(Material::new(color));
+        }
+        scene.add_object(&options.scene, object);
+        if trace {
+            // light gray wall behind the scene to catch the shadows
+            let mut wall = Object::from_shapes(Vec::new());
+            wall.add_shape_with_material(
+                Surface::new_vw(
+                    Vector::new(0.0, 0.0, -20.0),
+                    Vector::new(1.0, 0.0, 0.0),
+                    Vector::new(0.0, 1.0, 0.0),
+                    (-80.0, 80.0),
+                    (-80.0, 80.0),
+                    Vector::new(0.0, 0.0, 1.0)),
+                Material::new(Color::new(0.7, 0.7, 0.7)));
+            scene.add_object("wall", wall);
+        }
+    }
+    if trace && scene.lights.is_empty() {
+        scene.add_light(light(&options.light));
+    }
+
+    if let Some(path) = &options.save_scene {
+        // the camera goes first, so loading the file renders the same image
+        scene.cameras.insert(0, camera.clone());
+        if let Err(e) = scene.save(path) {
+            eprintln!("could not save scene {}: {}", path, e);
+            process::exit(1);
+        }
+        scene.cameras.remove(0);
+    }
+
+    if let Some(path) = &options.save_mesh {
+        let saved = scene.to_mesh(MESH_SEGMENTS).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)).and_then(|mesh| mesh.save(path));
+        if let Err(e) = saved {
+            eprintln!("could not save mesh {}: {}", path, e);
+            process::exit(1);
+        }
+    }
+
+    // built once, every sample of the progressive render uses it
+    let bvh = scene.bvh();
+    let fb = if trace {
+        if options.samples > 0 {
+            let mut tracer = PathTracer::new();
+            tracer.max_depth = options.depth;
+            let mut accumulator = Accumulator::new(width, height);
+            for frame in 0..options.samples {
+                accumulator.add(&tracer.render(&mut camera, &scene, &bvh, frame));
+                let done = accumulator.frames();
+                if done.is_power_of_two() && done < options.samples {
+                    if let Err(e) = save(&options, &accumulator.image()) {
+                        eprintln!("could not write {}: {}", options.out, e);
+                        process::exit(1);
+                    }
+                    println!("{} of {} samples", done, options.samples);
+                }
+            }
+            accumulator.image()
+        } else {
+            let mut tracer = RayTracer::new();
+            tracer.max_depth = options.depth;
+            tracer.render(&mut camera, &scene, &bvh)
+        }
+    } else {
+        let mut fb = Framebuffer::new(width, height);
+        if camera.samples > 1 {
+            fb.shade_samples(&mut camera, &scene, &bvh, color, background);
+        } else {
+            let hits = camera.render(&scene, &bvh);
+            fb.shade(&camera, &hits, color, background);
+        }
+        fb
+    };
+
+    if let Err(e) = save(&options, &fb) {
+        eprintln!("could not write {}: {}", options.out, e);
+        process::exit(1);
+    }
+    println!("{}", camera.get_debug_info());
+    println!("saved {}x{} image to {}", width, height, options.out);
+}
+
+// scene with just the model, moved to (0, 0, 0) and scaled to the size of the cube so the default camera sees all of it
+fn load_model(path: &str, smooth: Option<f64>) -> std::io::Result<Scene> {
+    let mut mesh = Mesh::load(path)?;
+    if let Some(degrees) = smooth {
+        mesh.compute_smooth_normals(NormalWeighting::Angle, as_radians(degrees));
+    }
+    let mut scene = Scene::from_object("model", mesh.to_object());
+    scene.background = Color::from_srgb8(BG_COLOR.0, BG_COLOR.1, BG_COLOR.2);
+    let model = scene.object_mut("model").expect("just added");
+    let bounds = model.bounds();
+    if !bounds.is_empty() {
+        let size = bounds.size();
+        let scale = 30.0 / size.x.max(size.y).max(size.z).max(1e-9);
+        model.scale(&Vector::new(scale, scale, scale));
+        model.translate(&(bounds.centroid() * -scale));
+    }
+    Ok(scene)
+}
+
+// light shining on the scene from the top left
+fn light(kind: &str) -> Light {
+    let position = Vector::new(-25.0, 35.0, 60.0);
+    let mut direction = position * -1.0;
+    direction.normalize();
+    match kind {
+        "directional" => Light::directional(direction, Color::white(), 3.0),
+        "spot" => Light::spot(position, direction, Color::white(), 20000.0, as_radians(15.0), as_radians(25.0)),
+        "area" => {
+            let (v, w) = direction.orthonormal_basis();
+            let panel = Surface::new_vw(position, v, w, (-8.0, 8.0), (-8.0, 8.0), direction);
+            Light::area(panel, Color::white(), 20000.0, 6).expect("the panel is bounded")
+        }
+        _ => Light::point(position, Color::white(), 20000.0),
+    }
+}
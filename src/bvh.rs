@@ -0,0 +1,324 @@
+use crate::aabb::Aabb;
+use crate::line::Line;
+use crate::shape::Shape;
+use crate::vector::Vector;
+
+// number of buckets the centroids are sorted into when looking for the best split
+const BINS: usize = 12;
+// nodes with this many shapes or less are never split
+const MIN_LEAF_SIZE: usize = 2;
+// nodes with more shapes than this are always split, even if SAH says a leaf is cheaper
+const MAX_LEAF_SIZE: usize = 8;
+// cost of visiting a node relative to intersecting a shape
+const TRAVERSAL_COST: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy)]
+enum NodeKind {
+    // shapes are indices[first..first + count]
+    Leaf { first: usize, count: usize },
+    // left child is always the node right after its parent, axis is the split axis of the centroids
+    Interior { right: usize, axis: usize },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    bounds: Aabb,
+    kind: NodeKind,
+}
+
+// bounding volume hierarchy over the shapes of an object, built with binned SAH.
+// The hierarchy only stores indices, the shapes are passed in again when tracing so
+// it has to be rebuilt whenever the shapes are moved, added or removed.
+#[derive(Debug, Clone)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+    // shapes with infinite bounds (planes) can't be sorted into the tree, they are tested with every ray
+    unbounded: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn new(shapes: &[Box<dyn Shape>]) -> Bvh {
+        let bounds: Vec<Aabb> = shapes.iter().map(|shape| shape.bounds()).collect();
+        let centroids: Vec<Vector> = bounds.iter().map(|b| b.centroid()).collect();
+        let (indices, unbounded): (Vec<usize>, Vec<usize>) = (0..shapes.len()).partition(|&i| bounds[i].is_finite());
+
+        let mut bvh = Bvh { nodes: Vec::new(), indices, unbounded };
+        if !bvh.indices.is_empty() {
+            bvh.build(&bounds, &centroids, 0, bvh.indices.len());
+        }
+        bvh
+    }
+
+    // number of nodes in the tree, 0 if there are no bounded shapes
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // box around all bounded shapes
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::empty(), |node| node.bounds)
+    }
+
+    // recursively builds the node for indices[first..first + count], returns its index
+    fn build(&mut self, bounds: &[Aabb], centroids: &[Vector], first: usize, count: usize) -> usize {
+        let node_index = self.nodes.len();
+        let items = &self.indices[first..first + count];
+        let node_bounds = items.iter().fold(Aabb::empty(), |b, &i| b.union(&bounds[i]));
+        let centroid_bounds = Aabb::from_points(&items.iter().map(|&i| centroids[i]).collect::<Vec<_>>());
+        self.nodes.push(Node { bounds: node_bounds, kind: NodeKind::Leaf { first, count } });
+
+        let axis = centroid_bounds.longest_axis();
+        let (lo, hi) = (component(&centroid_bounds.min, axis), component(&centroid_bounds.max, axis));
+        // all centroids in one spot, no split can separate them
+        if count <= MIN_LEAF_SIZE || hi - lo <= 0.0 {
+            return node_index;
+        }
+
+        let bin_of = |i: usize| {
+            let bin = ((component(&centroids[i], axis) - lo) / (hi - lo) * BINS as f64) as usize;
+            bin.min(BINS - 1)
+        };
+        let mut bin_bounds = [Aabb::empty(); BINS];
+        let mut bin_counts = [0usize; BINS];
+        for &i in items {
+            let bin = bin_of(i);
+            bin_bounds[bin] = bin_bounds[bin].union(&bounds[i]);
+            bin_counts[bin] += 1;
+        }
+
+        // cost of splitting after bin k, sweeping from both sides
+        let mut best_split = 0;
+        let mut best_cost = f64::INFINITY;
+        for split in 0..BINS - 1 {
+            let (mut left, mut right) = (Aabb::empty(), Aabb::empty());
+            let (mut left_count, mut right_count) = (0, 0);
+            for bin in 0..=split {
+                left = left.union(&bin_bounds[bin]);
+                left_count += bin_counts[bin];
+            }
+            for bin in split + 1..BINS {
+                right = right.union(&bin_bounds[bin]);
+                right_count += bin_counts[bin];
+            }
+            if left_count == 0 || right_count == 0 {
+                continue;
+            }
+            let cost = left.surface_area() * left_count as f64 + right.surface_area() * right_count as f64;
+            if cost < best_cost {
+                best_cost = cost;
+                best_split = split;
+            }
+        }
+
+        let area = node_bounds.surface_area();
+        let split_cost = TRAVERSAL_COST + best_cost / area;
+        let leaf_cost = count as f64;
+        if !best_cost.is_finite() || (split_cost >= leaf_cost && count <= MAX_LEAF_SIZE) {
+            return node_index;
+        }
+
+        // move the shapes of the left bins to the front
+        let items = &mut self.indices[first..first + count];
+        let mut mid = 0;
+        for k in 0..count {
+            if bin_of(items[k]) <= best_split {
+                items.swap(k, mid);
+                mid += 1;
+            }
+        }
+
+        self.build(bounds, centroids, first, mid);
+        let right = self.build(bounds, centroids, first + mid, count - mid);
+        self.nodes[node_index].kind = NodeKind::Interior { right, axis };
+        node_index
+    }
+
+    // closest shape hit by the line, returns the index of the shape and t.
+    // Same result as testing every shape, if two shapes are hit at the same t the lower index wins
+    pub fn closest_hit(&self, shapes: &[Box<dyn Shape>], line: &Line) -> Option<(usize, f64)> {
+        let mut closest: Option<(usize, f64)> = None;
+        let test = |index: usize, closest: &mut Option<(usize, f64)>| {
+            if let Some(t) = shapes[index].intersect(line) {
+                let closer = match *closest {
+                    Some((i, best)) => t < best || (t == best && index < i),
+                    None => true,
+                };
+                if closer {
+                    *closest = Some((index, t));
+                }
+            }
+        };
+
+        for &index in &self.unbounded {
+            test(index, &mut closest);
+        }
+        if self.nodes.is_empty() {
+            return closest;
+        }
+
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            let t_max = closest.map_or(f64::INFINITY, |(_, t)| t);
+            if node.bounds.intersect(line, 0.0, t_max).is_none() {
+                continue;
+            }
+            match node.kind {
+                NodeKind::Leaf { first, count } => {
+                    for &index in &self.indices[first..first + count] {
+                        test(index, &mut closest);
+                    }
+                }
+                NodeKind::Interior { right, axis } => {
+                    // visit the nearer child first so the farther one can be skipped more often
+                    let left = node_index + 1;
+                    if component(&line.direction, axis) < 0.0 {
+                        stack.push(left);
+                        stack.push(right);
+                    } else {
+                        stack.push(right);
+                        stack.push(left);
+                    }
+                }
+            }
+        }
+        closest
+    }
+
+    // true if any shape is hit before max_t, stops at the first hit found. Used for shadow rays
+    pub fn any_hit(&self, shapes: &[Box<dyn Shape>], line: &Line, max_t: f64) -> bool {
+        let hits = |index: &usize| shapes[*index].intersect(line).is_some_and(|t| t < max_t);
+        if self.unbounded.iter().any(hits) {
+            return true;
+        }
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if node.bounds.intersect(line, 0.0, max_t).is_none() {
+                continue;
+            }
+            match node.kind {
+                NodeKind::Leaf { first, count } => {
+                    if self.indices[first..first + count].iter().any(hits) {
+                        return true;
+                    }
+                }
+                NodeKind::Interior { right, .. } => {
+                    stack.push(right);
+                    stack.push(node_index + 1);
+                }
+            }
+        }
+        false
+    }
+}
+
+fn component(v: &Vector, axis: usize) -> f64 {
+    match axis {
+        0 => v.x,
+        1 => v.y,
+        _ => v.z,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::object::Object;
+    use crate::sphere::Sphere;
+    use crate::surface::Surface;
+    use crate::triangle::Triangle;
+
+    // showcase scene plus a wall of small triangles behind it and an infinite floor
+    fn busy_object() -> Object {
+        let mut object = Object::showcase();
+        for i in -20..20 {
+            for j in -20..20 {
+                let p = Vector::new(i as f64 * 1.5, j as f64 * 1.5, -30.0 + ((i * j) % 7) as f64);
+                object.add_shape(Triangle::new(p, p + Vector::new(1.4, 0.0, 0.0), p + Vector::new(0.0, 1.4, 0.5)));
+            }
+        }
+        object.add_shape(Surface::new_normal(Vector::new(0.0, -40.0, 0.0), Vector::new(0.0, 1.0, 0.0)));
+        object
+    }
+
+    fn rays() -> Vec<Line> {
+        let mut rays = Vec::new();
+        let origins = [Vector::new(0.0, 0.0, 100.0), Vector::new(60.0, 30.0, 50.0), Vector::new(-5.0, -50.0, -80.0), Vector::new(0.0, 0.0, 0.0)];
+        for origin in origins {
+            for i in -25..25 {
+                for j in -25..25 {
+                    let target = Vector::new(j as f64 * 1.7, i as f64 * 1.3, (i + j) as f64 * 0.4);
+                    let mut direction = target - origin;
+                    if direction.length() == 0.0 {
+                        continue;
+                    }
+                    direction.normalize();
+                    rays.push(Line::new(origin, direction));
+                }
+            }
+        }
+        rays
+    }
+
+    #[test]
+    fn same_as_brute_force_test() {
+        let object = busy_object();
+        let bvh = Bvh::new(&object.shapes);
+        assert!(bvh.node_count() > 1);
+        for line in rays() {
+            for bfc in [false, true] {
+                let brute = line.intersection_object(&object, &line.point, &bfc);
+                let fast = line.intersection_bvh(&object, &bvh, &bfc);
+                assert_eq!(brute.is_some(), fast.is_some(), "{:?}", line);
+                if let (Some(a), Some(b)) = (brute.hit(), fast.hit()) {
+                    assert_eq!(a.shape_index, b.shape_index, "{:?}", line);
+                    assert_eq!(a.t, b.t);
+                    assert_eq!(a.point, b.point);
+                    assert_eq!(a.front_face, b.front_face);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn any_hit_test() {
+        let object = busy_object();
+        let bvh = Bvh::new(&object.shapes);
+        for line in rays() {
+            let closest = bvh.closest_hit(&object.shapes, &line);
+            assert_eq!(bvh.any_hit(&object.shapes, &line, f64::INFINITY), closest.is_some());
+            if let Some((_, t)) = closest {
+                // nothing is in front of the closest hit
+                assert!(!bvh.any_hit(&object.shapes, &line, t * 0.999));
+            }
+        }
+    }
+
+    #[test]
+    fn build_test() {
+        let empty = Bvh::new(&[]);
+        assert_eq!(empty.node_count(), 0);
+        assert!(empty.closest_hit(&[], &Line::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0))).is_none());
+
+        // identical shapes can't be split
+        let shapes: Vec<Box<dyn Shape>> = (0..20).map(|_| Box::new(Sphere::new(Vector::new(0.0, 0.0, 0.0), 1.0)) as Box<dyn Shape>).collect();
+        let bvh = Bvh::new(&shapes);
+        assert_eq!(bvh.node_count(), 1);
+        let line = Line::new(Vector::new(0.0, 0.0, 10.0), Vector::new(0.0, 0.0, -1.0));
+        assert_eq!(bvh.closest_hit(&shapes, &line), Some((0, 9.0)));
+
+        // every shape ends up in exactly one leaf
+        let object = busy_object();
+        let bvh = Bvh::new(&object.shapes);
+        let mut seen: Vec<usize> = bvh.indices.iter().chain(bvh.unbounded.iter()).copied().collect();
+        seen.sort();
+        assert_eq!(seen, (0..object.shapes.len()).collect::<Vec<_>>());
+        assert_eq!(bvh.unbounded.len(), 1);
+    }
+}
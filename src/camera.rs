@@ -1,9 +1,55 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::color::Color;
 use crate::line::Line;
-use crate::object::Object;
 use crate::quaternion::Quaternion;
+use crate::random::{splitmix, Rng};
 use crate::raycasthit::RayCastHit;
+use crate::sampling::{Filter, SamplePattern};
+use crate::scene::{Scene, SceneBvh};
 use crate::vector::Vector;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    // parallel rays starting on a grid around the camera position.
+    // width and height are the world-space size of the grid, independent of the render resolution
+    Orthographic { width: f64, height: f64 },
+    // all rays start at the camera position and go through an image plane one unit in front of it.
+    // fov_y is the vertical field of view in radians, aspect is width / height of the image plane
+    Perspective { fov_y: f64, aspect: f64 },
+}
+
+// width and height in pixels of the tiles the image is split into for rendering
+const TILE_SIZE: i32 = 32;
+
+// counters collected while rendering, every tile has its own and they are merged when the tiles are put together.
+// rays counts primary rays plus whatever secondary rays the pixel function reports
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RenderStats {
+    pub rays: usize,
+    pub hits: usize,
+    pub tiles: usize,
+}
+
+impl RenderStats {
+    pub fn merge(&mut self, other: &RenderStats) {
+        self.rays += other.rays;
+        self.hits += other.hits;
+        self.tiles += other.tiles;
+    }
+}
+
+// block of pixels, columns x0..x1 and rows y0..y1 counted from the bottom left of the image
+#[derive(Debug, Clone, Copy)]
+struct Tile {
+    x0: i32,
+    y0: i32,
+    x1: i32,
+    y1: i32,
+}
+
+#[derive(Clone)]
 pub struct Camera {
     pub line: Line,
     pub render_width: i32,
@@ -14,6 +60,14 @@ pub struct Camera {
     rotation: Quaternion,
     debug: String,
     pub backface_culling: bool,
+    pub projection: Projection,
+    // number of threads rendering tiles in parallel, 1 renders on the calling thread and 0 uses every core
+    pub threads: usize,
+    // rays per pixel of render_samples, spread over the pixel by pattern and combined by filter
+    pub samples: usize,
+    pub pattern: SamplePattern,
+    pub filter: Filter,
+    stats: RenderStats,
 }
 
 impl Camera {
@@ -28,39 +82,257 @@ impl Camera {
             rotation: Quaternion::identity(),
             debug: String::new(),
             backface_culling: false,
+            // one world unit per pixel
+            projection: Projection::Orthographic { width: width as f64, height: height as f64 },
+            threads: 1,
+            samples: 1,
+            pattern: SamplePattern::Regular,
+            filter: Filter::Box,
+            stats: RenderStats::default(),
         }
     }
 
-    pub fn render(&mut self, object: &Object) -> Vec<RayCastHit> {
+    // orthographic camera whose viewport covers (width, height) world units, no matter how many pixels are rendered
+    pub fn new_orthographic(camera_position: Vector, camera_look_direction: Vector, width: i32, height: i32, up: Vector, right: Vector, viewport: (f64, f64)) -> Camera {
+        let mut camera = Camera::new(camera_position, camera_look_direction, width, height, up, right);
+        camera.projection = Projection::Orthographic { width: viewport.0, height: viewport.1 };
+        camera
+    }
+
+    // perspective camera, aspect ratio is taken from the render size
+    pub fn new_perspective(camera_position: Vector, camera_look_direction: Vector, width: i32, height: i32, up: Vector, right: Vector, fov_y: f64) -> Camera {
+        let mut camera = Camera::new(camera_position, camera_look_direction, width, height, up, right);
+        camera.projection = Projection::Perspective { fov_y, aspect: width as f64 / height as f64 };
+        camera
+    }
+
+    // closest hit of every pixel on the objects of the scene. bvh has to be built from the scene
+    pub fn render(&mut self, scene: &Scene, bvh: &SceneBvh) -> Vec<RayCastHit> {
+        let backface_culling = self.backface_culling;
+        self.render_pixels(|l, pos_on_screen, stats| {
+            let mut hit = l.intersection_scene(scene, bvh, &backface_culling);
+            hit.pos_on_screen = pos_on_screen;
+            if hit.is_some() {
+                stats.hits += 1;
+            }
+            hit
+        })
+    }
+
+    // calls pixel with the primary ray and pos_on_screen of every pixel, tile by tile on self.threads threads.
+    // The results are in the same order as the hits of render, row by row from the bottom left of the image.
+    // pixel counts its hits (and any extra rays it traces) in the stats it is given
+    pub fn render_pixels<T, F>(&mut self, pixel: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(&Line, (i32, i32), &mut RenderStats) -> T + Sync,
+    {
         // THIS IS JUST TO ROTATE THE CAMERA ONCE PER RENDER WITHOUT IT SPINNING AROUND
-        let mut l = self.line.clone();
-        let mut point = self.line.point;
-        point.rotate_by_quaternion(&self.rotation);
-        l.point.rotate_by_quaternion(&self.rotation);
-        l.direction.rotate_by_quaternion(&self.rotation);
-        let mut up = self.up.clone();
-        let mut right = self.right.clone();
-        up.rotate_by_quaternion(&self.rotation);
-        right.rotate_by_quaternion(&self.rotation);
+        let view = self.view_line();
+        let (up, right) = self.view_axes();
+        let (results, stats, threads) = self.render_tiles(|j, i, stats| {
+            let (x, y) = self.pixel_center(j, i);
+            let l = self.primary_ray(&view, &up, &right, x, y);
+            stats.rays += 1;
+            pixel(&l, (j, i), stats)
+        });
+        self.finish_render(stats, threads);
+        results
+    }
+
+    // anti-aliased version of render_pixels, calls sample with self.samples primary rays per pixel and
+    // combines the colors it returns with self.filter. sample also gets a random number generator that is
+    // seeded from seed and the pixel, so the same seed gives the same image on any number of threads
+    pub fn render_samples<F>(&mut self, seed: u64, sample: F) -> Vec<Color>
+    where
+        F: Fn(&Line, (i32, i32), &mut Rng, &mut RenderStats) -> Color + Sync,
+    {
+        let view = self.view_line();
+        let (up, right) = self.view_axes();
+        let radius = self.filter.radius();
+        let (pixel_width, pixel_height) = (2.0 / self.render_width as f64, 2.0 / self.render_height as f64);
+        let (results, stats, threads) = self.render_tiles(|j, i, stats| {
+            let (x, y) = self.pixel_center(j, i);
+            let pixel = ((j as u32 as u64) << 32) | i as u32 as u64;
+            let mut rng = Rng::new(splitmix(pixel) ^ splitmix(seed));
+
+            let mut sum = Color::black();
+            let mut plain_sum = Color::black();
+            let mut weights = 0.0;
+            let points = self.pattern.points(self.samples.max(1), &mut rng);
+            for &(u, v) in &points {
+                // the points cover the whole filter, which can be wider than the pixel
+                let (dx, dy) = ((u * 2.0 - 1.0) * radius, (v * 2.0 - 1.0) * radius);
+                let l = self.primary_ray(&view, &up, &right, x + dx * pixel_width, y + dy * pixel_height);
+                stats.rays += 1;
+                let color = sample(&l, (j, i), &mut rng, stats);
+                let weight = self.filter.weight(dx, dy);
+                sum += color * weight;
+                plain_sum += color;
+                weights += weight;
+            }
+            // all samples landed where the filter is 0, count them the same instead
+            if weights > 0.0 {
+                sum * (1.0 / weights)
+            } else {
+                plain_sum * (1.0 / points.len() as f64)
+            }
+        });
+        self.finish_render(stats, threads);
+        results
+    }
+
+    // calls pixel with the pos_on_screen of every pixel, tile by tile on self.threads threads,
+    // and puts the results back together row by row. Returns the merged stats and the number of threads used
+    fn render_tiles<T, F>(&self, pixel: F) -> (Vec<T>, RenderStats, usize)
+    where
+        T: Send,
+        F: Fn(i32, i32, &mut RenderStats) -> T + Sync,
+    {
+        let tiles = self.tiles();
+        let threads = self.thread_count().min(tiles.len());
+
+        // tiles are handed out one at a time, so threads that get cheap tiles pick up more of them
+        let next_tile = AtomicUsize::new(0);
+        let work = || {
+            let mut done = Vec::new();
+            loop {
+                let k = next_tile.fetch_add(1, Ordering::Relaxed);
+                if k >= tiles.len() {
+                    break;
+                }
+                let (results, stats) = self.render_tile(&tiles[k], &pixel);
+                done.push((tiles[k], results, stats));
+            }
+            done
+        };
+        let rendered: Vec<(Tile, Vec<T>, RenderStats)> = if threads <= 1 {
+            work()
+        } else {
+            thread::scope(|scope| {
+                let workers: Vec<_> = (0..threads).map(|_| scope.spawn(work)).collect();
+                workers.into_iter().flat_map(|worker| worker.join().expect("render thread panicked")).collect()
+            })
+        };
+
+        // put the tiles back together in the same row by row order as a single pass over the image
+        let width = self.render_width as usize;
+        let mut pixels: Vec<Option<T>> = (0..width * self.render_height as usize).map(|_| None).collect();
+        let mut stats = RenderStats::default();
+        for (tile, results, tile_stats) in rendered {
+            let tile_width = (tile.x1 - tile.x0) as usize;
+            for (k, result) in results.into_iter().enumerate() {
+                let row = tile.y0 as usize + k / tile_width;
+                let column = tile.x0 as usize + k % tile_width;
+                pixels[row * width + column] = Some(result);
+            }
+            stats.merge(&tile_stats);
+        }
+        let pixels = pixels.into_iter().map(|result| result.expect("every pixel is covered by a tile")).collect();
+        (pixels, stats, threads.max(1))
+    }
+
+    // renders the pixels of one tile, row by row
+    fn render_tile<T, F>(&self, tile: &Tile, pixel: &F) -> (Vec<T>, RenderStats)
+    where
+        F: Fn(i32, i32, &mut RenderStats) -> T,
+    {
+        let mut stats = RenderStats { tiles: 1, ..RenderStats::default() };
+        let mut results = Vec::with_capacity(((tile.x1 - tile.x0) * (tile.y1 - tile.y0)) as usize);
+        for row in tile.y0..tile.y1 {
+            for column in tile.x0..tile.x1 {
+                results.push(pixel(column - self.render_width / 2, row - self.render_height / 2, &mut stats));
+            }
+        }
+        (results, stats)
+    }
+
+    // keeps the stats of a render and writes them to the debug info
+    fn finish_render(&mut self, stats: RenderStats, threads: usize) {
+        let l = self.view_line();
+        let (up, right) = self.view_axes();
 
         self.debug.clear();
         self.debug.push_str(&format!("Camera position: {}\n", l.point.to_string()));
         self.debug.push_str(&format!("Camera direction: {}\n", l.direction.to_string()));
         self.debug.push_str(&format!("Camera up: {}\n", up.to_string()));
         self.debug.push_str(&format!("Camera right: {}\n", right.to_string()));
+        self.debug.push_str(&format!("Camera projection: {:?}\n", self.projection));
+        self.debug.push_str(&format!("Rendered {} rays in {} tiles on {} threads, {} hits\n", stats.rays, stats.tiles, threads, stats.hits));
+        self.stats = stats;
+    }
 
-        // ###########################################################################
+    // ray through (x, y) on the image plane, both in range [-1, 1]. view, up and right are the rotated camera vectors
+    pub fn primary_ray(&self, view: &Line, up: &Vector, right: &Vector, x: f64, y: f64) -> Line {
+        match self.projection {
+            Projection::Orthographic { width, height } => {
+                Line::new(view.point + *up * (y * height * 0.5) + *right * (x * width * 0.5), view.direction)
+            }
+            Projection::Perspective { fov_y, aspect } => {
+                let half_height = (fov_y * 0.5).tan();
+                let half_width = half_height * aspect;
+                let mut direction = view.direction + *up * (y * half_height) + *right * (x * half_width);
+                direction.normalize();
+                Line::new(view.point, direction)
+            }
+        }
+    }
 
-        let mut hits: Vec<RayCastHit> = Vec::new();
-        for i in (-self.render_height / 2)..(self.render_height / 2) {
-            for j in (-self.render_width / 2)..(self.render_width / 2) {
-                l.point = point + up * i as f64 + right * j as f64;
-                let mut hit = l.intersection_object(&object, &l.point, &self.backface_culling);
-                hit.pos_on_screen = (j, i);
-                hits.push(hit);
+    // splits the image into TILE_SIZE x TILE_SIZE tiles, the last row and column of tiles can be smaller
+    fn tiles(&self) -> Vec<Tile> {
+        let mut tiles = Vec::new();
+        for y0 in (0..self.render_height).step_by(TILE_SIZE as usize) {
+            for x0 in (0..self.render_width).step_by(TILE_SIZE as usize) {
+                tiles.push(Tile {
+                    x0,
+                    y0,
+                    x1: (x0 + TILE_SIZE).min(self.render_width),
+                    y1: (y0 + TILE_SIZE).min(self.render_height),
+                });
             }
         }
-        hits
+        tiles
+    }
+
+    // threads used by render, 0 means one per core
+    fn thread_count(&self) -> usize {
+        if self.threads == 0 {
+            thread::available_parallelism().map_or(1, |n| n.get())
+        } else {
+            self.threads
+        }
+    }
+
+    // counters of the last render
+    pub fn stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    // camera position and look direction with the camera rotation applied
+    pub fn view_line(&self) -> Line {
+        let mut l = self.line;
+        l.point.rotate_by_quaternion(&self.rotation);
+        l.direction.rotate_by_quaternion(&self.rotation);
+        l
+    }
+
+    // up and right vectors with the camera rotation applied
+    pub fn view_axes(&self) -> (Vector, Vector) {
+        let mut up = self.up;
+        let mut right = self.right;
+        up.rotate_by_quaternion(&self.rotation);
+        right.rotate_by_quaternion(&self.rotation);
+        (up, right)
+    }
+
+    // position of the center of pixel (j, i) on the image plane, both coordinates in range [-1, 1].
+    // j and i are the same as in pos_on_screen, counted from the center of the image
+    pub fn pixel_center(&self, j: i32, i: i32) -> (f64, f64) {
+        let column = (j + self.render_width / 2) as f64;
+        let row = (i + self.render_height / 2) as f64;
+        let x = (column + 0.5) / self.render_width as f64 * 2.0 - 1.0;
+        let y = (row + 0.5) / self.render_height as f64 * 2.0 - 1.0;
+        (x, y)
     }
 
     pub fn set_camera_position(&mut self, v: &Vector) {
@@ -83,4 +355,127 @@ impl Camera {
     pub fn get_debug_info(&self) -> String {
         self.debug.clone()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::as_radians;
+    use crate::object::Object;
+
+    fn count_hits(camera: &mut Camera, scene: &Scene) -> usize {
+        camera.render(scene, &scene.bvh()).iter().filter(|hit| hit.is_some()).count()
+    }
+
+    #[test]
+    fn orthographic_size_does_not_depend_on_distance() {
+        let cube = Scene::from_object("cube", Object::cube(5.0));
+        let mut near = Camera::new(Vector::new(0.0, 0.0, 20.0), Vector::new(0.0, 0.0, -1.0), 40, 40, Vector::new(0.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let mut far = Camera::new(Vector::new(0.0, 0.0, 80.0), Vector::new(0.0, 0.0, -1.0), 40, 40, Vector::new(0.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        assert_eq!(count_hits(&mut near, &cube), count_hits(&mut far, &cube));
+    }
+
+    #[test]
+    fn resolution_does_not_change_framing() {
+        let cube = Scene::from_object("cube", Object::cube(15.0));
+        let mut low = Camera::new_orthographic(Vector::new(0.0, 0.0, 50.0), Vector::new(0.0, 0.0, -1.0), 60, 60, Vector::new(0.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0), (60.0, 60.0));
+        let mut high = Camera::new_orthographic(Vector::new(0.0, 0.0, 50.0), Vector::new(0.0, 0.0, -1.0), 600, 600, Vector::new(0.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0), (60.0, 60.0));
+        // the cube covers the middle 30x30 units of a 60x60 viewport, so a quarter of the image
+        assert_eq!(count_hits(&mut low, &cube), 30 * 30);
+        assert_eq!(count_hits(&mut high, &cube), 300 * 300);
+    }
+
+    #[test]
+    fn odd_resolution_renders_every_pixel() {
+        let cube = Scene::from_object("cube", Object::cube(5.0));
+        let mut camera = Camera::new(Vector::new(0.0, 0.0, 20.0), Vector::new(0.0, 0.0, -1.0), 7, 5, Vector::new(0.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        assert_eq!(camera.render(&cube, &cube.bvh()).len(), 35);
+        let (x, y) = camera.pixel_center(-3, -2);
+        assert!((x + 6.0 / 7.0).abs() < 1e-12);
+        assert!((y + 4.0 / 5.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn perspective_far_objects_are_smaller() {
+        let cube = Scene::from_object("cube", Object::cube(5.0));
+        let mut near = Camera::new_perspective(Vector::new(0.0, 0.0, 20.0), Vector::new(0.0, 0.0, -1.0), 40, 40, Vector::new(0.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0), as_radians(60.0));
+        let mut far = Camera::new_perspective(Vector::new(0.0, 0.0, 80.0), Vector::new(0.0, 0.0, -1.0), 40, 40, Vector::new(0.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0), as_radians(60.0));
+        let near_hits = count_hits(&mut near, &cube);
+        let far_hits = count_hits(&mut far, &cube);
+        assert!(far_hits > 0);
+        assert!(near_hits > far_hits * 4);
+    }
+
+    #[test]
+    fn parallel_render_matches_serial() {
+        let scene = Scene::from_object("showcase", Object::showcase());
+        // odd size so the last row and column of tiles are partial
+        let mut camera = Camera::new_perspective(Vector::new(3.0, -2.0, 70.0), Vector::new(0.0, 0.0, -1.0), 101, 67, Vector::new(0.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0), as_radians(50.0));
+        let bvh = scene.bvh();
+        let serial = camera.render(&scene, &bvh);
+        let serial_stats = camera.stats();
+        camera.threads = 4;
+        let parallel = camera.render(&scene, &bvh);
+        assert_eq!(camera.stats(), serial_stats);
+        assert_eq!(serial_stats.rays, 101 * 67);
+        assert_eq!(serial_stats.tiles, 4 * 3);
+        assert_eq!(serial_stats.hits, serial.iter().filter(|hit| hit.is_some()).count());
+        assert!(serial_stats.hits > 0);
+
+        assert_eq!(serial.len(), parallel.len());
+        for (a, b) in serial.iter().zip(parallel.iter()) {
+            assert_eq!(a.pos_on_screen, b.pos_on_screen);
+            assert_eq!(a.t(), b.t());
+            assert_eq!(a.hit().map(|hit| hit.shape_index), b.hit().map(|hit| hit.shape_index));
+        }
+        // row by row from the bottom left
+        assert_eq!(serial[0].pos_on_screen, (-50, -33));
+        assert_eq!(serial[1].pos_on_screen, (-49, -33));
+        assert_eq!(serial[101].pos_on_screen, (-50, -32));
+    }
+
+    #[test]
+    fn supersampling_covers_edges() {
+        // one world unit per pixel, the sides of the cube go through the middle of a quarter of a pixel
+        let cube = Object::cube(5.25);
+        let bvh = cube.bvh();
+        let mut camera = Camera::new(Vector::new(0.0, 0.0, 20.0), Vector::new(0.0, 0.0, -1.0), 20, 20, Vector::new(0.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let coverage = |camera: &mut Camera| {
+            let colors = camera.render_samples(7, |l, _, _, _| {
+                if l.intersection_bvh(&cube, &bvh, &false).is_some() { Color::white() } else { Color::black() }
+            });
+            // pixels next to the right edge inside the cube, on the edge and outside, counted from the bottom left
+            let at = |j: usize, i: usize| colors[i * 20 + j].r;
+            (at(14, 10), at(15, 10), at(16, 10))
+        };
+
+        // a single ray through the pixel center misses the edge
+        assert_eq!(coverage(&mut camera), (1.0, 0.0, 0.0));
+
+        camera.samples = 16;
+        assert_eq!(coverage(&mut camera), (1.0, 0.25, 0.0));
+        assert_eq!(camera.stats().rays, 16 * 20 * 20);
+
+        camera.pattern = SamplePattern::Jittered;
+        let (_, edge, _) = coverage(&mut camera);
+        assert!(edge > 0.0 && edge < 0.5);
+        // the random spots come from the seed, not from the threads
+        camera.threads = 3;
+        assert_eq!(coverage(&mut camera).1, edge);
+
+        // wider filters reach into the pixels next to the edge, weighted less the further they go
+        camera.pattern = SamplePattern::Halton;
+        camera.filter = Filter::Tent { radius: 1.0 };
+        let (inside, edge, outside) = coverage(&mut camera);
+        assert!(inside > 0.9 && inside < 1.0);
+        // the tent from 5.5 - 1 to 5.5 + 1 is covered up to 5.25, which is 9 / 32 of its area
+        assert!((edge - 9.0 / 32.0).abs() < 0.05, "{}", edge);
+        assert_eq!(outside, 0.0);
+
+        // filters that were never validated give every sample at the pixel center the same weight
+        for filter in [Filter::Tent { radius: 0.0 }, Filter::Gaussian { radius: f64::NAN, sigma: -1.0 }] {
+            camera.filter = filter;
+            assert_eq!(coverage(&mut camera), (1.0, 0.0, 0.0));
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::ops;
+
+// linear RGB color, components are in range [0, 1] for displayable colors but can go above that
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+}
+
+impl Color {
+    pub fn new(r: f64, g: f64, b: f64) -> Color {
+        Color { r, g, b }
+    }
+
+    pub fn black() -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    pub fn white() -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
+    // create a linear color from 8 bit sRGB values, like the ones used by color pickers
+    pub fn from_srgb8(r: u8, g: u8, b: u8) -> Color {
+        Color::from_srgb(r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0)
+    }
+
+    // create a linear color from sRGB values in [0, 1]
+    pub fn from_srgb(r: f64, g: f64, b: f64) -> Color {
+        Color::new(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b))
+    }
+
+    // convert to 8 bit sRGB values, components outside [0, 1] are clamped
+    pub fn to_srgb8(&self) -> [u8; 3] {
+        let encode = |c: f64| (linear_to_srgb(c.clamp(0.0, 1.0)) * 255.0).round() as u8;
+        [encode(self.r), encode(self.g), encode(self.b)]
+    }
+
+    // perceived brightness of the color
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    // linear interpolation, self at t = 0 and other at t = 1
+    pub fn lerp(&self, other: &Color, t: f64) -> Color {
+        *self * (1.0 - t) + *other * t
+    }
+}
+
+// sRGB transfer functions, see https://en.wikipedia.org/wiki/SRGB#Transformation
+fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f64) -> f64 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// + operator overload
+impl ops::Add<Color> for Color {
+    type Output = Color;
+
+    fn add(self, other: Color) -> Color {
+        Color::new(self.r + other.r, self.g + other.g, self.b + other.b)
+    }
+}
+
+impl ops::AddAssign<Color> for Color {
+    fn add_assign(&mut self, other: Color) {
+        self.r += other.r;
+        self.g += other.g;
+        self.b += other.b;
+    }
+}
+
+// - operator overload
+impl ops::Sub<Color> for Color {
+    type Output = Color;
+
+    fn sub(self, other: Color) -> Color {
+        Color::new(self.r - other.r, self.g - other.g, self.b - other.b)
+    }
+}
+
+// * operator overload
+// component-wise multiply, used to filter light by a surface color
+impl ops::Mul<Color> for Color {
+    type Output = Color;
+
+    fn mul(self, other: Color) -> Color {
+        Color::new(self.r * other.r, self.g * other.g, self.b * other.b)
+    }
+}
+
+// scalar multiply ([color] * scalar)
+impl ops::Mul<f64> for Color {
+    type Output = Color;
+
+    fn mul(self, scalar: f64) -> Color {
+        Color::new(self.r * scalar, self.g * scalar, self.b * scalar)
+    }
+}
+
+impl ops::MulAssign<f64> for Color {
+    fn mul_assign(&mut self, scalar: f64) {
+        self.r *= scalar;
+        self.g *= scalar;
+        self.b *= scalar;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn srgb_round_trip_test() {
+        for value in [0u8, 1, 10, 100, 172, 210, 255] {
+            let color = Color::from_srgb8(value, value, value);
+            assert_eq!(color.to_srgb8(), [value, value, value]);
+        }
+    }
+
+    #[test]
+    fn clamp_test() {
+        let color = Color::new(2.0, -1.0, 0.5);
+        let [r, g, _] = color.to_srgb8();
+        assert_eq!(r, 255);
+        assert_eq!(g, 0);
+    }
+
+    #[test]
+    fn ops_test() {
+        let c1 = Color::new(0.5, 0.25, 1.0);
+        let c2 = Color::new(0.5, 1.0, 0.0);
+        assert_eq!(c1 * c2, Color::new(0.25, 0.25, 0.0));
+        assert_eq!(c1 + c2, Color::new(1.0, 1.25, 1.0));
+        assert_eq!(c1 * 2.0, Color::new(1.0, 0.5, 2.0));
+    }
+}
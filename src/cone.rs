@@ -0,0 +1,156 @@
+use std::f64::consts::PI;
+
+use crate::aabb::Aabb;
+use crate::disk::{circle_points, disk_bounds};
+use crate::line::Line;
+use crate::math::solve_quadratic;
+use crate::quaternion::Quaternion;
+use crate::shape::{Shape, MIN_T};
+use crate::triangle::{triangulate_fan, triangulate_grid, Triangle};
+use crate::vector::Vector;
+
+// finite cone with the tip at apex, opening along axis (which must be normalized).
+// The base circle of the given radius is height units away from the apex, if capped it is closed with a disk.
+#[derive(Debug, Clone, Copy)]
+pub struct Cone {
+    pub apex: Vector,
+    pub axis: Vector,
+    pub radius: f64,
+    pub height: f64,
+    pub capped: bool,
+}
+
+impl Cone {
+    pub fn new(apex: Vector, axis: Vector, radius: f64, height: f64, capped: bool) -> Cone {
+        Cone { apex, axis, radius, height, capped }
+    }
+
+    // squared cosine of the half angle at the apex
+    fn cos_squared(&self) -> f64 {
+        self.height * self.height / (self.height * self.height + self.radius * self.radius)
+    }
+}
+
+impl Shape for Cone {
+    // returns the smallest t > 0 for which line.point_on_line(t) lies on the cone
+    fn intersect(&self, line: &Line) -> Option<f64> {
+        let o = line.point - self.apex;
+        let d = line.direction;
+        let o_along = o.dot(&self.axis);
+        let d_along = d.dot(&self.axis);
+        let cos2 = self.cos_squared();
+
+        // points on the infinite double cone satisfy (v . axis)^2 = cos^2 * |v|^2,
+        // limiting it to 0 <= h <= height removes the mirrored half
+        let a = d_along * d_along - cos2 * d.length_squared();
+        let b = 2.0 * (d_along * o_along - cos2 * d.dot(&o));
+        let c = o_along * o_along - cos2 * o.length_squared();
+        let side = solve_quadratic([c, b, a]).into_iter().filter(|t| {
+            let h = o_along + d_along * t;
+            *t > MIN_T && (0.0..=self.height).contains(&h)
+        });
+
+        let mut cap = None;
+        if self.capped && d_along.abs() > 1e-12 {
+            let t = (self.height - o_along) / d_along;
+            let radial = o + d * t - self.axis * self.height;
+            if t > MIN_T && radial.length_squared() <= self.radius * self.radius {
+                cap = Some(t);
+            }
+        }
+
+        side.chain(cap).fold(None, |closest: Option<f64>, t| Some(closest.map_or(t, |c| c.min(t))))
+    }
+
+    // outward facing normal at a point on the cone
+    fn normal_at(&self, point: &Vector) -> Vector {
+        let v = *point - self.apex;
+        let h = v.dot(&self.axis);
+        let epsilon = 1e-9 * self.height.max(1.0);
+        let radial = v - self.axis * h;
+        if self.capped && h >= self.height - epsilon && radial.length() < self.radius - epsilon {
+            return self.axis;
+        }
+        let mut n = v * self.cos_squared() - self.axis * h;
+        n.normalize();
+        n
+    }
+
+    // u is the angle around the axis. On the side v goes from the apex (0) to the base (1),
+    // on the cap it is the distance from the center (0) to the edge (1)
+    fn uv_at(&self, point: &Vector) -> (f64, f64) {
+        let (e1, e2) = self.axis.orthonormal_basis();
+        let v = *point - self.apex;
+        let h = v.dot(&self.axis);
+        let radial = v - self.axis * h;
+        let u = 0.5 + radial.dot(&e2).atan2(radial.dot(&e1)) / (2.0 * PI);
+        if self.normal_at(point) == self.axis {
+            (u, radial.length() / self.radius)
+        } else {
+            (u, h / self.height)
+        }
+    }
+
+    fn bounds(&self) -> Aabb {
+        let mut bounds = disk_bounds(&(self.apex + self.axis * self.height), &self.axis, self.radius);
+        bounds.expand(&self.apex);
+        bounds
+    }
+
+    fn rotate(&mut self, q: &Quaternion) {
+        self.apex.rotate_by_quaternion(q);
+        self.axis.rotate_by_quaternion(q);
+    }
+
+    fn scale(&mut self, s: &f64) {
+        self.apex *= *s;
+        self.radius *= s.abs();
+        self.height *= s.abs();
+        if *s < 0.0 {
+            self.axis *= -1.0;
+        }
+    }
+
+    fn triangles(&self, segments: usize) -> Option<Vec<Triangle>> {
+        let segments = segments.max(3);
+        let base = self.apex + self.axis * self.height;
+        let ring = circle_points(&base, &self.axis, self.radius, segments);
+        let mut triangles = triangulate_grid(&[vec![self.apex; segments + 1], ring.clone()]);
+        if self.capped {
+            triangles.extend(triangulate_fan(&base, &ring));
+        }
+        Some(triangles)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 45 degree cone pointing up, base at y = 0
+    fn cone() -> Cone {
+        Cone::new(Vector::new(0.0, 2.0, 0.0), Vector::new(0.0, -1.0, 0.0), 2.0, 2.0, true)
+    }
+
+    #[test]
+    fn side_test() {
+        let line = Line::new(Vector::new(0.0, 1.0, 10.0), Vector::new(0.0, 0.0, -1.0));
+        let t = cone().intersect(&line).unwrap();
+        assert!((t - 9.0).abs() < 1e-12);
+        let mut expected = Vector::new(0.0, 1.0, 1.0);
+        expected.normalize();
+        let n = cone().normal_at(&line.point_on_line(&t));
+        assert!((n - expected).length() < 1e-12);
+        // the mirrored half of the double cone is not hit
+        let above = Line::new(Vector::new(0.0, 3.0, 10.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(cone().intersect(&above).is_none());
+    }
+
+    #[test]
+    fn cap_test() {
+        let line = Line::new(Vector::new(0.5, -5.0, 0.0), Vector::new(0.0, 1.0, 0.0));
+        let t = cone().intersect(&line).unwrap();
+        assert_eq!(t, 5.0);
+        assert_eq!(cone().normal_at(&line.point_on_line(&t)), Vector::new(0.0, -1.0, 0.0));
+    }
+}
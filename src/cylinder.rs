@@ -0,0 +1,160 @@
+use std::f64::consts::PI;
+
+use crate::aabb::Aabb;
+use crate::disk::{circle_points, disk_bounds};
+use crate::line::Line;
+use crate::math::solve_quadratic;
+use crate::quaternion::Quaternion;
+use crate::shape::{Shape, MIN_T};
+use crate::triangle::{triangulate_fan, triangulate_grid, Triangle};
+use crate::vector::Vector;
+
+// finite cylinder starting at base and going height units along axis (which must be normalized).
+// If capped, both ends are closed with disks.
+#[derive(Debug, Clone, Copy)]
+pub struct Cylinder {
+    pub base: Vector,
+    pub axis: Vector,
+    pub radius: f64,
+    pub height: f64,
+    pub capped: bool,
+}
+
+impl Cylinder {
+    pub fn new(base: Vector, axis: Vector, radius: f64, height: f64, capped: bool) -> Cylinder {
+        Cylinder { base, axis, radius, height, capped }
+    }
+}
+
+impl Shape for Cylinder {
+    // returns the smallest t > 0 for which line.point_on_line(t) lies on the cylinder
+    fn intersect(&self, line: &Line) -> Option<f64> {
+        let o = line.point - self.base;
+        let d = line.direction;
+        let o_along = o.dot(&self.axis);
+        let d_along = d.dot(&self.axis);
+
+        // the side is an infinite cylinder limited to 0 <= h <= height
+        let o_perp = o - self.axis * o_along;
+        let d_perp = d - self.axis * d_along;
+        let a = d_perp.length_squared();
+        let b = 2.0 * o_perp.dot(&d_perp);
+        let c = o_perp.length_squared() - self.radius * self.radius;
+        let side = solve_quadratic([c, b, a]).into_iter().filter(|t| {
+            let h = o_along + d_along * t;
+            *t > MIN_T && (0.0..=self.height).contains(&h)
+        });
+
+        let mut caps = Vec::new();
+        if self.capped && d_along.abs() > 1e-12 {
+            for h in [0.0, self.height] {
+                let t = (h - o_along) / d_along;
+                let radial = o_perp + d_perp * t;
+                if t > MIN_T && radial.length_squared() <= self.radius * self.radius {
+                    caps.push(t);
+                }
+            }
+        }
+
+        side.chain(caps).fold(None, |closest: Option<f64>, t| Some(closest.map_or(t, |c| c.min(t))))
+    }
+
+    // outward facing normal at a point on the cylinder
+    fn normal_at(&self, point: &Vector) -> Vector {
+        let d = *point - self.base;
+        let h = d.dot(&self.axis);
+        let mut radial = d - self.axis * h;
+        let epsilon = 1e-9 * self.height.max(1.0);
+        if self.capped && radial.length() < self.radius - epsilon {
+            if h <= epsilon {
+                return self.axis * -1.0;
+            }
+            if h >= self.height - epsilon {
+                return self.axis;
+            }
+        }
+        radial.normalize();
+        radial
+    }
+
+    // u is the angle around the axis. On the side v goes from the base (0) to the top (1),
+    // on the caps it is the distance from the center (0) to the edge (1)
+    fn uv_at(&self, point: &Vector) -> (f64, f64) {
+        let (e1, e2) = self.axis.orthonormal_basis();
+        let d = *point - self.base;
+        let h = d.dot(&self.axis);
+        let radial = d - self.axis * h;
+        let u = 0.5 + radial.dot(&e2).atan2(radial.dot(&e1)) / (2.0 * PI);
+        let n = self.normal_at(point);
+        if n == self.axis || n == self.axis * -1.0 {
+            (u, radial.length() / self.radius)
+        } else {
+            (u, h / self.height)
+        }
+    }
+
+    fn bounds(&self) -> Aabb {
+        let base = disk_bounds(&self.base, &self.axis, self.radius);
+        let top = disk_bounds(&(self.base + self.axis * self.height), &self.axis, self.radius);
+        base.union(&top)
+    }
+
+    fn rotate(&mut self, q: &Quaternion) {
+        self.base.rotate_by_quaternion(q);
+        self.axis.rotate_by_quaternion(q);
+    }
+
+    fn scale(&mut self, s: &f64) {
+        self.base *= *s;
+        self.radius *= s.abs();
+        self.height *= s.abs();
+        if *s < 0.0 {
+            self.axis *= -1.0;
+        }
+    }
+
+    fn triangles(&self, segments: usize) -> Option<Vec<Triangle>> {
+        let segments = segments.max(3);
+        let top = self.base + self.axis * self.height;
+        let bottom_ring = circle_points(&self.base, &self.axis, self.radius, segments);
+        let top_ring = circle_points(&top, &self.axis, self.radius, segments);
+        let mut triangles = triangulate_grid(&[bottom_ring.clone(), top_ring.clone()]);
+        if self.capped {
+            triangles.extend(triangulate_fan(&top, &top_ring));
+            let reversed: Vec<Vector> = bottom_ring.into_iter().rev().collect();
+            triangles.extend(triangulate_fan(&self.base, &reversed));
+        }
+        Some(triangles)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cylinder(capped: bool) -> Cylinder {
+        Cylinder::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0), 1.0, 4.0, capped)
+    }
+
+    #[test]
+    fn side_test() {
+        let line = Line::new(Vector::new(0.0, 2.0, 10.0), Vector::new(0.0, 0.0, -1.0));
+        let t = cylinder(false).intersect(&line).unwrap();
+        assert_eq!(t, 9.0);
+        assert_eq!(cylinder(false).normal_at(&line.point_on_line(&t)), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(cylinder(false).uv_at(&line.point_on_line(&t)).1, 0.5);
+        // above the top
+        let miss = Line::new(Vector::new(0.0, 5.0, 10.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(cylinder(true).intersect(&miss).is_none());
+    }
+
+    #[test]
+    fn cap_test() {
+        let line = Line::new(Vector::new(0.5, 10.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        let t = cylinder(true).intersect(&line).unwrap();
+        assert_eq!(t, 6.0);
+        assert_eq!(cylinder(true).normal_at(&line.point_on_line(&t)), Vector::new(0.0, 1.0, 0.0));
+        // without caps the line goes through the tube
+        assert!(cylinder(false).intersect(&line).is_none());
+    }
+}
@@ -0,0 +1,107 @@
+use std::f64::consts::PI;
+
+use crate::aabb::Aabb;
+use crate::line::Line;
+use crate::quaternion::Quaternion;
+use crate::shape::{Shape, MIN_T};
+use crate::triangle::{triangulate_fan, Triangle};
+use crate::vector::Vector;
+
+// flat circle, the normal must be normalized
+#[derive(Debug, Clone, Copy)]
+pub struct Disk {
+    pub center: Vector,
+    pub normal: Vector,
+    pub radius: f64,
+}
+
+impl Disk {
+    pub fn new(center: Vector, normal: Vector, radius: f64) -> Disk {
+        Disk { center, normal, radius }
+    }
+}
+
+impl Shape for Disk {
+    // returns the smallest t > 0 for which line.point_on_line(t) lies on the disk
+    fn intersect(&self, line: &Line) -> Option<f64> {
+        let denominator = self.normal.dot(&line.direction);
+        if denominator.abs() < 1e-12 {
+            return None;
+        }
+        let t = (self.center - line.point).dot(&self.normal) / denominator;
+        if t <= MIN_T {
+            return None;
+        }
+        let p = line.point_on_line(&t);
+        if (p - self.center).length_squared() > self.radius * self.radius {
+            return None;
+        }
+        Some(t)
+    }
+
+    fn normal_at(&self, _point: &Vector) -> Vector {
+        self.normal
+    }
+
+    // polar coordinates, u is the angle around the normal, v the distance from the center (0) to the edge (1)
+    fn uv_at(&self, point: &Vector) -> (f64, f64) {
+        let (e1, e2) = self.normal.orthonormal_basis();
+        let d = *point - self.center;
+        let angle = d.dot(&e2).atan2(d.dot(&e1));
+        (0.5 + angle / (2.0 * PI), d.length() / self.radius)
+    }
+
+    fn bounds(&self) -> Aabb {
+        disk_bounds(&self.center, &self.normal, self.radius)
+    }
+
+    fn rotate(&mut self, q: &Quaternion) {
+        self.center.rotate_by_quaternion(q);
+        self.normal.rotate_by_quaternion(q);
+    }
+
+    fn scale(&mut self, s: &f64) {
+        self.center *= *s;
+        self.radius *= s.abs();
+    }
+
+    fn triangles(&self, segments: usize) -> Option<Vec<Triangle>> {
+        Some(triangulate_fan(&self.center, &circle_points(&self.center, &self.normal, self.radius, segments.max(3))))
+    }
+}
+
+// segments + 1 points going counter-clockwise around normal (which must be normalized) on a circle,
+// the last one is the first one again so the polyline is closed
+pub fn circle_points(center: &Vector, normal: &Vector, radius: f64, segments: usize) -> Vec<Vector> {
+    let (e1, e2) = normal.orthonormal_basis();
+    (0..=segments)
+        .map(|i| {
+            let angle = 2.0 * PI * (i % segments) as f64 / segments as f64;
+            *center + (e1 * angle.cos() + e2 * angle.sin()) * radius
+        })
+        .collect()
+}
+
+// bounding box of a disk, along each axis the circle extends radius * sin(angle between the axis and the normal)
+pub fn disk_bounds(center: &Vector, normal: &Vector, radius: f64) -> Aabb {
+    let extent = |n: f64| radius * (1.0 - n * n).max(0.0).sqrt();
+    let e = Vector::new(extent(normal.x), extent(normal.y), extent(normal.z));
+    Aabb::new(*center - e, *center + e)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn intersect_test() {
+        let disk = Disk::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0), 2.0);
+        let hit = Line::new(Vector::new(1.0, 1.0, 5.0), Vector::new(0.0, 0.0, -1.0));
+        assert_eq!(disk.intersect(&hit), Some(5.0));
+        assert_eq!(disk.uv_at(&hit.point_on_line(&5.0)).1, 2.0f64.sqrt() / 2.0);
+        let miss = Line::new(Vector::new(2.0, 1.0, 5.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(disk.intersect(&miss).is_none());
+        let behind = Line::new(Vector::new(1.0, 1.0, 5.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(disk.intersect(&behind).is_none());
+    }
+}
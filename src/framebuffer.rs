@@ -0,0 +1,281 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::raycasthit::RayCastHit;
+use crate::scene::{Scene, SceneBvh};
+
+// image of width x height pixels in linear RGB, with an optional depth channel.
+// Pixels are stored row by row, starting from the top left corner.
+#[derive(Debug, Clone)]
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    pub color: Vec<Color>,
+    pub depth: Option<Vec<f64>>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            color: vec![Color::black(); width * height],
+            depth: None,
+        }
+    }
+
+    // framebuffer with a depth channel, every pixel starts infinitely far away
+    pub fn with_depth(width: usize, height: usize) -> Framebuffer {
+        let mut fb = Framebuffer::new(width, height);
+        fb.depth = Some(vec![f64::INFINITY; width * height]);
+        fb
+    }
+
+    // image from the colors returned by Camera::render_samples, which go row by row from the bottom left
+    pub fn from_render(width: usize, height: usize, colors: Vec<Color>) -> Framebuffer {
+        let mut fb = Framebuffer::new(width, height);
+        for (k, color) in colors.into_iter().enumerate() {
+            fb.set(k % width, height - 1 - k / width, color);
+        }
+        fb
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        self.color[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Color) {
+        self.color[y * self.width + x] = color;
+    }
+
+    // returns None if the framebuffer was created without depth
+    pub fn get_depth(&self, x: usize, y: usize) -> Option<f64> {
+        self.depth.as_ref().map(|depth| depth[y * self.width + x])
+    }
+
+    pub fn clear(&mut self, color: Color) {
+        self.color.fill(color);
+        if let Some(depth) = &mut self.depth {
+            depth.fill(f64::INFINITY);
+        }
+    }
+
+    // converts pos_on_screen of a hit (counted from the center, y going up) to framebuffer coordinates
+    pub fn pixel_of(&self, pos_on_screen: (i32, i32)) -> Option<(usize, usize)> {
+        let (j, i) = pos_on_screen;
+        let x = j + self.width as i32 / 2;
+        let y = self.height as i32 - 1 - (i + self.height as i32 / 2);
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            None
+        } else {
+            Some((x as usize, y as usize))
+        }
+    }
+
+    // fills the framebuffer from the result of Camera::render.
+    // Hit pixels get the surface color scaled by RayCastHit::brightness, the rest gets the background.
+    // Depth is the distance from the camera along its look direction.
+    pub fn shade(&mut self, camera: &Camera, hits: &[RayCastHit], surface_color: Color, background: Color) {
+        self.clear(background);
+        let view = camera.view_line();
+        for hit in hits {
+            if let (Some(point), Some((x, y))) = (hit.point(), self.pixel_of(hit.pos_on_screen)) {
+                self.set(x, y, surface_color * hit.brightness());
+                let index = y * self.width + x;
+                if let Some(depth) = &mut self.depth {
+                    depth[index] = (point - view.point).dot(&view.direction);
+                }
+            }
+        }
+    }
+
+    // anti-aliased version of shade, renders the scene with camera.samples rays per pixel and the
+    // sample pattern and filter of the camera. The depth channel is left alone. bvh has to be built from the scene
+    pub fn shade_samples(&mut self, camera: &mut Camera, scene: &Scene, bvh: &SceneBvh, surface_color: Color, background: Color) {
+        let backface_culling = camera.backface_culling;
+        let colors = camera.render_samples(0, |l, _, _, stats| {
+            let hit = l.intersection_scene(scene, bvh, &backface_culling);
+            if hit.is_none() {
+                return background;
+            }
+            stats.hits += 1;
+            surface_color * hit.brightness()
+        });
+        self.color = Framebuffer::from_render(self.width, self.height, colors).color;
+    }
+
+    // 8 bit sRGB values of every pixel, three bytes per pixel
+    pub fn to_srgb8(&self) -> Vec<u8> {
+        self.color.iter().flat_map(|c| c.to_srgb8()).collect()
+    }
+
+    // binary PPM (P6)
+    pub fn write_ppm<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        out.write_all(&self.to_srgb8())
+    }
+
+    // ASCII PPM (P3), one row of pixels per line
+    pub fn write_ppm_ascii<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(out, "P3\n{} {}\n255\n", self.width, self.height)?;
+        for row in self.to_srgb8().chunks(self.width * 3) {
+            let line: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+            writeln!(out, "{}", line.join(" "))?;
+        }
+        Ok(())
+    }
+
+    // 8 bit RGB PNG
+    pub fn write_png<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(out, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer.write_image_data(&self.to_srgb8()).map_err(io::Error::other)?;
+        writer.finish().map_err(io::Error::other)
+    }
+
+    // saves the image, the format is picked from the file extension (.png or .ppm)
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+        let write: fn(&Framebuffer, &mut BufWriter<File>) -> io::Result<()> = match extension.as_deref() {
+            Some("png") => |fb, out| fb.write_png(out),
+            Some("ppm") => |fb, out| fb.write_ppm(out),
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unsupported image format: {}", path.display()))),
+        };
+        let mut file = BufWriter::new(File::create(path)?);
+        write(self, &mut file)?;
+        file.flush()
+    }
+}
+
+// running sum of frames rendered from the same view. A progressive renderer adds one noisy frame at a time
+// and shows the average, which gets closer to the converged image with every frame
+#[derive(Debug, Clone)]
+pub struct Accumulator {
+    pub width: usize,
+    pub height: usize,
+    sum: Vec<Color>,
+    frames: u32,
+}
+
+impl Accumulator {
+    pub fn new(width: usize, height: usize) -> Accumulator {
+        Accumulator { width, height, sum: vec![Color::black(); width * height], frames: 0 }
+    }
+
+    // number of frames added since the last reset
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    // forgets all frames, for example when the camera moved
+    pub fn reset(&mut self) {
+        self.sum.fill(Color::black());
+        self.frames = 0;
+    }
+
+    pub fn add(&mut self, frame: &Framebuffer) {
+        assert_eq!((frame.width, frame.height), (self.width, self.height), "frame size doesn't match the accumulator");
+        for (sum, color) in self.sum.iter_mut().zip(frame.color.iter()) {
+            *sum += *color;
+        }
+        self.frames += 1;
+    }
+
+    // average of the frames added so far, black before the first frame
+    pub fn image(&self) -> Framebuffer {
+        let mut fb = Framebuffer::new(self.width, self.height);
+        if self.frames > 0 {
+            let scale = 1.0 / self.frames as f64;
+            for (color, sum) in fb.color.iter_mut().zip(self.sum.iter()) {
+                *color = *sum * scale;
+            }
+        }
+        fb
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::object::Object;
+    use crate::vector::Vector;
+
+    fn checker() -> Framebuffer {
+        let mut fb = Framebuffer::new(2, 2);
+        fb.set(0, 0, Color::white());
+        fb.set(1, 1, Color::new(1.0, 0.0, 0.0));
+        fb
+    }
+
+    #[test]
+    fn ppm_test() {
+        let mut out = Vec::new();
+        checker().write_ppm(&mut out).unwrap();
+        let mut expected = b"P6\n2 2\n255\n".to_vec();
+        expected.extend_from_slice(&[255, 255, 255, 0, 0, 0, 0, 0, 0, 255, 0, 0]);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn ppm_ascii_test() {
+        let mut out = Vec::new();
+        checker().write_ppm_ascii(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "P3\n2 2\n255\n255 255 255 0 0 0\n0 0 0 255 0 0\n");
+    }
+
+    #[test]
+    fn png_test() {
+        let mut out = Vec::new();
+        checker().write_png(&mut out).unwrap();
+
+        let decoder = png::Decoder::new(out.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).unwrap();
+        assert_eq!((info.width, info.height), (2, 2));
+        assert_eq!(&data[..info.buffer_size()], checker().to_srgb8().as_slice());
+    }
+
+    #[test]
+    fn shade_test() {
+        let cube = Scene::from_object("cube", Object::cube(5.0));
+        let mut camera = Camera::new(Vector::new(0.0, 0.0, 20.0), Vector::new(0.0, 0.0, -1.0), 20, 20, Vector::new(0.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let hits = camera.render(&cube, &cube.bvh());
+        let mut fb = Framebuffer::with_depth(20, 20);
+        let background = Color::new(0.0, 0.0, 1.0);
+        fb.shade(&camera, &hits, Color::new(1.0, 0.0, 0.0), background);
+
+        assert_eq!(fb.get(0, 0), background);
+        assert_eq!(fb.get_depth(0, 0), Some(f64::INFINITY));
+        // the front face of the cube is seen head-on
+        assert_eq!(fb.get(10, 10), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(fb.get_depth(10, 10), Some(15.0));
+    }
+
+    #[test]
+    fn accumulator_test() {
+        let mut accumulator = Accumulator::new(2, 2);
+        assert_eq!(accumulator.image().get(0, 0), Color::black());
+        accumulator.add(&checker());
+        let mut frame = Framebuffer::new(2, 2);
+        frame.clear(Color::new(0.0, 0.0, 1.0));
+        accumulator.add(&frame);
+        assert_eq!(accumulator.frames(), 2);
+        let image = accumulator.image();
+        assert_eq!(image.get(0, 0), Color::new(0.5, 0.5, 1.0));
+        assert_eq!(image.get(1, 1), Color::new(0.5, 0.0, 0.5));
+        assert_eq!(image.get(1, 0), Color::new(0.0, 0.0, 0.5));
+
+        accumulator.reset();
+        assert_eq!(accumulator.frames(), 0);
+        accumulator.add(&frame);
+        assert_eq!(accumulator.image().get(0, 0), Color::new(0.0, 0.0, 1.0));
+    }
+}
@@ -0,0 +1,36 @@
+// math and ray casting library, the raylib viewer lives in main.rs behind the `gui` feature
+
+pub mod vector;
+pub mod point;
+pub mod mat4;
+pub mod math;
+pub mod quaternion;
+pub mod surface;
+pub mod line;
+pub mod object;
+pub mod camera;
+pub mod raycasthit;
+pub mod color;
+pub mod framebuffer;
+pub mod triangle;
+pub mod sphere;
+pub mod cylinder;
+pub mod cone;
+pub mod disk;
+pub mod torus;
+pub mod shape;
+pub mod aabb;
+pub mod bvh;
+pub mod light;
+pub mod material;
+pub mod raytracer;
+pub mod random;
+pub mod sampling;
+pub mod transform;
+pub mod pathtracer;
+pub mod scene;
+pub mod scenefile;
+pub mod mesh;
+pub mod obj;
+pub mod stl;
+pub mod ply;
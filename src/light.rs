@@ -0,0 +1,288 @@
+use crate::color::Color;
+use crate::line::Line;
+use crate::shape::Shape;
+use crate::surface::Surface;
+use crate::vector::Vector;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Light {
+    // light shining equally in every direction from a single position.
+    // The light reaching a point falls off with the square of its distance
+    Point { position: Vector, color: Color, intensity: f64 },
+    // light coming from infinitely far away along direction, like the sun. It doesn't fall off with distance
+    Directional { direction: Vector, color: Color, intensity: f64 },
+    // point light that only shines into a cone around direction. Inside inner_angle it is at full intensity,
+    // between inner_angle and outer_angle it fades out. Angles are in radians from the cone axis
+    Spot { position: Vector, direction: Vector, color: Color, intensity: f64, inner_angle: f64, outer_angle: f64 },
+    // rectangle of light on the side its normal points to, made from a bounded surface.
+    // It's sampled on a samples x samples grid, which gives soft shadows
+    Area { surface: Surface, color: Color, intensity: f64, samples: usize },
+}
+
+// what a point receives from a light
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightSample {
+    // normalized direction from the point towards the light
+    pub direction: Vector,
+    // distance to the light, shadow rays only look for blockers closer than this
+    pub distance: f64,
+    // color times intensity arriving at the point
+    pub radiance: Color,
+}
+
+impl Light {
+    pub fn point(position: Vector, color: Color, intensity: f64) -> Light {
+        Light::Point { position, color, intensity }
+    }
+
+    pub fn directional(direction: Vector, color: Color, intensity: f64) -> Light {
+        let mut direction = direction;
+        direction.normalize();
+        Light::Directional { direction, color, intensity }
+    }
+
+    pub fn spot(position: Vector, direction: Vector, color: Color, intensity: f64, inner_angle: f64, outer_angle: f64) -> Light {
+        let mut direction = direction;
+        direction.normalize();
+        Light::Spot { position, direction, color, intensity, inner_angle, outer_angle: outer_angle.max(inner_angle) }
+    }
+
+    // returns None if the surface is an infinite plane
+    pub fn area(surface: Surface, color: Color, intensity: f64, samples: usize) -> Option<Light> {
+        if !surface.is_bounded() {
+            return None;
+        }
+        Some(Light::Area { surface, color, intensity, samples: samples.max(1) })
+    }
+
+    // number of samples the renderer takes of the light for every point it shades
+    pub fn sample_count(&self) -> usize {
+        match *self {
+            Light::Area { samples, .. } => samples * samples,
+            _ => 1,
+        }
+    }
+
+    // true for lights that come from a single point or direction, rays sent out at random never hit them
+    pub fn is_delta(&self) -> bool {
+        !matches!(self, Light::Area { .. })
+    }
+
+    // size of the lit rectangle, 0 for delta lights
+    pub fn surface_area(&self) -> f64 {
+        match self {
+            Light::Area { surface, .. } => match (surface.v, surface.w, surface.max_v, surface.max_w) {
+                (Some(v), Some(w), Some((v0, v1)), Some((w0, w1))) => v.cross(&w).length() * (v1 - v0) * (w1 - w0),
+                _ => 0.0,
+            },
+            _ => 0.0,
+        }
+    }
+
+    // where the line hits an area light from the lit side, returns t and the light leaving the light towards the line start
+    pub fn intersect(&self, line: &Line) -> Option<(f64, Color)> {
+        match self {
+            Light::Area { surface, color, intensity, .. } => {
+                if line.direction.dot(&surface.normal) >= 0.0 {
+                    return None;
+                }
+                let t = surface.intersect(line)?;
+                Some((t, *color * (intensity / self.surface_area())))
+            }
+            _ => None,
+        }
+    }
+
+    // probability density, per solid angle, of sample() picking the direction of sample when u and v are uniformly random.
+    // Only meaningful for area lights
+    pub fn pdf(&self, sample: &LightSample) -> f64 {
+        match self {
+            Light::Area { surface, .. } => {
+                let mut normal = surface.normal;
+                normal.normalize();
+                let cos = -sample.direction.dot(&normal);
+                if cos <= 0.0 {
+                    return 0.0;
+                }
+                sample.distance * sample.distance / (self.surface_area() * cos)
+            }
+            _ => 0.0,
+        }
+    }
+
+    // light arriving at point from the spot (u, v) of the light, both in range [0, 1].
+    // Only area lights use u and v, the other lights always come from the same place.
+    // None if the point doesn't receive any light from there
+    pub fn sample(&self, point: &Vector, u: f64, v: f64) -> Option<LightSample> {
+        match *self {
+            Light::Point { position, color, intensity } => from_position(point, &position, color * intensity),
+            Light::Directional { direction, color, intensity } => Some(LightSample {
+                direction: direction * -1.0,
+                distance: f64::INFINITY,
+                radiance: color * intensity,
+            }),
+            Light::Spot { position, direction, color, intensity, inner_angle, outer_angle } => {
+                let sample = from_position(point, &position, color * intensity)?;
+                let cos = -sample.direction.dot(&direction);
+                let (cos_inner, cos_outer) = (inner_angle.cos(), outer_angle.cos());
+                let falloff = if cos >= cos_inner {
+                    1.0
+                } else if cos <= cos_outer {
+                    return None;
+                } else {
+                    smoothstep((cos - cos_outer) / (cos_inner - cos_outer))
+                };
+                Some(LightSample { radiance: sample.radiance * falloff, ..sample })
+            }
+            Light::Area { surface, color, intensity, .. } => {
+                let (v_range, w_range) = (surface.max_v?, surface.max_w?);
+                let t = v_range.0 + (v_range.1 - v_range.0) * u;
+                let s = w_range.0 + (w_range.1 - w_range.0) * v;
+                let position = surface.point + surface.v? * t + surface.w? * s;
+                let sample = from_position(point, &position, color * intensity)?;
+                // the rectangle emits like a diffuse surface, the most light leaves straight along its normal
+                let mut normal = surface.normal;
+                normal.normalize();
+                let cos = -sample.direction.dot(&normal);
+                if cos <= 0.0 {
+                    return None;
+                }
+                Some(LightSample { radiance: sample.radiance * cos, ..sample })
+            }
+        }
+    }
+
+    // every sample the renderer takes of the light for a point, on a stratified grid for area lights.
+    // The radiance of each sample is divided by their number, so they add up to the light arriving at the point
+    pub fn samples(&self, point: &Vector) -> Vec<LightSample> {
+        let count = self.sample_count();
+        let side = (count as f64).sqrt().round() as usize;
+        let mut samples = Vec::with_capacity(count);
+        for k in 0..count {
+            let u = ((k % side) as f64 + 0.5) / side as f64;
+            let v = ((k / side) as f64 + 0.5) / side as f64;
+            if let Some(sample) = self.sample(point, u, v) {
+                samples.push(LightSample { radiance: sample.radiance * (1.0 / count as f64), ..sample });
+            }
+        }
+        samples
+    }
+}
+
+// light of a point light at position
+fn from_position(point: &Vector, position: &Vector, radiance: Color) -> Option<LightSample> {
+    let mut direction = *position - *point;
+    let distance = direction.length();
+    if distance == 0.0 {
+        return None;
+    }
+    direction.normalize();
+    Some(LightSample { direction, distance, radiance: radiance * (1.0 / (distance * distance)) })
+}
+
+// smooth curve from 0 at x = 0 to 1 at x = 1
+fn smoothstep(x: f64) -> f64 {
+    let x = x.clamp(0.0, 1.0);
+    x * x * (3.0 - 2.0 * x)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::as_radians;
+
+    #[test]
+    fn point_light_test() {
+        let light = Light::point(Vector::new(0.0, 4.0, 0.0), Color::white(), 32.0);
+        let sample = light.sample(&Vector::new(0.0, 0.0, 0.0), 0.0, 0.0).unwrap();
+        assert_eq!(sample.direction, Vector::new(0.0, 1.0, 0.0));
+        assert_eq!(sample.distance, 4.0);
+        assert_eq!(sample.radiance, Color::new(2.0, 2.0, 2.0));
+        assert!(light.sample(&Vector::new(0.0, 4.0, 0.0), 0.0, 0.0).is_none());
+        assert_eq!(light.samples(&Vector::new(0.0, 0.0, 0.0)), vec![sample]);
+    }
+
+    #[test]
+    fn directional_light_test() {
+        let light = Light::directional(Vector::new(0.0, -2.0, 0.0), Color::new(1.0, 0.5, 0.0), 2.0);
+        // the same everywhere
+        for point in [Vector::new(0.0, 0.0, 0.0), Vector::new(100.0, -50.0, 3.0)] {
+            let sample = light.sample(&point, 0.0, 0.0).unwrap();
+            assert_eq!(sample.direction, Vector::new(0.0, 1.0, 0.0));
+            assert_eq!(sample.distance, f64::INFINITY);
+            assert_eq!(sample.radiance, Color::new(2.0, 1.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn spot_light_test() {
+        let light = Light::spot(Vector::new(0.0, 10.0, 0.0), Vector::new(0.0, -1.0, 0.0), Color::white(), 100.0, as_radians(20.0), as_radians(40.0));
+        let at = |x: f64| light.sample(&Vector::new(x, 0.0, 0.0), 0.0, 0.0).map_or(0.0, |sample| sample.radiance.r);
+        // straight below is the same as a point light
+        assert_eq!(at(0.0), 1.0);
+        // inside the inner cone only the distance matters
+        let x = 10.0 * as_radians(15.0).tan();
+        assert!((at(x) - 100.0 / (100.0 + x * x)).abs() < 1e-12);
+        // fading out between the cones
+        let x = 10.0 * as_radians(30.0).tan();
+        let full = 100.0 / (100.0 + x * x);
+        assert!(at(x) > 0.0 && at(x) < full);
+        // nothing outside of the outer cone
+        assert_eq!(at(10.0 * as_radians(45.0).tan()), 0.0);
+    }
+
+    #[test]
+    fn area_light_test() {
+        // 2x2 light facing down from 10 units above the origin
+        let surface = Surface::new_vw(
+            Vector::new(0.0, 10.0, 0.0),
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 0.0, 1.0),
+            (-1.0, 1.0),
+            (-1.0, 1.0),
+            Vector::new(0.0, -1.0, 0.0));
+        assert!(Light::area(Surface::new_normal(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0)), Color::white(), 1.0, 4).is_none());
+        let light = Light::area(surface, Color::white(), 100.0, 4).unwrap();
+        assert_eq!(light.sample_count(), 16);
+
+        // the corners of the rectangle
+        let sample = light.sample(&Vector::new(0.0, 0.0, 0.0), 0.0, 1.0).unwrap();
+        let mut direction = Vector::new(-1.0, 10.0, 1.0);
+        direction.normalize();
+        assert_eq!(sample.direction, direction);
+
+        // far away it's close to a point light in the middle of the rectangle
+        let far = Vector::new(0.0, -990.0, 0.0);
+        let total = light.samples(&far).iter().fold(0.0, |sum, sample| sum + sample.radiance.r);
+        assert!((total - 100.0 / (1000.0 * 1000.0)).abs() < 1e-9);
+
+        // nothing is emitted behind the light
+        assert!(light.samples(&Vector::new(0.0, 20.0, 0.0)).is_empty());
+    }
+
+    #[test]
+    fn area_light_hit_test() {
+        let surface = Surface::new_vw(
+            Vector::new(0.0, 10.0, 0.0),
+            Vector::new(2.0, 0.0, 0.0),
+            Vector::new(0.0, 0.0, 1.0),
+            (-1.0, 1.0),
+            (-1.0, 1.0),
+            Vector::new(0.0, -1.0, 0.0));
+        let light = Light::area(surface, Color::white(), 80.0, 1).unwrap();
+        assert!(!light.is_delta());
+        assert!(Light::point(Vector::new(0.0, 0.0, 0.0), Color::white(), 1.0).is_delta());
+        assert_eq!(light.surface_area(), 8.0);
+
+        // light leaving the rectangle is the intensity spread over its area
+        let up = Line::new(Vector::new(0.5, 0.0, 0.5), Vector::new(0.0, 1.0, 0.0));
+        assert_eq!(light.intersect(&up), Some((10.0, Color::new(10.0, 10.0, 10.0))));
+        // it only shines downwards
+        let down = Line::new(Vector::new(0.5, 20.0, 0.5), Vector::new(0.0, -1.0, 0.0));
+        assert!(light.intersect(&down).is_none());
+
+        // straight below the middle the pdf is distance squared over area
+        let sample = light.sample(&Vector::new(0.0, 0.0, 0.0), 0.5, 0.5).unwrap();
+        assert!((light.pdf(&sample) - 100.0 / 8.0).abs() < 1e-12);
+    }
+}
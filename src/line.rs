@@ -1,8 +1,12 @@
 use float_cmp::{approx_eq, F64Margin};
+use crate::bvh::Bvh;
 use crate::object::Object;
 use crate::point::Point;
-use crate::raycasthit::RayCastHit;
+use crate::raycasthit::{HitRecord, RayCastHit};
+use crate::scene::{Scene, SceneBvh};
+use crate::shape::Shape;
 use crate::surface::Surface;
+use crate::triangle::Triangle;
 use crate::vector::Vector;
 
 #[derive(Debug, Clone, Copy)]
@@ -55,61 +59,115 @@ impl Line {
     }
 
     // Returns the point of intersection if they intersect. Otherwise returns None.
+    // Unlike intersection_shape, the hit can also be behind the start of the line (t < 0).
     pub fn intersection_surface(&self, surface: &Surface) -> RayCastHit {
         let parallel_check = self.direction.dot(&surface.normal);
         if approx_eq!(f64, parallel_check, 0.0, F64Margin::default()) {
-            RayCastHit::new(None)
+            RayCastHit::miss()
         } else {
             let t = ((surface.normal * -1.0).dot(&(self.point - surface.point)))
                 / (surface.normal.dot(&self.direction));
             let intersection = self.point_on_line(&t);
-            let (t, s) = surface.get_t_s_from_point(&intersection);
-            let angle = self.direction.angle_radians(&surface.normal);
 
             // this checks if the intersection isnt outside of out bound surface (a rectangle)
-            if surface.point_on_surface(&t, &s).is_none() {
-                RayCastHit::new(None)
-            } else {
-                RayCastHit::new(Some((intersection, angle)))
+            if surface.is_bounded() {
+                let (t, s) = surface.get_t_s_from_point(&intersection);
+                if surface.point_on_surface(&t, &s).is_none() {
+                    return RayCastHit::miss();
+                }
+            }
+            RayCastHit::new(Some(HitRecord::new(&self.direction, intersection, t, surface.normal, surface.uv_at(&intersection))))
+        }
+    }
+
+    // Returns the point of intersection with a triangle, None if the line misses it.
+    // The hit can also be behind the start of the line (t < 0), uv are the barycentric weights of b and c.
+    pub fn intersection_triangle(&self, triangle: &Triangle) -> RayCastHit {
+        match triangle.intersect(self) {
+            Some(hit) => {
+                let (_, u, v) = hit.barycentric;
+                RayCastHit::new(Some(HitRecord::new(&self.direction, self.point_on_line(&hit.t), hit.t, hit.normal, (u, v))))
             }
+            None => RayCastHit::miss(),
         }
     }
 
+    // Returns the closest point of intersection in front of the line start, None if there is none.
+    pub fn intersection_shape(&self, shape: &dyn Shape) -> RayCastHit {
+        match shape.intersect(self) {
+            Some(t) => {
+                let intersection = self.point_on_line(&t);
+                let normal = shape.normal_at(&intersection);
+                RayCastHit::new(Some(HitRecord::new(&self.direction, intersection, t, normal, shape.uv_at(&intersection))))
+            }
+            None => RayCastHit::miss(),
+        }
+    }
+
+    // Returns the closest hit in front of cam_pos. With backface culling (bfc) enabled,
+    // a closest hit on the back face of a shape counts as a miss.
+    // Tests every shape of the object, see intersection_bvh for the accelerated version.
     pub fn intersection_object(&self, obj: &Object, cam_pos: &Vector, bfc: &bool) -> RayCastHit {
-        let mut closest_intersection: RayCastHit = RayCastHit::new(None);
+        let mut closest_intersection: Option<HitRecord> = None;
         let mut closest_distance: f64 = 0.0;
-
-        for surface in &obj.surfaces {
-            // intersection with each surface
-            let intersection = self.intersection_surface(&surface);
+        // the shapes are in object space
+        let local = match obj.to_object_space(self) {
+            Some(local) => local,
+            None => return RayCastHit::miss(),
+        };
+
+        for (index, shape) in obj.shapes.iter().enumerate() {
+            // intersection with each shape
+            let intersection = shape.intersect(&local).map(|t| obj.world_hit(self, &local, index, t));
             // if we have a hit
-            if intersection.is_some() {
+            if let Some(intersection) = &intersection {
 
-                let from_cam_to_point = intersection.unwrap().0 - *cam_pos;
+                let from_cam_to_point = intersection.point - *cam_pos;
 
                 if from_cam_to_point.dot(&self.direction) >= 0.0 {
-                    let intersection = intersection.unwrap();
-                    let distance = (*cam_pos).distance(&intersection.0);
+                    let distance = (*cam_pos).distance(&intersection.point);
 
-                    if closest_intersection.is_none() {
-                        closest_intersection = RayCastHit::new(Some(intersection));
-                        closest_distance = distance;
-                    } else if distance < closest_distance {
-                        closest_intersection = RayCastHit::new(Some(intersection));
+                    if closest_intersection.is_none() || distance < closest_distance {
+                        closest_intersection = Some(*intersection);
                         closest_distance = distance;
                     }
                 }
             }
         }
-        if closest_intersection.is_some() {
-            if *bfc {
-                if closest_intersection.angle().cos() < 0.0 {
-                    //println!("camera pos: {}, hit: {}, angle: {}", cam_pos.to_string(), closest_intersection.unwrap().0.to_string(), closest_intersection.angle());
-                    closest_intersection = RayCastHit::new(None);
+        if let Some(closest) = closest_intersection {
+            // normals point out of the shapes, so the back faces are the ones facing away from the line
+            if *bfc && !closest.front_face {
+                closest_intersection = None;
+            }
+        }
+        RayCastHit::new(closest_intersection)
+    }
+
+    // Same as intersection_object with cam_pos at the start of the line, but only tests the shapes
+    // whose bounding boxes the line passes through. The bvh has to be built from obj.shapes.
+    pub fn intersection_bvh(&self, obj: &Object, bvh: &Bvh, bfc: &bool) -> RayCastHit {
+        let local = match obj.to_object_space(self) {
+            Some(local) => local,
+            None => return RayCastHit::miss(),
+        };
+        match bvh.closest_hit(&obj.shapes, &local) {
+            Some((index, t)) => {
+                let hit = obj.world_hit(self, &local, index, t);
+                if *bfc && !hit.front_face {
+                    return RayCastHit::miss();
                 }
+                RayCastHit::new(Some(hit))
             }
+            None => RayCastHit::miss(),
         }
-        closest_intersection
     }
-}
 
+    // closest hit on any object of the scene, with object_index set to the object that was hit.
+    // With backface culling (bfc) enabled a closest hit on a back face counts as a miss. bvh has to be built from the scene
+    pub fn intersection_scene(&self, scene: &Scene, bvh: &SceneBvh, bfc: &bool) -> RayCastHit {
+        match scene.closest_hit(bvh, self) {
+            Some(hit) if !*bfc || hit.front_face => RayCastHit::new(Some(hit)),
+            _ => RayCastHit::miss(),
+        }
+    }
+}
@@ -2,30 +2,26 @@ use std::fs::File;
 use std::io::Write;
 use float_cmp::{approx_eq, F64Margin};
 use raylib::ffi::ImageFormat;
-use vector::*;
-use point::*;
-use mat4::*;
-use crate::line::Line;
-use crate::math::{as_degrees, as_radians};
-use crate::quaternion::Quaternion;
-use crate::surface::Surface;
-use crate::camera::Camera;
+use math_for_graphics::vector::*;
+use math_for_graphics::point::*;
+use math_for_graphics::mat4::*;
+use math_for_graphics::line::Line;
+use math_for_graphics::math::{as_degrees, as_radians};
+use math_for_graphics::quaternion::Quaternion;
+use math_for_graphics::surface::Surface;
+use math_for_graphics::camera::{Camera, Projection};
 
 use raylib::prelude::*;
 use cstr::cstr;
-use crate::object::Object;
-use crate::raycasthit::RayCastHit;
-
-mod vector;
-mod point;
-mod mat4;
-mod math;
-mod quaternion;
-mod surface;
-mod line;
-mod object;
-mod camera;
-mod raycasthit;
+use math_for_graphics::object::Object;
+use math_for_graphics::raycasthit::RayCastHit;
+use math_for_graphics::color::Color as LinearColor;
+use math_for_graphics::framebuffer::{Accumulator, Framebuffer};
+use math_for_graphics::light::Light;
+use math_for_graphics::pathtracer::PathTracer;
+use math_for_graphics::sampling::SamplePattern;
+use math_for_graphics::scene::Scene;
+use math_for_graphics::transform::Transform;
 
 // globals
 const WIDTH: i32 = 1850;
@@ -62,6 +58,23 @@ pub fn save_to_file(hits: &Vec<RayCastHit>) {
     }
 }
 
+// save the current frame as a PNG image, errors are only reported so the viewer keeps running
+pub fn save_image(image: &Framebuffer) {
+    if let Err(e) = image.save("output.png") {
+        eprintln!("could not save output.png: {}", e);
+    }
+}
+
+// square light above the scene, to the top left of the default camera
+fn area_light() -> Light {
+    let position = Vector::new(-25.0, 35.0, 60.0);
+    let mut direction = position * -1.0;
+    direction.normalize();
+    let (v, w) = direction.orthonormal_basis();
+    let panel = Surface::new_vw(position, v, w, (-8.0, 8.0), (-8.0, 8.0), direction);
+    Light::area(panel, LinearColor::white(), 20000.0, 1).unwrap()
+}
+
 pub fn display_debug(c: &Camera) {
     println!("{}", c.get_debug_info());
 }
@@ -101,54 +114,27 @@ fn main() {
 
 
 
-    // initialize surfaces that create a cube
-    let mut front = Surface::new_vw(
-        Vector::new(0.0, 0.0, 15.0),
-        Vector::new(1.0, 0.0, 0.0),
-        Vector::new(0.0, 1.0, 0.0),
-        (-15.0, 15.0),
-        (-15.0, 15.0),
-        Vector::new(0.0, 0.0, -1.0));
-    let mut back = Surface::new_vw(
-        Vector::new(0.0, 0.0, -15.0),
-        Vector::new(1.0, 0.0, 0.0),
-        Vector::new(0.0, 1.0, 0.0),
-        (-15.0, 15.0),
-        (-15.0, 15.0),
-        Vector::new(0.0, 0.0, 1.0));
-    let mut left = Surface::new_vw(
-        Vector::new(-15.0, 0.0, 0.0),
-        Vector::new(0.0, 0.0, 1.0),
-        Vector::new(0.0, 1.0, 0.0),
-        (-15.0, 15.0),
-        (-15.0, 15.0),
-        Vector::new(1.0, 0.0, 0.0));
-    let mut right = Surface::new_vw(
-        Vector::new(15.0, 0.0, 0.0),
-        Vector::new(0.0, 0.0, 1.0),
-        Vector::new(0.0, 1.0, 0.0),
-        (-15.0, 15.0),
-        (-15.0, 15.0),
-        Vector::new(-1.0, 0.0, 0.0));
-    let mut top = Surface::new_vw(
-        Vector::new(0.0, 15.0, 0.0),
-        Vector::new(1.0, 0.0, 0.0),
-        Vector::new(0.0, 0.0, 1.0),
-        (-15.0, 15.0),
-        (-15.0, 15.0),
-        Vector::new(0.0, -1.0, 0.0));
-    let mut bottom = Surface::new_vw(
-        Vector::new(0.0, -15.0, 0.0),
-        Vector::new(1.0, 0.0, 0.0),
-        Vector::new(0.0, 0.0, 1.0),
-        (-15.0, 15.0),
-        (-15.0, 15.0),
-        Vector::new(0.0, 1.0, 0.0));
-
-    let mut surfaces = vec![front, back, left, right, top, bottom];
-    //let mut surfaces = vec![back, left, right,front];
-    let mut surfaces = Object::new(surfaces);
-    //surfaces.scale(&2.0);
+    // the scene file given as the first argument, or a cube surrounded by the other primitives
+    let scene_path = std::env::args().nth(1);
+    let mut scene = match &scene_path {
+        Some(path) => match Scene::load(path) {
+            Ok(scene) => scene,
+            Err(e) => {
+                eprintln!("could not load scene {}: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        None => {
+            let mut scene = Scene::from_object("showcase", Object::showcase());
+            scene.background = LinearColor::from_srgb8(BG_COLOR.r, BG_COLOR.g, BG_COLOR.b);
+            scene
+        }
+    };
+    let initial_transforms: Vec<Transform> = scene.objects.iter().map(|o| o.object.transform()).collect();
+    // the path tracer needs something to light the scene
+    if scene.lights.is_empty() {
+        scene.add_light(area_light());
+    }
 
     //let mut hits: Vec<Vec<bool>> = vec![vec![false; RENDER_HEIGHT as usize]; RENDER_WIDTH as usize];
     //let mut angles: Vec<Vec<f64>> = vec![vec![0.0; RENDER_HEIGHT as usize]; RENDER_WIDTH as usize];
@@ -162,46 +148,64 @@ fn main() {
     let (mut x, mut y, mut z) = (0.0, 0.0, 0.0);
     let (mut cam_x, mut cam_y, mut cam_z) = (0.0, 0.0, 0.0);
 
-    let mut camera_pos = Vector::new(0.0, 0.0, 50.0);
-
-    let mut camera = Camera::new(
-        camera_pos.clone(),
-        Vector::new(0.0, 0.0, -1.0),
-        RENDER_WIDTH, RENDER_HEIGHT,
-        Vector::new(0.0, 1.0, 0.0),
-        Vector::new(1.0, 0.0, 0.0));
+    // the viewer starts from the first camera of the scene, the sliders move it from there
+    let mut camera = match scene.cameras.first() {
+        Some(c) => c.clone(),
+        None => Camera::new(
+            Vector::new(0.0, 0.0, 50.0),
+            Vector::new(0.0, 0.0, -1.0),
+            RENDER_WIDTH, RENDER_HEIGHT,
+            Vector::new(0.0, 1.0, 0.0),
+            Vector::new(1.0, 0.0, 0.0)),
+    };
+    // the pixels are drawn on a grid of fixed size
+    camera.render_width = RENDER_WIDTH;
+    camera.render_height = RENDER_HEIGHT;
+    if let Projection::Perspective { aspect, .. } = &mut camera.projection {
+        *aspect = RENDER_WIDTH as f64 / RENDER_HEIGHT as f64;
+    }
+    // render tiles on every core so the sliders stay responsive
+    camera.threads = 0;
+    // path traced frames go through a new random spot of every pixel, so the accumulated image is anti-aliased
+    camera.pattern = SamplePattern::Jittered;
+    let mut camera_pos = camera.line.point;
 
     let mut first_frame: bool = true;
 
     let mut cube_color: Color = Color::new(255, 0, 0, 255);
 
+    // path tracing adds one sample per pixel every frame for as long as nothing moves
+    let mut path_trace = false;
+    let path_tracer = PathTracer::new();
+    let mut accumulator = Accumulator::new(RENDER_WIDTH as usize, RENDER_HEIGHT as usize);
+    // the shapes never change, moving the objects only needs new boxes around them
+    let mut bvh = scene.bvh();
+    let mut last_view = (cam_x, cam_y, cam_z, camera_pos.x, camera_pos.y, camera_pos.z, matches!(camera.projection, Projection::Perspective { .. }));
+
     while !rl.window_should_close() {
         let mut d = rl.begin_drawing(&thread);
         d.clear_background(BG_COLOR);
 
-        hits = camera.render(&surfaces);
-
-        for hit in hits.iter() {
-            if hit.is_some() {
-                // the hit color value calculated to be sqrt( sin(|angle|) )
-                let color_value = {
-                    let angle_cos = hit.angle().cos();
-                    if angle_cos >= 0.0 {
-                        angle_cos.sqrt()
-                    } else {
-                        angle_cos.abs().sqrt()
-                    }
-                };
-
-                //let color = Color::color_from_hsv(1.0, 1.0, color_value as f32);
-                let color = Color::new(
-                    ((color_value) * cube_color.r as f64) as u8,
-                    ((color_value) * cube_color.g as f64) as u8,
-                    ((color_value) * cube_color.b as f64) as u8,
-                    255);
-                let (i, mut j) = hit.pos_on_screen;
-                j = -j;
-                d.draw_rectangle((i * PIXEL_SIZE) as i32 + OFFSET.0, (j * PIXEL_SIZE) as i32 + OFFSET.1,PIXEL_SIZE as i32, PIXEL_SIZE as i32, color);
+        // both modes shade into a framebuffer, so the saved image is the one on the screen
+        let image = if path_trace {
+            let frame = path_tracer.render(&mut camera, &scene, &bvh, accumulator.frames());
+            accumulator.add(&frame);
+            accumulator.image()
+        } else {
+            hits = camera.render(&scene, &bvh);
+            let mut fb = Framebuffer::new(RENDER_WIDTH as usize, RENDER_HEIGHT as usize);
+            fb.shade(&camera, &hits,
+                LinearColor::from_srgb8(cube_color.r, cube_color.g, cube_color.b),
+                LinearColor::from_srgb8(BG_COLOR.r, BG_COLOR.g, BG_COLOR.b));
+            fb
+        };
+        for py in 0..RENDER_HEIGHT {
+            for px in 0..RENDER_WIDTH {
+                let [r, g, b] = image.get(px as usize, py as usize).to_srgb8();
+                d.draw_rectangle(
+                    (px - RENDER_WIDTH / 2) * PIXEL_SIZE + OFFSET.0,
+                    (py - RENDER_HEIGHT / 2 + 1) * PIXEL_SIZE + OFFSET.1,
+                    PIXEL_SIZE, PIXEL_SIZE, Color::new(r, g, b, 255));
             }
         }
 
@@ -240,12 +244,31 @@ fn main() {
             Vector2::new(25.0, slider_height as f32),
             Vector2::new(535.0, slider_height as f32), 4.0, Color::BLACK);
 
-        surfaces.rotate(&q);
+        // only changes the transforms of the objects, the shapes keep their original pose
+        for o in scene.objects.iter_mut() {
+            o.object.rotate(&q);
+        }
 
         slider_height += 20;
 
+        let reset = d.gui_button(Rectangle::new(270.0, slider_height as f32,110.0, 50.0), None);
+        if reset {
+            // back to where the scene placed them
+            for (o, transform) in scene.objects.iter_mut().zip(initial_transforms.iter()) {
+                o.object.set_transform(*transform);
+            }
+            (x, y, z) = (0.0, 0.0, 0.0);
+        }
+        if reset || x != 0.0 || y != 0.0 || z != 0.0 {
+            bvh.update_bounds(&scene);
+        }
+        d.draw_text("reset", 280, slider_height + 5, 32, Color::WHITE);
+
         if d.gui_button(Rectangle::new(400.0, slider_height as f32,100.0, 50.0), None) {
-            save_to_file(&hits);
+            if !path_trace {
+                save_to_file(&hits);
+            }
+            save_image(&image);
         }
         d.draw_text("save", 410, slider_height + 5, 32, Color::WHITE);
 
@@ -297,6 +320,33 @@ fn main() {
 
         d.draw_text("Backface Culling", 1410, slider_height + 5, 32, Color::WHITE);
 
+        slider_height += 60;
+
+        let perspective = d.gui_check_box(Rectangle::new(1750.0, slider_height as f32,50.0, 50.0), None, matches!(camera.projection, Projection::Perspective { .. }));
+        // the projection of the scene camera is kept until the check box is clicked
+        if perspective != matches!(camera.projection, Projection::Perspective { .. }) {
+            camera.projection = if perspective {
+                Projection::Perspective { fov_y: as_radians(60.0), aspect: RENDER_WIDTH as f64 / RENDER_HEIGHT as f64 }
+            } else {
+                Projection::Orthographic { width: RENDER_WIDTH as f64, height: RENDER_HEIGHT as f64 }
+            };
+        }
+
+        d.draw_text("Perspective", 1410, slider_height + 5, 32, Color::WHITE);
+
+        slider_height += 60;
+
+        let toggled = d.gui_check_box(Rectangle::new(1750.0, slider_height as f32,50.0, 50.0), None, path_trace);
+        d.draw_text(&format!("Path trace ({})", accumulator.frames()), 1410, slider_height + 5, 32, Color::WHITE);
+
+        // start over whenever the image would change
+        let view = (cam_x, cam_y, cam_z, camera_pos.x, camera_pos.y, camera_pos.z, perspective);
+        if toggled != path_trace || view != last_view || reset || x != 0.0 || y != 0.0 || z != 0.0 {
+            accumulator.reset();
+        }
+        path_trace = toggled;
+        last_view = view;
+
         //println!("v: {}, w: {}, n: {}", surface.v.unwrap().to_string(), surface.w.unwrap().to_string(), surface.normal.to_string());
 
     }
@@ -231,6 +231,35 @@ impl Mat4 {
         self.m = m;
     }
 
+    // applies the matrix to a point, including the translation
+    pub fn transform_point(&self, p: &Vector) -> Vector {
+        let m = &self.m;
+        Vector::new(
+            m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3],
+            m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3],
+            m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3])
+    }
+
+    // applies the matrix to a direction, which isn't affected by the translation
+    pub fn transform_direction(&self, d: &Vector) -> Vector {
+        let m = &self.m;
+        Vector::new(
+            m[0][0] * d.x + m[0][1] * d.y + m[0][2] * d.z,
+            m[1][0] * d.x + m[1][1] * d.y + m[1][2] * d.z,
+            m[2][0] * d.x + m[2][1] * d.y + m[2][2] * d.z)
+    }
+
+    // matrix for normals, the transpose of the inverse. Keeps normals perpendicular to surfaces under
+    // non-uniform scale and shear, the result has to be normalized again. None if the matrix can't be inverted
+    pub fn normal_matrix(&self) -> Option<Mat4> {
+        let mut m = *self;
+        if !m.inverse() {
+            return None;
+        }
+        m.transpose();
+        Some(m)
+    }
+
     // simple to_string for debugging purposes
     pub fn to_string(&self) -> String {
         let mut out: String = String::new();
@@ -0,0 +1,238 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::color::Color;
+use crate::vector::Vector;
+
+// reflectance of dielectrics (plastic, stone, ...) looking straight at them
+const DIELECTRIC_F0: f64 = 0.04;
+
+// the model used to reflect light from the lights
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Brdf {
+    // perfectly diffuse, looks the same from every direction
+    Lambert,
+    // diffuse plus a highlight around the mirror direction, controlled by specular and shininess
+    BlinnPhong,
+    // microfacet model with GGX distribution, controlled by roughness and metalness
+    // see: https://learnopengl.com/PBR/Theory
+    CookTorrance,
+}
+
+// how a shape reflects, transmits and emits light.
+// reflectivity and transparency are the fractions of light that are mirrored and refracted by the ray tracer,
+// whatever is left is lit by the lights through the BRDF
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Material {
+    pub brdf: Brdf,
+    // diffuse color, for metals it's the color of the reflections
+    pub albedo: Color,
+    // color and sharpness of the Blinn-Phong highlight
+    pub specular: Color,
+    pub shininess: f64,
+    // Cook-Torrance parameters, both in range [0, 1]
+    pub roughness: f64,
+    pub metalness: f64,
+    // light given off by the material itself
+    pub emission: Color,
+    pub reflectivity: f64,
+    pub transparency: f64,
+    // index of refraction, only used by transparent materials
+    pub ior: f64,
+}
+
+impl Material {
+    // plain diffuse material
+    pub fn new(albedo: Color) -> Material {
+        Material {
+            brdf: Brdf::Lambert,
+            albedo,
+            specular: Color::black(),
+            shininess: 1.0,
+            roughness: 1.0,
+            metalness: 0.0,
+            emission: Color::black(),
+            reflectivity: 0.0,
+            transparency: 0.0,
+            ior: 1.0,
+        }
+    }
+
+    pub fn blinn_phong(albedo: Color, specular: Color, shininess: f64) -> Material {
+        Material { brdf: Brdf::BlinnPhong, specular, shininess, ..Material::new(albedo) }
+    }
+
+    pub fn cook_torrance(albedo: Color, roughness: f64, metalness: f64) -> Material {
+        Material {
+            brdf: Brdf::CookTorrance,
+            roughness: roughness.clamp(0.0, 1.0),
+            metalness: metalness.clamp(0.0, 1.0),
+            ..Material::new(albedo)
+        }
+    }
+
+    pub fn mirror(albedo: Color, reflectivity: f64) -> Material {
+        Material { reflectivity, ..Material::new(albedo) }
+    }
+
+    // clear dielectric like glass (ior 1.5) or water (ior 1.33), the split between reflection and refraction follows Fresnel
+    pub fn glass(albedo: Color, ior: f64) -> Material {
+        Material { transparency: 1.0, ior, ..Material::new(albedo) }
+    }
+
+    // black material that only gives off light
+    pub fn emissive(emission: Color) -> Material {
+        Material { emission, ..Material::new(Color::black()) }
+    }
+
+    // fraction of light lit by the lights directly
+    pub fn diffuse(&self) -> f64 {
+        (1.0 - self.reflectivity - self.transparency).max(0.0)
+    }
+
+    // fraction of the light arriving from to_light that leaves towards to_viewer, per steradian.
+    // normal, to_light and to_viewer must be normalized, the normal on the side of the viewer.
+    // Multiplied by the light arriving and the cosine between normal and to_light it gives the reflected light
+    pub fn brdf(&self, normal: &Vector, to_light: &Vector, to_viewer: &Vector) -> Color {
+        let n_dot_l = normal.dot(to_light);
+        let n_dot_v = normal.dot(to_viewer);
+        if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
+            return Color::black();
+        }
+        let lambert = self.albedo * (1.0 / PI);
+        match self.brdf {
+            Brdf::Lambert => lambert,
+            Brdf::BlinnPhong => {
+                let n_dot_h = normal.dot(&halfway(to_light, to_viewer)).max(0.0);
+                // normalized so the highlight doesn't get brighter or darker when the shininess changes
+                let normalization = (self.shininess + 8.0) / (8.0 * PI);
+                lambert + self.specular * (normalization * n_dot_h.powf(self.shininess))
+            }
+            Brdf::CookTorrance => {
+                let h = halfway(to_light, to_viewer);
+                let n_dot_h = normal.dot(&h).max(0.0);
+                let v_dot_h = to_viewer.dot(&h).max(0.0);
+                let alpha = (self.roughness * self.roughness).max(1e-4);
+
+                let f0 = Color::new(DIELECTRIC_F0, DIELECTRIC_F0, DIELECTRIC_F0).lerp(&self.albedo, self.metalness);
+                let fresnel = f0.lerp(&Color::white(), (1.0 - v_dot_h).powi(5));
+                let specular = fresnel * (ggx_distribution(n_dot_h, alpha) * smith_geometry(n_dot_l, n_dot_v, alpha) / (4.0 * n_dot_l * n_dot_v));
+
+                // light that isn't reflected at the surface is scattered below it, metals absorb it
+                let kd = (Color::white() - fresnel) * (1.0 - self.metalness);
+                kd * lambert + specular
+            }
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Material {
+        Material::new(Color::white())
+    }
+}
+
+fn halfway(a: &Vector, b: &Vector) -> Vector {
+    let mut h = *a + *b;
+    h.normalize();
+    h
+}
+
+// share of microfacets facing along the halfway vector
+fn ggx_distribution(n_dot_h: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+}
+
+// share of microfacets that are neither shadowed nor hidden by others, Smith with Schlick-GGX
+fn smith_geometry(n_dot_l: f64, n_dot_v: f64, alpha: f64) -> f64 {
+    let k = alpha / 2.0;
+    let g = |cos: f64| cos / (cos * (1.0 - k) + k);
+    g(n_dot_l) * g(n_dot_v)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn direction(theta: f64, phi: f64) -> Vector {
+        Vector::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos())
+    }
+
+    // fraction of the light from to_light that is reflected into the whole hemisphere, integrated numerically
+    fn albedo(material: &Material, to_light: &Vector) -> Color {
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        let steps = 200;
+        let (d_theta, d_phi) = (PI / 2.0 / steps as f64, 2.0 * PI / steps as f64);
+        let mut total = Color::black();
+        for i in 0..steps {
+            let theta = (i as f64 + 0.5) * d_theta;
+            for j in 0..steps {
+                let phi = (j as f64 + 0.5) * d_phi;
+                let to_viewer = direction(theta, phi);
+                let solid_angle = theta.sin() * d_theta * d_phi;
+                total += material.brdf(&normal, to_light, &to_viewer) * (theta.cos() * solid_angle);
+            }
+        }
+        total
+    }
+
+    #[test]
+    fn lambert_test() {
+        let material = Material::new(Color::new(0.5, 0.25, 1.0));
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        // the same from every direction
+        let a = material.brdf(&normal, &direction(0.3, 0.0), &direction(0.0, 0.0));
+        let b = material.brdf(&normal, &direction(0.3, 0.0), &direction(1.2, 2.0));
+        assert_eq!(a, b);
+        assert_eq!(a, Color::new(0.5 / PI, 0.25 / PI, 1.0 / PI));
+        // reflects exactly its albedo
+        let total = albedo(&material, &direction(0.5, 1.0));
+        assert!((total.r - 0.5).abs() < 1e-3 && (total.b - 1.0).abs() < 1e-3);
+        // nothing from or towards below the surface
+        assert_eq!(material.brdf(&normal, &direction(2.0, 0.0), &direction(0.0, 0.0)), Color::black());
+        assert_eq!(material.brdf(&normal, &direction(0.0, 0.0), &direction(2.0, 0.0)), Color::black());
+    }
+
+    #[test]
+    fn blinn_phong_test() {
+        let material = Material::blinn_phong(Color::black(), Color::white(), 50.0);
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        let to_light = direction(0.5, 0.0);
+        // brightest in the mirror direction
+        let mirror = material.brdf(&normal, &to_light, &direction(0.5, PI));
+        let off = material.brdf(&normal, &to_light, &direction(0.9, PI));
+        assert!(mirror.r > off.r * 2.0);
+        // reciprocal
+        let a = material.brdf(&normal, &to_light, &direction(0.9, 2.0));
+        let b = material.brdf(&normal, &direction(0.9, 2.0), &to_light);
+        assert!((a.r - b.r).abs() < 1e-12);
+    }
+
+    #[test]
+    fn cook_torrance_test() {
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        for (roughness, metalness) in [(0.2, 0.0), (0.5, 0.0), (0.3, 1.0), (0.8, 1.0), (0.5, 0.5)] {
+            let material = Material::cook_torrance(Color::white(), roughness, metalness);
+            // reciprocal
+            let a = material.brdf(&normal, &direction(0.4, 0.0), &direction(0.9, 2.0));
+            let b = material.brdf(&normal, &direction(0.9, 2.0), &direction(0.4, 0.0));
+            assert!((a.r - b.r).abs() < 1e-9);
+            // doesn't reflect more light than it receives
+            let total = albedo(&material, &direction(0.4, 0.0));
+            assert!(total.r <= 1.0 && total.r > 0.5, "{} {} {:?}", roughness, metalness, total);
+        }
+
+        // metals have no diffuse part and color their reflections
+        let metal = Material::cook_torrance(Color::new(1.0, 0.0, 0.0), 0.3, 1.0);
+        let highlight = metal.brdf(&normal, &direction(0.4, 0.0), &direction(0.4, PI));
+        assert!(highlight.r > highlight.g * 10.0);
+        // smooth surfaces have a sharper highlight
+        let smooth = Material::cook_torrance(Color::white(), 0.1, 1.0);
+        let rough = Material::cook_torrance(Color::white(), 0.9, 1.0);
+        let peak = |m: &Material| m.brdf(&normal, &direction(0.4, 0.0), &direction(0.4, PI)).r;
+        assert!(peak(&smooth) > peak(&rough) * 10.0);
+    }
+}
@@ -8,6 +8,159 @@ pub fn as_degrees(angle: f64) -> f64 {
     angle * 180.0 / std::f64::consts::PI
 }
 
+// coefficients closer to zero than this are treated as zero by the polynomial solvers
+const EQN_EPSILON: f64 = 1e-9;
+
+fn is_zero(x: f64) -> bool {
+    x.abs() < EQN_EPSILON
+}
+
+// real roots of c[0] + c[1]x + c[2]x^2 = 0, sorted in ascending order
+pub fn solve_quadratic(c: [f64; 3]) -> Vec<f64> {
+    if is_zero(c[2]) {
+        // linear equation
+        return if is_zero(c[1]) { vec![] } else { vec![-c[0] / c[1]] };
+    }
+    let p = c[1] / (2.0 * c[2]);
+    let q = c[0] / c[2];
+    let d = p * p - q;
+
+    if is_zero(d) {
+        vec![-p]
+    } else if d < 0.0 {
+        vec![]
+    } else {
+        let sqrt_d = d.sqrt();
+        vec![-sqrt_d - p, sqrt_d - p]
+    }
+}
+
+// real roots of c[0] + c[1]x + c[2]x^2 + c[3]x^3 = 0, sorted in ascending order
+// algorithm from Graphics Gems I, "Cubic and Quartic Roots" by Jochen Schwarze
+pub fn solve_cubic(c: [f64; 4]) -> Vec<f64> {
+    if is_zero(c[3]) {
+        return solve_quadratic([c[0], c[1], c[2]]);
+    }
+    // normal form: x^3 + Ax^2 + Bx + C = 0
+    let a = c[2] / c[3];
+    let b = c[1] / c[3];
+    let c = c[0] / c[3];
+
+    // substitute x = y - A/3 to eliminate the quadratic term: y^3 + 3py + 2q = 0
+    let sq_a = a * a;
+    let p = 1.0 / 3.0 * (-1.0 / 3.0 * sq_a + b);
+    let q = 1.0 / 2.0 * (2.0 / 27.0 * a * sq_a - 1.0 / 3.0 * a * b + c);
+
+    // use Cardano's formula
+    let cb_p = p * p * p;
+    let d = q * q + cb_p;
+
+    let mut roots = if is_zero(d) {
+        if is_zero(q) {
+            // one triple solution
+            vec![0.0]
+        } else {
+            // one single and one double solution
+            let u = (-q).cbrt();
+            vec![2.0 * u, -u]
+        }
+    } else if d < 0.0 {
+        // three real solutions
+        let phi = 1.0 / 3.0 * (-q / (-cb_p).sqrt()).clamp(-1.0, 1.0).acos();
+        let t = 2.0 * (-p).sqrt();
+        vec![
+            t * phi.cos(),
+            -t * (phi + std::f64::consts::PI / 3.0).cos(),
+            -t * (phi - std::f64::consts::PI / 3.0).cos(),
+        ]
+    } else {
+        // one real solution
+        let sqrt_d = d.sqrt();
+        vec![(sqrt_d - q).cbrt() - (sqrt_d + q).cbrt()]
+    };
+
+    for root in roots.iter_mut() {
+        *root -= a / 3.0;
+    }
+    roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    roots
+}
+
+// real roots of c[0] + c[1]x + c[2]x^2 + c[3]x^3 + c[4]x^4 = 0, sorted in ascending order.
+// Roots are found with Ferrari's method and then polished with a few Newton iterations,
+// which removes most of the precision loss of the closed form solution.
+pub fn solve_quartic(c: [f64; 5]) -> Vec<f64> {
+    if is_zero(c[4]) {
+        return solve_cubic([c[0], c[1], c[2], c[3]]);
+    }
+    // normal form: x^4 + Ax^3 + Bx^2 + Cx + D = 0
+    let a = c[3] / c[4];
+    let b = c[2] / c[4];
+    let cc = c[1] / c[4];
+    let d = c[0] / c[4];
+
+    // substitute x = y - A/4 to eliminate the cubic term: y^4 + py^2 + qy + r = 0
+    let sq_a = a * a;
+    let p = -3.0 / 8.0 * sq_a + b;
+    let q = 1.0 / 8.0 * sq_a * a - 1.0 / 2.0 * a * b + cc;
+    let r = -3.0 / 256.0 * sq_a * sq_a + 1.0 / 16.0 * sq_a * b - 1.0 / 4.0 * a * cc + d;
+
+    let mut roots = if is_zero(r) {
+        // no absolute term: y(y^3 + py + q) = 0
+        let mut roots = solve_cubic([q, p, 0.0, 1.0]);
+        roots.push(0.0);
+        roots
+    } else {
+        // solve the resolvent cubic (2z - p)(z^2 - r) = q^2 / 4 and take its largest solution. It is at least p / 2,
+        // so both factors are positive and the quadratic equations below have real coefficients. The smaller
+        // solutions can make them negative and lose the roots of quartics with (nearly) double roots
+        let z = *solve_cubic([1.0 / 2.0 * r * p - 1.0 / 8.0 * q * q, -r, -1.0 / 2.0 * p, 1.0]).last().unwrap();
+
+        // to build two quadratic equations, rounding can leave the factors slightly negative
+        let u = (z * z - r).max(0.0).sqrt();
+        let v = (2.0 * z - p).max(0.0).sqrt();
+
+        let mut roots = solve_quadratic([z - u, if q < 0.0 { -v } else { v }, 1.0]);
+        roots.extend(solve_quadratic([z + u, if q < 0.0 { v } else { -v }, 1.0]));
+        roots
+    };
+
+    for root in roots.iter_mut() {
+        *root -= a / 4.0;
+        *root = polish_root(&c, *root);
+    }
+    roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    roots
+}
+
+// Newton's method on the polynomial c[0] + c[1]x + ... + c[n]x^n, starting from x. Steps that don't bring
+// the polynomial closer to zero are not taken, near double roots the derivative is close to zero and a step
+// could jump far away
+fn polish_root(c: &[f64], mut x: f64) -> f64 {
+    let evaluate = |x: f64| {
+        let (mut value, mut derivative) = (0.0, 0.0);
+        for coefficient in c.iter().rev() {
+            derivative = derivative * x + value;
+            value = value * x + coefficient;
+        }
+        (value, derivative)
+    };
+    let (mut value, mut derivative) = evaluate(x);
+    for _ in 0..4 {
+        if derivative == 0.0 {
+            break;
+        }
+        let next = x - value / derivative;
+        let (next_value, next_derivative) = evaluate(next);
+        if !next.is_finite() || next_value.abs() >= value.abs() {
+            break;
+        }
+        (x, value, derivative) = (next, next_value, next_derivative);
+    }
+    x
+}
+
+
 
 
 
@@ -26,4 +179,37 @@ mod test {
     fn test_as_degrees() {
         assert_eq!(as_degrees(std::f64::consts::PI), 180.0);
     }
+
+    fn assert_roots(roots: Vec<f64>, expected: &[f64]) {
+        assert_eq!(roots.len(), expected.len(), "roots: {:?}, expected: {:?}", roots, expected);
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!((root - expected).abs() < 1e-9, "roots: {:?}, expected: {:?}", roots, expected);
+        }
+    }
+
+    #[test]
+    fn test_solve_quadratic() {
+        // (x - 1)(x - 3) = x^2 - 4x + 3
+        assert_roots(solve_quadratic([3.0, -4.0, 1.0]), &[1.0, 3.0]);
+        assert_roots(solve_quadratic([1.0, 0.0, 1.0]), &[]);
+        assert_roots(solve_quadratic([-2.0, 1.0, 0.0]), &[2.0]);
+    }
+
+    #[test]
+    fn test_solve_cubic() {
+        // (x + 2)(x - 1)(x - 4) = x^3 - 3x^2 - 6x + 8
+        assert_roots(solve_cubic([8.0, -6.0, -3.0, 1.0]), &[-2.0, 1.0, 4.0]);
+        // x^3 - 8
+        assert_roots(solve_cubic([-8.0, 0.0, 0.0, 1.0]), &[2.0]);
+    }
+
+    #[test]
+    fn test_solve_quartic() {
+        // (x + 3)(x + 1)(x - 2)(x - 5) = x^4 - 3x^3 - 15x^2 + 19x + 30
+        assert_roots(solve_quartic([30.0, 19.0, -15.0, -3.0, 1.0]), &[-3.0, -1.0, 2.0, 5.0]);
+        // (x^2 + 1)(x - 1)(x - 2) = x^4 - 3x^3 + 3x^2 - 3x + 2
+        assert_roots(solve_quartic([2.0, -3.0, 3.0, -3.0, 1.0]), &[1.0, 2.0]);
+        // x^4 + 1 has no real roots
+        assert_roots(solve_quartic([1.0, 0.0, 0.0, 0.0, 1.0]), &[]);
+    }
 }
\ No newline at end of file
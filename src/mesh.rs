@@ -0,0 +1,624 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::aabb::Aabb;
+use crate::color::Color;
+use crate::material::Material;
+use crate::obj::{self, ObjModel};
+use crate::object::Object;
+use crate::ply;
+use crate::stl;
+use crate::triangle::{SmoothTriangle, Triangle};
+use crate::vector::Vector;
+
+// triangles with less area than this times their longest edge squared are treated as lines or points
+const DEGENERATE_AREA: f64 = 1e-12;
+
+// how much the triangles around a vertex count towards its smooth normal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalWeighting {
+    // by their area, small triangles hardly change the normal
+    Area,
+    // by their angle at the vertex, so splitting a triangle in two doesn't change the normal
+    Angle,
+}
+
+// indexed triangle mesh, what the model loaders produce. Vertices are shared between triangles,
+// a vertex is a position plus, if the mesh has them, a normal, texture coordinates, a tangent and a color
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub positions: Vec<Vector>,
+    // one per position, or empty if the mesh has no normals
+    pub normals: Vec<Vector>,
+    // one per position, or empty if the mesh has no texture coordinates
+    pub uvs: Vec<(f64, f64)>,
+    // one per position, or empty. Normalized direction in which u grows, perpendicular to the normal
+    pub tangents: Vec<Vector>,
+    // one per position, or empty if the mesh has no vertex colors
+    pub colors: Vec<Color>,
+    // three vertex indices per triangle, counter-clockwise seen from the front
+    pub indices: Vec<[usize; 3]>,
+    // materials used by the triangles
+    pub materials: Vec<Material>,
+    // index into materials of every triangle, empty if all triangles use the default material
+    pub material_indices: Vec<usize>,
+}
+
+impl Mesh {
+    pub fn new() -> Mesh {
+        Mesh::default()
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn triangle(&self, index: usize) -> Triangle {
+        let [a, b, c] = self.indices[index];
+        Triangle::new(self.positions[a], self.positions[b], self.positions[c])
+    }
+
+    // material of a triangle. Without materials, triangles of a mesh with vertex colors are diffuse
+    // in the average color of their corners
+    pub fn material(&self, triangle_index: usize) -> Material {
+        if self.material_indices.is_empty() && !self.colors.is_empty() {
+            let [a, b, c] = self.indices[triangle_index];
+            return Material::new((self.colors[a] + self.colors[b] + self.colors[c]) * (1.0 / 3.0));
+        }
+        self.material_indices.get(triangle_index).and_then(|&i| self.materials.get(i)).copied().unwrap_or_default()
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        Aabb::from_points(&self.positions)
+    }
+
+    // adds the triangles of other behind the ones of this mesh. Normals, texture coordinates, tangents and colors
+    // are only kept if both meshes have them
+    pub fn append(&mut self, other: &Mesh) {
+        let offset = self.positions.len();
+        let counts = (offset, other.positions.len());
+        extend_attribute(&mut self.normals, &other.normals, counts);
+        extend_attribute(&mut self.uvs, &other.uvs, counts);
+        extend_attribute(&mut self.tangents, &other.tangents, counts);
+        extend_attribute(&mut self.colors, &other.colors, counts);
+        self.positions.extend_from_slice(&other.positions);
+
+        // the material indices are filled in for both meshes as soon as one of them has any
+        if !self.material_indices.is_empty() || !other.material_indices.is_empty() {
+            if self.material_indices.is_empty() {
+                self.materials.clear();
+                if !self.indices.is_empty() {
+                    self.materials.push(Material::default());
+                    self.material_indices = vec![0; self.indices.len()];
+                }
+            }
+            let first = self.materials.len();
+            if other.material_indices.is_empty() {
+                self.materials.push(Material::default());
+                self.material_indices.extend(std::iter::repeat_n(first, other.indices.len()));
+            } else {
+                self.materials.extend_from_slice(&other.materials);
+                self.material_indices.extend(other.material_indices.iter().map(|i| i + first));
+            }
+        }
+        self.indices.extend(other.indices.iter().map(|[a, b, c]| [a + offset, b + offset, c + offset]));
+    }
+
+    // merges vertices that are at most tolerance apart and have the same normal, texture coordinates, tangent and color,
+    // and returns how many vertices were removed. Formats that store the corners of every triangle on their own,
+    // like STL, share vertices between triangles after this. A tolerance of 0 only merges identical positions
+    pub fn weld(&mut self, tolerance: f64) -> usize {
+        // vertices within tolerance are in the same or a neighbouring cell
+        let cell_size = tolerance.max(self.bounds().size().length() * 1e-6).max(f64::MIN_POSITIVE);
+        let cell = |p: &Vector| ((p.x / cell_size).floor() as i64, (p.y / cell_size).floor() as i64, (p.z / cell_size).floor() as i64);
+        let mut grid: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+        // old index of every vertex that is kept, and the new index of every old vertex
+        let mut kept: Vec<usize> = Vec::new();
+        let mut remap: Vec<usize> = Vec::with_capacity(self.positions.len());
+        for i in 0..self.positions.len() {
+            let p = self.positions[i];
+            let (x, y, z) = cell(&p);
+            let neighbours = (-1..=1).flat_map(|dx| (-1..=1).flat_map(move |dy| (-1..=1).map(move |dz| (x + dx, y + dy, z + dz))));
+            let found = neighbours
+                .filter_map(|key| grid.get(&key))
+                .flatten()
+                .copied()
+                .find(|&k| p.distance(&self.positions[kept[k]]) <= tolerance && self.same_attributes(i, kept[k]));
+            let index = found.unwrap_or_else(|| {
+                kept.push(i);
+                grid.entry((x, y, z)).or_default().push(kept.len() - 1);
+                kept.len() - 1
+            });
+            remap.push(index);
+        }
+
+        let removed = self.positions.len() - kept.len();
+        self.keep_vertices(&kept);
+        for triangle in self.indices.iter_mut() {
+            *triangle = triangle.map(|i| remap[i]);
+        }
+        removed
+    }
+
+    // replaces the vertices by the ones at the old indices in kept, which can repeat vertices.
+    // The triangles have to be renumbered by the caller
+    fn keep_vertices(&mut self, kept: &[usize]) {
+        self.positions = kept.iter().map(|&i| self.positions[i]).collect();
+        if !self.normals.is_empty() {
+            self.normals = kept.iter().map(|&i| self.normals[i]).collect();
+        }
+        if !self.uvs.is_empty() {
+            self.uvs = kept.iter().map(|&i| self.uvs[i]).collect();
+        }
+        if !self.tangents.is_empty() {
+            self.tangents = kept.iter().map(|&i| self.tangents[i]).collect();
+        }
+        if !self.colors.is_empty() {
+            self.colors = kept.iter().map(|&i| self.colors[i]).collect();
+        }
+    }
+
+    fn same_attributes(&self, a: usize, b: usize) -> bool {
+        (self.normals.is_empty() || self.normals[a] == self.normals[b])
+            && (self.uvs.is_empty() || self.uvs[a] == self.uvs[b])
+            && (self.tangents.is_empty() || self.tangents[a] == self.tangents[b])
+            && (self.colors.is_empty() || self.colors[a] == self.colors[b])
+    }
+
+    // removes triangles without area, the ones using a vertex twice or with all corners on a line,
+    // and returns how many there were. Vertices no triangle uses anymore are removed too
+    pub fn remove_degenerate(&mut self) -> usize {
+        let keep: Vec<bool> = (0..self.indices.len())
+            .map(|index| {
+                let [a, b, c] = self.indices[index];
+                let t = self.triangle(index);
+                let longest = (t.b - t.a).length_squared().max((t.c - t.b).length_squared()).max((t.a - t.c).length_squared());
+                a != b && b != c && c != a && t.area() > DEGENERATE_AREA * longest
+            })
+            .collect();
+        let before = self.indices.len();
+        let mut keep_iter = keep.iter();
+        self.indices.retain(|_| *keep_iter.next().unwrap());
+        if !self.material_indices.is_empty() {
+            let mut keep_iter = keep.iter();
+            self.material_indices.retain(|_| *keep_iter.next().unwrap());
+        }
+
+        // old index of every vertex that is still used, and the new index of every old vertex
+        let mut used = vec![false; self.positions.len()];
+        for &i in self.indices.iter().flatten() {
+            used[i] = true;
+        }
+        let kept: Vec<usize> = (0..used.len()).filter(|&i| used[i]).collect();
+        let mut remap = vec![0; used.len()];
+        for (new, &old) in kept.iter().enumerate() {
+            remap[old] = new;
+        }
+        self.keep_vertices(&kept);
+        for triangle in self.indices.iter_mut() {
+            *triangle = triangle.map(|i| remap[i]);
+        }
+        before - self.indices.len()
+    }
+
+    // gives every triangle the normal of its face at all corners, so the mesh is shaded flat. Vertices shared by
+    // triangles facing different ways are split. Triangles without area get (0, 0, 0)
+    pub fn compute_flat_normals(&mut self) {
+        let corner_normals: Vec<[Vector; 3]> = (0..self.indices.len()).map(|index| [self.face_normal(index); 3]).collect();
+        self.set_corner_normals(&corner_normals);
+    }
+
+    // gives every vertex the weighted average of the face normals of the triangles around its position, so the mesh
+    // is shaded smoothly. Triangles whose faces are more than crease_angle (in radians) apart don't share their
+    // normals, vertices on such an edge are split so the edge stays sharp. Vertices at the same position are
+    // smoothed together even if they aren't shared, like the ones read from STL before welding
+    pub fn compute_smooth_normals(&mut self, weighting: NormalWeighting, crease_angle: f64) {
+        let face_normals: Vec<Vector> = (0..self.indices.len()).map(|index| self.face_normal(index)).collect();
+        let weights: Vec<[f64; 3]> = (0..self.indices.len()).map(|index| self.corner_weights(index, weighting)).collect();
+        // (triangle, corner) of all corners at a position
+        let mut corners_at: HashMap<[u64; 3], Vec<(usize, usize)>> = HashMap::new();
+        for (index, triangle) in self.indices.iter().enumerate() {
+            for (corner, &vertex) in triangle.iter().enumerate() {
+                corners_at.entry(vector_key(&self.positions[vertex])).or_default().push((index, corner));
+            }
+        }
+
+        let min_cos = crease_angle.cos();
+        let mut corner_normals = vec![[Vector::new(0.0, 0.0, 0.0); 3]; self.indices.len()];
+        for corners in corners_at.values() {
+            for &(index, corner) in corners {
+                let face = face_normals[index];
+                let mut normal = Vector::new(0.0, 0.0, 0.0);
+                for &(other, other_corner) in corners {
+                    if other == index || face.dot(&face_normals[other]) >= min_cos {
+                        normal += face_normals[other] * weights[other][other_corner];
+                    }
+                }
+                if normal.length_squared() > 0.0 {
+                    normal.normalize();
+                    corner_normals[index][corner] = normal;
+                } else {
+                    corner_normals[index][corner] = face;
+                }
+            }
+        }
+        self.set_corner_normals(&corner_normals);
+    }
+
+    // computes the tangents from the texture coordinates, see: https://learnopengl.com/Advanced-Lighting/Normal-Mapping
+    // They are averaged over the triangles around a vertex and made perpendicular to its normal, so the normals
+    // should be computed first. A mesh without texture coordinates gets no tangents
+    pub fn compute_tangents(&mut self) {
+        self.tangents.clear();
+        if self.uvs.is_empty() {
+            return;
+        }
+        let mut sums = vec![Vector::new(0.0, 0.0, 0.0); self.positions.len()];
+        for &[a, b, c] in &self.indices {
+            let (edge1, edge2) = (self.positions[b] - self.positions[a], self.positions[c] - self.positions[a]);
+            let (du1, dv1) = (self.uvs[b].0 - self.uvs[a].0, self.uvs[b].1 - self.uvs[a].1);
+            let (du2, dv2) = (self.uvs[c].0 - self.uvs[a].0, self.uvs[c].1 - self.uvs[a].1);
+            let det = du1 * dv2 - du2 * dv1;
+            // the texture isn't stretched over the triangle, it has no u direction
+            if det == 0.0 {
+                continue;
+            }
+            let tangent = (edge1 * dv2 - edge2 * dv1) * (1.0 / det);
+            for vertex in [a, b, c] {
+                sums[vertex] += tangent;
+            }
+        }
+        self.tangents = sums
+            .into_iter()
+            .enumerate()
+            .map(|(vertex, mut tangent)| {
+                if let Some(normal) = self.normals.get(vertex) {
+                    tangent -= *normal * normal.dot(&tangent);
+                }
+                if tangent.length_squared() > 0.0 {
+                    tangent.normalize();
+                    tangent
+                } else {
+                    Vector::new(0.0, 0.0, 0.0)
+                }
+            })
+            .collect();
+    }
+
+    // normalized face normal, (0, 0, 0) for triangles without area
+    fn face_normal(&self, index: usize) -> Vector {
+        let triangle = self.triangle(index);
+        if triangle.area() > 0.0 {
+            triangle.normal()
+        } else {
+            Vector::new(0.0, 0.0, 0.0)
+        }
+    }
+
+    // how much each corner of a triangle counts towards the normal of its vertex
+    fn corner_weights(&self, index: usize, weighting: NormalWeighting) -> [f64; 3] {
+        let triangle = self.triangle(index);
+        if triangle.area() == 0.0 {
+            return [0.0; 3];
+        }
+        match weighting {
+            NormalWeighting::Area => [triangle.area(); 3],
+            NormalWeighting::Angle => {
+                let corners = [triangle.a, triangle.b, triangle.c];
+                // rounding can push the cosine of tiny angles past 1, max turns the NaN from acos into 0
+                [0, 1, 2].map(|i| (corners[(i + 1) % 3] - corners[i]).angle_radians(&(corners[(i + 2) % 3] - corners[i])).max(0.0))
+            }
+        }
+    }
+
+    // sets the normal of every corner of every triangle. Corners of a vertex that get different normals are
+    // split into vertices of their own. Vertices that no triangle uses are removed
+    fn set_corner_normals(&mut self, corner_normals: &[[Vector; 3]]) {
+        let mut vertices: HashMap<(usize, [u64; 3]), usize> = HashMap::new();
+        let mut kept = Vec::new();
+        let mut normals = Vec::new();
+        for (triangle, corners) in self.indices.iter_mut().zip(corner_normals) {
+            for (vertex, normal) in triangle.iter_mut().zip(corners) {
+                *vertex = *vertices.entry((*vertex, vector_key(normal))).or_insert_with(|| {
+                    kept.push(*vertex);
+                    normals.push(*normal);
+                    kept.len() - 1
+                });
+            }
+        }
+        self.keep_vertices(&kept);
+        self.normals = normals;
+    }
+
+    // reads a mesh in the format of the file extension: obj (with its materials), stl or ply
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Mesh> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("obj") => Ok(ObjModel::load(path)?.mesh),
+            Some("stl") => stl::load(path),
+            Some("ply") => ply::load(path),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unsupported mesh format: {}", path.display()))),
+        }
+    }
+
+    // writes the mesh in the format of the file extension, obj or stl (binary). An OBJ mesh with materials
+    // gets a material library next to it, with the same name and the extension mtl
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("obj") => {
+                let mut library = None;
+                if !self.material_indices.is_empty() {
+                    let mtl_path = path.with_extension("mtl");
+                    let mut file = BufWriter::new(File::create(&mtl_path)?);
+                    obj::write_mtl(&self.materials, &mut file)?;
+                    file.flush()?;
+                    library = mtl_path.file_name().and_then(|name| name.to_str()).map(String::from);
+                }
+                let mut file = BufWriter::new(File::create(path)?);
+                obj::write_obj(self, library.as_deref(), &mut file)?;
+                file.flush()
+            }
+            Some("stl") => {
+                let mut file = BufWriter::new(File::create(path)?);
+                stl::write_binary(self, &mut file)?;
+                file.flush()
+            }
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unsupported mesh format: {}", path.display()))),
+        }
+    }
+
+    // error describing the first index that doesn't fit the buffers, checked by the loaders before handing out a mesh
+    pub fn validate(&self) -> Result<(), String> {
+        let count = self.positions.len();
+        if !self.normals.is_empty() && self.normals.len() != count {
+            return Err(format!("{} normals for {} vertices", self.normals.len(), count));
+        }
+        if !self.uvs.is_empty() && self.uvs.len() != count {
+            return Err(format!("{} texture coordinates for {} vertices", self.uvs.len(), count));
+        }
+        if !self.tangents.is_empty() && self.tangents.len() != count {
+            return Err(format!("{} tangents for {} vertices", self.tangents.len(), count));
+        }
+        if !self.colors.is_empty() && self.colors.len() != count {
+            return Err(format!("{} colors for {} vertices", self.colors.len(), count));
+        }
+        if let Some((t, index)) = self.indices.iter().enumerate().find_map(|(t, triangle)| triangle.iter().find(|&&i| i >= count).map(|i| (t, i))) {
+            return Err(format!("triangle {} uses vertex {}, there are {} vertices", t, index, count));
+        }
+        if !self.material_indices.is_empty() && self.material_indices.len() != self.indices.len() {
+            return Err(format!("{} material indices for {} triangles", self.material_indices.len(), self.indices.len()));
+        }
+        if let Some(index) = self.material_indices.iter().find(|&&i| i >= self.materials.len()) {
+            return Err(format!("material {} is used, there are {} materials", index, self.materials.len()));
+        }
+        Ok(())
+    }
+
+    // object with a shape and its material for every triangle, in the same order. Meshes with normals
+    // are made of SmoothTriangles interpolating them, the others of flat Triangles
+    pub fn to_object(&self) -> Object {
+        let mut object = Object::from_shapes(Vec::new());
+        for index in 0..self.indices.len() {
+            if self.normals.is_empty() {
+                object.add_shape_with_material(self.triangle(index), self.material(index));
+            } else {
+                let normals = self.indices[index].map(|vertex| self.normals[vertex]);
+                object.add_shape_with_material(SmoothTriangle::new(self.triangle(index), normals), self.material(index));
+            }
+        }
+        object
+    }
+}
+
+// bits of the components, the same for vectors that are exactly equal
+fn vector_key(v: &Vector) -> [u64; 3] {
+    // adding 0 turns -0 into 0
+    [v.x, v.y, v.z].map(|c| (c + 0.0).to_bits())
+}
+
+// appends theirs to ours if both meshes have the attribute, (our vertex count, their vertex count),
+// otherwise the merged mesh doesn't have it
+fn extend_attribute<T: Copy>(ours: &mut Vec<T>, theirs: &[T], (our_count, their_count): (usize, usize)) {
+    if ours.len() == our_count && theirs.len() == their_count {
+        ours.extend_from_slice(theirs);
+    } else {
+        ours.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::any::Any;
+
+    use crate::color::Color;
+    use crate::line::Line;
+    use crate::math::as_radians;
+    use crate::sphere::Sphere;
+
+    // unit square in the xy plane made of two triangles
+    fn square() -> Mesh {
+        let mut mesh = Mesh::new();
+        mesh.positions = vec![Vector::new(0.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), Vector::new(1.0, 1.0, 0.0), Vector::new(0.0, 1.0, 0.0)];
+        mesh.indices = vec![[0, 1, 2], [0, 2, 3]];
+        mesh
+    }
+
+    #[test]
+    fn to_object_test() {
+        let mut mesh = square();
+        mesh.materials = vec![Material::new(Color::new(1.0, 0.0, 0.0)), Material::new(Color::new(0.0, 1.0, 0.0))];
+        mesh.material_indices = vec![1, 0];
+        assert!(mesh.validate().is_ok());
+        assert_eq!(mesh.bounds(), Aabb::new(Vector::new(0.0, 0.0, 0.0), Vector::new(1.0, 1.0, 0.0)));
+
+        let object = mesh.to_object();
+        assert_eq!(object.shapes.len(), 2);
+        let bvh = object.bvh();
+        let hit = Line::new(Vector::new(0.25, 0.75, 5.0), Vector::new(0.0, 0.0, -1.0)).intersection_bvh(&object, &bvh, &false);
+        assert_eq!(hit.t(), Some(5.0));
+        assert_eq!(hit.hit().unwrap().shape_index, 1);
+        assert_eq!(hit.normal(), Some(Vector::new(0.0, 0.0, 1.0)));
+        assert_eq!(object.material(1).albedo, Color::new(1.0, 0.0, 0.0));
+
+        mesh.indices.push([0, 1, 4]);
+        assert!(mesh.validate().unwrap_err().contains("vertex 4"));
+    }
+
+    #[test]
+    fn append_test() {
+        let mut mesh = square();
+        let mut other = square();
+        other.materials = vec![Material::new(Color::new(1.0, 0.0, 0.0))];
+        other.material_indices = vec![0, 0];
+        other.uvs = vec![(0.0, 0.0); 4];
+        mesh.append(&other);
+        assert!(mesh.validate().is_ok());
+        assert_eq!(mesh.triangle_count(), 4);
+        assert_eq!(mesh.indices[3], [4, 6, 7]);
+        // only the second square has texture coordinates, so the mesh can't have them
+        assert!(mesh.uvs.is_empty());
+        assert_eq!(mesh.material(0), Material::default());
+        assert_eq!(mesh.material(3).albedo, Color::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn weld_test() {
+        // the two triangles of the square with their own corners, like STL stores them
+        let mut mesh = square();
+        mesh.positions = mesh.indices.iter().flatten().map(|&i| mesh.positions[i]).collect();
+        mesh.indices = vec![[0, 1, 2], [3, 4, 5]];
+        mesh.positions[3].x += 1e-4;
+        assert_eq!(mesh.clone().weld(0.0), 1);
+        assert_eq!(mesh.weld(1e-3), 2);
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+
+        // vertices with different colors stay apart
+        let mut mesh = square();
+        mesh.positions.push(mesh.positions[0]);
+        mesh.indices[1][0] = 4;
+        mesh.colors = vec![Color::new(1.0, 0.0, 0.0), Color::new(1.0, 0.0, 0.0), Color::new(0.0, 0.0, 1.0), Color::new(0.0, 0.0, 1.0), Color::new(0.0, 1.0, 0.0)];
+        assert_eq!(mesh.weld(0.0), 0);
+        assert_eq!(mesh.material(0).albedo, Color::new(2.0, 0.0, 1.0) * (1.0 / 3.0));
+    }
+
+    #[test]
+    fn remove_degenerate_test() {
+        let mut mesh = square();
+        mesh.positions.push(Vector::new(2.0, 0.0, 0.0));
+        mesh.indices.extend([[0, 1, 4], [2, 2, 3], [0, 3, 2]]);
+        mesh.materials = vec![Material::default(), Material::new(Color::new(1.0, 0.0, 0.0))];
+        mesh.material_indices = vec![0, 0, 0, 0, 1];
+        assert_eq!(mesh.remove_degenerate(), 2);
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3], [0, 3, 2]]);
+        // the vertex only the triangle on a line used is gone
+        assert_eq!(mesh.vertex_count(), 4);
+        assert!(mesh.validate().is_ok());
+        assert_eq!(mesh.material(2).albedo, Color::new(1.0, 0.0, 0.0));
+    }
+
+    fn cube() -> Mesh {
+        let mut mesh = Object::cube(1.0).to_mesh(16).unwrap();
+        mesh.weld(0.0);
+        mesh
+    }
+
+    #[test]
+    fn flat_normals_test() {
+        // the square is flat, its vertices can stay shared
+        let mut mesh = square();
+        mesh.compute_flat_normals();
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.normals, vec![Vector::new(0.0, 0.0, 1.0); 4]);
+
+        // every face of the cube gets its own four corners
+        let mut mesh = cube();
+        assert_eq!(mesh.vertex_count(), 8);
+        mesh.compute_flat_normals();
+        assert!(mesh.validate().is_ok());
+        assert_eq!(mesh.vertex_count(), 24);
+        for index in 0..mesh.triangle_count() {
+            let normal = mesh.triangle(index).normal();
+            assert!(mesh.indices[index].iter().all(|&vertex| mesh.normals[vertex] == normal));
+        }
+    }
+
+    #[test]
+    fn smooth_normals_test() {
+        // the faces of the cube are 90 degrees apart, a smaller crease angle keeps the edges sharp
+        let mut mesh = cube();
+        mesh.compute_smooth_normals(NormalWeighting::Angle, as_radians(60.0));
+        assert_eq!(mesh.vertex_count(), 24);
+        let mut flat = cube();
+        flat.compute_flat_normals();
+        for index in 0..mesh.triangle_count() {
+            assert_eq!(mesh.indices[index].map(|vertex| mesh.normals[vertex]), flat.indices[index].map(|vertex| flat.normals[vertex]));
+        }
+
+        // smoothed over the edges, every corner points away from the center. Two triangles of a face meet at some
+        // corners, weighting by angle counts each face the same there
+        let mut mesh = cube();
+        mesh.compute_smooth_normals(NormalWeighting::Angle, as_radians(100.0));
+        assert_eq!(mesh.vertex_count(), 8);
+        for (position, normal) in mesh.positions.iter().zip(&mesh.normals) {
+            let mut expected = *position;
+            expected.normalize();
+            assert_eq!(*normal, expected);
+        }
+        let mut mesh = cube();
+        mesh.compute_smooth_normals(NormalWeighting::Area, as_radians(100.0));
+        assert_eq!(mesh.vertex_count(), 8);
+        assert!(mesh.positions.iter().zip(&mesh.normals).any(|(position, normal)| normal.dot(position) < position.length() * 0.99));
+    }
+
+    #[test]
+    fn tangents_test() {
+        let mut mesh = square();
+        mesh.compute_tangents();
+        assert!(mesh.tangents.is_empty());
+
+        // u grows along x, v along y
+        mesh.uvs = mesh.positions.iter().map(|p| (p.x, p.y)).collect();
+        mesh.compute_flat_normals();
+        mesh.compute_tangents();
+        assert!(mesh.validate().is_ok());
+        assert_eq!(mesh.tangents, vec![Vector::new(1.0, 0.0, 0.0); 4]);
+
+        // made perpendicular to tilted normals
+        mesh.normals = vec![Vector::new(-1.0, 0.0, 1.0) * (1.0 / 2.0f64.sqrt()); 4];
+        mesh.compute_tangents();
+        assert_eq!(mesh.tangents[0], Vector::new(1.0, 0.0, 1.0) * (1.0 / 2.0f64.sqrt()));
+    }
+
+    #[test]
+    fn smooth_shading_test() {
+        // hits on a smooth sphere mesh are much closer to the normals of the real sphere than on a flat one
+        let sphere = Object::from_shapes(vec![Box::new(Sphere::new(Vector::new(0.0, 0.0, 0.0), 1.0))]);
+        let mut mesh = sphere.to_mesh(16).unwrap();
+        mesh.weld(0.0);
+        let flat = mesh.to_object();
+        mesh.compute_smooth_normals(NormalWeighting::Angle, as_radians(60.0));
+        let smooth = mesh.to_object();
+        assert!(smooth.shapes.iter().all(|shape| (shape.as_ref() as &dyn Any).is::<SmoothTriangle>()));
+
+        let error = |object: &Object, line: &Line| {
+            let hit = line.intersection_bvh(object, &object.bvh(), &false);
+            let point = line.point_on_line(&hit.t().unwrap());
+            hit.normal().unwrap().angle_degrees(&point)
+        };
+        for line in [Line::new(Vector::new(0.3, 0.2, 5.0), Vector::new(0.0, 0.0, -1.0)), Line::new(Vector::new(5.0, 0.45, -0.1), Vector::new(-1.0, 0.0, 0.0))] {
+            assert!(error(&smooth, &line) < 1.0);
+            assert!(error(&flat, &line) > 2.0);
+        }
+    }
+}
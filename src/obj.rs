@@ -0,0 +1,485 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::color::Color;
+use crate::material::{Brdf, Material};
+use crate::mesh::Mesh;
+use crate::vector::Vector;
+
+// Wavefront OBJ model, see: https://en.wikipedia.org/wiki/Wavefront_.obj_file
+// Reads vertices (v), texture coordinates (vt), normals (vn), faces (f) and the material statements
+// mtllib and usemtl. Faces with more than three corners are split into a fan of triangles, so they should be convex.
+// Groups, smoothing groups, lines and free-form geometry are skipped
+#[derive(Debug, Clone)]
+pub struct ObjModel {
+    pub mesh: Mesh,
+    // files named by mtllib, relative to the OBJ file
+    pub material_libraries: Vec<String>,
+    // usemtl name of every material of the mesh. Triangles before the first usemtl use a material named ""
+    pub material_names: Vec<String>,
+}
+
+impl ObjModel {
+    // errors start with the line number they were found on
+    pub fn parse(text: &str) -> Result<ObjModel, String> {
+        let mut positions: Vec<Vector> = Vec::new();
+        let mut uvs: Vec<(f64, f64)> = Vec::new();
+        let mut normals: Vec<Vector> = Vec::new();
+        let mut model = ObjModel { mesh: Mesh::new(), material_libraries: Vec::new(), material_names: Vec::new() };
+        // mesh vertex of every distinct position/uv/normal combination used by a face
+        let mut vertices: HashMap<(usize, Option<usize>, Option<usize>), usize> = HashMap::new();
+        let (mut all_uvs, mut all_normals) = (true, true);
+        let mut material: Option<usize> = None;
+        let mut materials: Vec<Option<usize>> = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let error = |message: String| format!("line {}: {}", number + 1, message);
+            let line = line.split('#').next().unwrap_or("");
+            let mut tokens = line.split_whitespace();
+            let Some(keyword) = tokens.next() else { continue };
+            let values: Vec<&str> = tokens.collect();
+            match keyword {
+                "v" => {
+                    let [x, y, z] = parse_numbers::<3>(keyword, &values).map_err(error)?;
+                    positions.push(Vector::new(x, y, z));
+                }
+                "vt" => {
+                    let u = parse_numbers::<1>(keyword, &values).map_err(error)?[0];
+                    let v = if values.len() > 1 { parse_numbers::<1>(keyword, &values[1..]).map_err(error)?[0] } else { 0.0 };
+                    uvs.push((u, v));
+                }
+                "vn" => {
+                    let [x, y, z] = parse_numbers::<3>(keyword, &values).map_err(error)?;
+                    normals.push(Vector::new(x, y, z));
+                }
+                "f" => {
+                    if values.len() < 3 {
+                        return Err(error(format!("face needs at least 3 vertices, got {}", values.len())));
+                    }
+                    let mut corners = Vec::with_capacity(values.len());
+                    for value in &values {
+                        let (p, t, n) = parse_corner(value, positions.len(), uvs.len(), normals.len()).map_err(error)?;
+                        all_uvs &= t.is_some();
+                        all_normals &= n.is_some();
+                        let next = vertices.len();
+                        let index = *vertices.entry((p, t, n)).or_insert(next);
+                        if index == next {
+                            model.mesh.positions.push(positions[p]);
+                            model.mesh.uvs.push(t.map_or((0.0, 0.0), |t| uvs[t]));
+                            model.mesh.normals.push(n.map_or(Vector::new(0.0, 0.0, 0.0), |n| normals[n]));
+                        }
+                        corners.push(index);
+                    }
+                    for i in 1..corners.len() - 1 {
+                        model.mesh.indices.push([corners[0], corners[i], corners[i + 1]]);
+                        materials.push(material);
+                    }
+                }
+                "usemtl" => {
+                    let name = values.join(" ");
+                    if name.is_empty() {
+                        return Err(error(String::from("usemtl needs a material name")));
+                    }
+                    material = Some(model.material_index(&name));
+                }
+                "mtllib" => {
+                    if values.is_empty() {
+                        return Err(error(String::from("mtllib needs a file name")));
+                    }
+                    model.material_libraries.extend(values.iter().map(|v| v.to_string()));
+                }
+                _ => {}
+            }
+        }
+
+        // a mesh has normals and texture coordinates for every vertex or for none
+        if !all_uvs {
+            model.mesh.uvs.clear();
+        }
+        if !all_normals {
+            model.mesh.normals.clear();
+        }
+        if !model.material_names.is_empty() {
+            let unnamed = if materials.contains(&None) { Some(model.material_index("")) } else { None };
+            model.mesh.material_indices = materials.iter().map(|m| m.or(unnamed).unwrap()).collect();
+            model.mesh.materials = vec![Material::default(); model.material_names.len()];
+        }
+        Ok(model)
+    }
+
+    // reads an OBJ file and the material libraries it uses. Materials that aren't in any library
+    // keep the default material
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<ObjModel> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let mut model = ObjModel::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))?;
+        let directory = path.parent().unwrap_or(Path::new(""));
+        for library in model.material_libraries.clone() {
+            let library_path = directory.join(&library);
+            let text = fs::read_to_string(&library_path)
+                .map_err(|e| io::Error::new(e.kind(), format!("could not read material library {}: {}", library_path.display(), e)))?;
+            let materials = parse_mtl(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", library_path.display(), e)))?;
+            model.set_materials(&materials);
+        }
+        Ok(model)
+    }
+
+    // replaces the materials whose name is in library
+    pub fn set_materials(&mut self, library: &[(String, Material)]) {
+        for (name, material) in library {
+            if let Some(index) = self.material_names.iter().position(|n| n == name) {
+                self.mesh.materials[index] = *material;
+            }
+        }
+    }
+
+    fn material_index(&mut self, name: &str) -> usize {
+        self.material_names.iter().position(|n| n == name).unwrap_or_else(|| {
+            self.material_names.push(String::from(name));
+            self.material_names.len() - 1
+        })
+    }
+}
+
+// materials of an MTL file by name, see: https://paulbourke.net/dataformats/mtl/
+// Kd is the albedo, Ks and Ns the Blinn-Phong highlight, Ke the emission, Ni the index of refraction
+// and d (or Tr = 1 - d) the opacity. The PBR extension Pr and Pm switch to Cook-Torrance. Texture maps are skipped
+pub fn parse_mtl(text: &str) -> Result<Vec<(String, Material)>, String> {
+    let mut materials: Vec<(String, Material)> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let error = |message: String| format!("line {}: {}", number + 1, message);
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else { continue };
+        let values: Vec<&str> = tokens.collect();
+        if keyword == "newmtl" {
+            if values.is_empty() {
+                return Err(error(String::from("newmtl needs a material name")));
+            }
+            materials.push((values.join(" "), Material::default()));
+            continue;
+        }
+        if !["Kd", "Ks", "Ke", "Ns", "Ni", "d", "Tr", "Pr", "Pm"].contains(&keyword) {
+            continue;
+        }
+        let Some((_, material)) = materials.last_mut() else {
+            return Err(error(format!("{} before the first newmtl", keyword)));
+        };
+        match keyword {
+            "Kd" | "Ks" | "Ke" => {
+                let color = parse_color(keyword, &values).map_err(error)?;
+                match keyword {
+                    "Kd" => material.albedo = color,
+                    "Ks" => {
+                        material.specular = color;
+                        if material.brdf == Brdf::Lambert && color != Color::black() {
+                            material.brdf = Brdf::BlinnPhong;
+                        }
+                    }
+                    _ => material.emission = color,
+                }
+            }
+            _ => {
+                let [value] = parse_numbers::<1>(keyword, &values).map_err(error)?;
+                match keyword {
+                    "Ns" => material.shininess = value.max(1.0),
+                    "Ni" => material.ior = value,
+                    "d" => material.transparency = (1.0 - value).clamp(0.0, 1.0),
+                    "Tr" => material.transparency = value.clamp(0.0, 1.0),
+                    "Pr" => {
+                        material.brdf = Brdf::CookTorrance;
+                        material.roughness = value.clamp(0.0, 1.0);
+                    }
+                    _ => {
+                        material.brdf = Brdf::CookTorrance;
+                        material.metalness = value.clamp(0.0, 1.0);
+                    }
+                }
+            }
+        }
+    }
+    Ok(materials)
+}
+
+// writes the mesh as OBJ, with normals and texture coordinates if it has them. Materials are named
+// material0, material1, ... after their index and read from material_library, see write_mtl
+pub fn write_obj<W: Write>(mesh: &Mesh, material_library: Option<&str>, writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "# {} vertices, {} triangles", mesh.vertex_count(), mesh.triangle_count())?;
+    if let Some(library) = material_library {
+        writeln!(writer, "mtllib {}", library)?;
+    }
+    for p in &mesh.positions {
+        writeln!(writer, "v {} {} {}", p.x, p.y, p.z)?;
+    }
+    for (u, v) in &mesh.uvs {
+        writeln!(writer, "vt {} {}", u, v)?;
+    }
+    for n in &mesh.normals {
+        writeln!(writer, "vn {} {} {}", n.x, n.y, n.z)?;
+    }
+    let mut material = None;
+    for (index, triangle) in mesh.indices.iter().enumerate() {
+        if let Some(&used) = mesh.material_indices.get(index) {
+            if material != Some(used) {
+                writeln!(writer, "usemtl material{}", used)?;
+                material = Some(used);
+            }
+        }
+        let corners: Vec<String> = triangle
+            .iter()
+            .map(|i| match (mesh.uvs.is_empty(), mesh.normals.is_empty()) {
+                (true, true) => format!("{}", i + 1),
+                (false, true) => format!("{}/{}", i + 1, i + 1),
+                (true, false) => format!("{}//{}", i + 1, i + 1),
+                (false, false) => format!("{}/{}/{}", i + 1, i + 1, i + 1),
+            })
+            .collect();
+        writeln!(writer, "f {}", corners.join(" "))?;
+    }
+    Ok(())
+}
+
+// writes the materials as MTL, named material0, material1, ... after their index.
+// Reflectivity can't be written down, so mirrors come back as plain materials
+pub fn write_mtl<W: Write>(materials: &[Material], writer: &mut W) -> io::Result<()> {
+    for (index, m) in materials.iter().enumerate() {
+        writeln!(writer, "newmtl material{}", index)?;
+        writeln!(writer, "Kd {} {} {}", m.albedo.r, m.albedo.g, m.albedo.b)?;
+        match m.brdf {
+            Brdf::Lambert => {}
+            Brdf::BlinnPhong => {
+                writeln!(writer, "Ks {} {} {}", m.specular.r, m.specular.g, m.specular.b)?;
+                writeln!(writer, "Ns {}", m.shininess)?;
+            }
+            Brdf::CookTorrance => {
+                writeln!(writer, "Pr {}", m.roughness)?;
+                writeln!(writer, "Pm {}", m.metalness)?;
+            }
+        }
+        if m.emission != Color::black() {
+            writeln!(writer, "Ke {} {} {}", m.emission.r, m.emission.g, m.emission.b)?;
+        }
+        if m.transparency > 0.0 {
+            writeln!(writer, "d {}", 1.0 - m.transparency)?;
+            writeln!(writer, "Ni {}", m.ior)?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+// the first N values as numbers, more values are allowed and skipped
+fn parse_numbers<const N: usize>(keyword: &str, values: &[&str]) -> Result<[f64; N], String> {
+    if values.len() < N {
+        return Err(format!("{} needs {} numbers, got {}", keyword, N, values.len()));
+    }
+    let mut numbers = [0.0; N];
+    for (number, value) in numbers.iter_mut().zip(values) {
+        *number = value.parse::<f64>().map_err(|_| format!("invalid number in {}: {}", keyword, value))?;
+    }
+    Ok(numbers)
+}
+
+// r g b, or a single value for gray
+fn parse_color(keyword: &str, values: &[&str]) -> Result<Color, String> {
+    if values.len() < 3 {
+        let [gray] = parse_numbers::<1>(keyword, values)?;
+        return Ok(Color::new(gray, gray, gray));
+    }
+    let [r, g, b] = parse_numbers::<3>(keyword, values)?;
+    Ok(Color::new(r, g, b))
+}
+
+// position, texture coordinate and normal index of a face corner written as v, v/vt, v//vn or v/vt/vn.
+// OBJ indices start at 1, negative ones count back from the last element read so far
+fn parse_corner(value: &str, positions: usize, uvs: usize, normals: usize) -> Result<(usize, Option<usize>, Option<usize>), String> {
+    let parts: Vec<&str> = value.split('/').collect();
+    if parts.len() > 3 {
+        return Err(format!("invalid face vertex: {}", value));
+    }
+    let index = |part: &str, count: usize, (kind, plural): (&str, &str)| -> Result<usize, String> {
+        let i = part.parse::<i64>().map_err(|_| format!("invalid {} index in face vertex: {}", kind, value))?;
+        let resolved = if i > 0 { i - 1 } else { count as i64 + i };
+        if i == 0 || resolved < 0 || resolved >= count as i64 {
+            return Err(format!("{} index {} out of range, there are {} {}", kind, i, count, plural));
+        }
+        Ok(resolved as usize)
+    };
+    let position = index(parts[0], positions, ("vertex", "vertices"))?;
+    let uv = match parts.get(1) {
+        Some(part) if !part.is_empty() => Some(index(part, uvs, ("texture coordinate", "texture coordinates"))?),
+        _ => None,
+    };
+    let normal = match parts.get(2) {
+        Some(part) if !part.is_empty() => Some(index(part, normals, ("normal", "normals"))?),
+        Some(_) => return Err(format!("missing normal index in face vertex: {}", value)),
+        None => None,
+    };
+    Ok((position, uv, normal))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::line::Line;
+
+    // unit cube with a normal per face and a material for the top
+    const CUBE: &str = "
+        # cube
+        mtllib cube.mtl
+        o cube
+        v -1 -1 -1
+        v  1 -1 -1
+        v  1  1 -1
+        v -1  1 -1
+        v -1 -1  1
+        v  1 -1  1
+        v  1  1  1
+        v -1  1  1
+        vt 0 0
+        vt 1 0
+        vt 1 1
+        vt 0 1
+        vn 0 0 1
+        vn 0 0 -1
+        vn 1 0 0
+        vn -1 0 0
+        vn 0 1 0
+        vn 0 -1 0
+        s off
+        f 5/1/1 6/2/1 7/3/1 8/4/1
+        f 2/1/2 1/2/2 4/3/2 3/4/2
+        f 6/1/3 2/2/3 3/3/3 7/4/3
+        f 1/1/4 5/2/4 8/3/4 4/4/4
+        usemtl top
+        f -1/1/5 -2/2/5 -6/3/5 -5/4/5
+        usemtl bottom
+        f 1/1/6 2/2/6 6/3/6 5/4/6
+    ";
+
+    #[test]
+    fn parse_test() {
+        let model = ObjModel::parse(CUBE).unwrap();
+        let mesh = &model.mesh;
+        assert!(mesh.validate().is_ok());
+        assert_eq!(mesh.triangle_count(), 12);
+        // every face has its own corners, because the normals differ
+        assert_eq!(mesh.vertex_count(), 24);
+        assert_eq!(mesh.normals.len(), 24);
+        assert_eq!(mesh.uvs.len(), 24);
+        assert_eq!(model.material_libraries, vec!["cube.mtl"]);
+        assert_eq!(model.material_names, vec!["top", "bottom", ""]);
+        assert_eq!(mesh.material_indices, vec![2, 2, 2, 2, 2, 2, 2, 2, 0, 0, 1, 1]);
+
+        // the top face, written with negative indices, points up
+        let top = mesh.triangle(8);
+        assert_eq!(top.normal(), Vector::new(0.0, 1.0, 0.0));
+        assert_eq!(mesh.normals[mesh.indices[8][0]], Vector::new(0.0, 1.0, 0.0));
+
+        let object = mesh.to_object();
+        let bvh = object.bvh();
+        for direction in [Vector::new(1.0, 0.0, 0.0), Vector::new(0.0, -1.0, 0.0), Vector::new(0.0, 0.0, -1.0)] {
+            let hit = Line::new(direction * -10.0 + Vector::new(0.1, 0.2, 0.3), direction).intersection_bvh(&object, &bvh, &false);
+            assert!((hit.t().unwrap() - 9.0).abs() < 0.5);
+            assert_eq!(hit.normal().unwrap(), direction * -1.0);
+        }
+    }
+
+    #[test]
+    fn materials_test() {
+        let mut model = ObjModel::parse(CUBE).unwrap();
+        let library = parse_mtl("
+            newmtl top
+            Kd 1 0 0
+            Ks 0.5 0.5 0.5
+            Ns 50
+            newmtl glass
+            Kd 0.9
+            d 0.25
+            Ni 1.5
+        ").unwrap();
+        assert_eq!(library.len(), 2);
+        assert_eq!(library[1].1.albedo, Color::new(0.9, 0.9, 0.9));
+        assert_eq!(library[1].1.transparency, 0.75);
+        assert_eq!(library[1].1.ior, 1.5);
+
+        model.set_materials(&library);
+        let top = model.mesh.material(8);
+        assert_eq!(top.brdf, Brdf::BlinnPhong);
+        assert_eq!(top.albedo, Color::new(1.0, 0.0, 0.0));
+        assert_eq!(top.shininess, 50.0);
+        // not in the library
+        assert_eq!(model.mesh.material(10), Material::default());
+
+        assert_eq!(parse_mtl("Kd 1 1 1").unwrap_err(), "line 1: Kd before the first newmtl");
+        assert_eq!(parse_mtl("newmtl a\nPr 0.2\nPm x").unwrap_err(), "line 3: invalid number in Pm: x");
+    }
+
+    #[test]
+    fn triangulate_test() {
+        // a hexagon without normals and texture coordinates, and a triangle that has some
+        let model = ObjModel::parse("
+            v 1 0 0
+            v 0.5 1 0
+            v -0.5 1 0
+            v -1 0 0
+            v -0.5 -1 0
+            v 0.5 -1 0
+            vt 0 0
+            f 1 2 3 4 5 6
+            f 1/1 2/1 3/1
+        ").unwrap();
+        let mesh = &model.mesh;
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3], [0, 3, 4], [0, 4, 5], [6, 7, 8]]);
+        let area: f64 = (0..4).map(|i| mesh.triangle(i).area()).sum();
+        assert!((area - 3.0).abs() < 1e-12);
+        // only some corners have texture coordinates, so the mesh has none
+        assert!(mesh.uvs.is_empty());
+        assert!(mesh.normals.is_empty());
+        assert!(mesh.material_indices.is_empty());
+    }
+
+    #[test]
+    fn write_test() {
+        let mut model = ObjModel::parse(CUBE).unwrap();
+        model.mesh.materials[0] = Material::cook_torrance(Color::new(1.0, 0.78, 0.34), 0.35, 1.0);
+        model.mesh.materials[1] = Material::glass(Color::new(0.9, 1.0, 0.9), 1.33);
+        model.mesh.materials[2] = Material::blinn_phong(Color::new(0.1, 0.6, 0.1), Color::new(0.5, 0.5, 0.5), 60.0);
+
+        let mut obj = Vec::new();
+        write_obj(&model.mesh, Some("cube.mtl"), &mut obj).unwrap();
+        let mut mtl = Vec::new();
+        write_mtl(&model.mesh.materials, &mut mtl).unwrap();
+
+        let mut read = ObjModel::parse(&String::from_utf8(obj).unwrap()).unwrap();
+        read.set_materials(&parse_mtl(&String::from_utf8(mtl).unwrap()).unwrap());
+        assert_eq!(read.material_libraries, vec!["cube.mtl"]);
+        assert_eq!(read.mesh.positions, model.mesh.positions);
+        assert_eq!(read.mesh.normals, model.mesh.normals);
+        assert_eq!(read.mesh.uvs, model.mesh.uvs);
+        assert_eq!(read.mesh.indices, model.mesh.indices);
+        for index in 0..model.mesh.triangle_count() {
+            assert_eq!(read.mesh.material(index), model.mesh.material(index));
+        }
+    }
+
+    #[test]
+    fn errors_test() {
+        let error = |text: &str| ObjModel::parse(text).unwrap_err();
+        assert_eq!(error("v 1 2 3\nv 1 2"), "line 2: v needs 3 numbers, got 2");
+        assert_eq!(error("v 1 2 a"), "line 1: invalid number in v: a");
+        assert_eq!(error("v 0 0 0\nv 1 0 0\nf 1 2"), "line 3: face needs at least 3 vertices, got 2");
+        assert_eq!(error("v 0 0 0\n\nf 1 1 4"), "line 3: vertex index 4 out of range, there are 1 vertices");
+        assert_eq!(error("v 0 0 0\nf 1 1 0"), "line 2: vertex index 0 out of range, there are 1 vertices");
+        assert_eq!(error("v 0 0 0\nf 1 1 -2"), "line 2: vertex index -2 out of range, there are 1 vertices");
+        assert_eq!(error("v 0 0 0\nf 1/1 1 1"), "line 2: texture coordinate index 1 out of range, there are 0 texture coordinates");
+        assert_eq!(error("v 0 0 0\nf 1/x 1 1"), "line 2: invalid texture coordinate index in face vertex: 1/x");
+        assert_eq!(error("v 0 0 0\nf 1/1/1/1 1 1"), "line 2: invalid face vertex: 1/1/1/1");
+        assert_eq!(error("usemtl"), "line 1: usemtl needs a material name");
+
+        let missing = ObjModel::load("/nonexistent/model.obj").unwrap_err();
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+    }
+}
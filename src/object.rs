@@ -1,24 +1,456 @@
-use crate::Quaternion;
+use std::any::Any;
+
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::color::Color;
+use crate::cone::Cone;
+use crate::cylinder::Cylinder;
+use crate::disk::Disk;
+use crate::line::Line;
+use crate::mat4::Mat4;
+use crate::material::Material;
+use crate::mesh::Mesh;
+use crate::quaternion::Quaternion;
+use crate::raycasthit::HitRecord;
+use crate::shape::Shape;
+use crate::sphere::Sphere;
+use crate::torus::Torus;
 use crate::surface::Surface;
+use crate::transform::Transform;
+use crate::triangle::SmoothTriangle;
+use crate::vector::Vector;
 
+// collection of shapes that are rendered and transformed together.
+// The shapes stay in object space, the transform places them in the world. Rays are moved into
+// object space to be intersected, so moving the object never touches (or slowly distorts) the shapes
 pub struct Object {
-    pub surfaces: Vec<Surface>,
+    pub shapes: Vec<Box<dyn Shape>>,
+    // material of the shape with the same index, shapes without one use the default material
+    pub materials: Vec<Material>,
+    transform: Transform,
+    // model matrix of transform, its inverse (None if the transform flattens the object)
+    // and the inverse transpose that moves normals to world space
+    matrix: Mat4,
+    inverse: Option<Mat4>,
+    normal_matrix: Mat4,
 }
 
 impl Object {
     pub fn new(surfaces: Vec<Surface>) -> Object {
-        Object { surfaces }
+        let mut object = Object::from_shapes(Vec::new());
+        for surface in surfaces {
+            object.add_shape(surface);
+        }
+        object
+    }
+
+    pub fn from_shapes(shapes: Vec<Box<dyn Shape>>) -> Object {
+        let materials = vec![Material::default(); shapes.len()];
+        Object {
+            shapes,
+            materials,
+            transform: Transform::identity(),
+            matrix: Mat4::identity(),
+            inverse: Some(Mat4::identity()),
+            normal_matrix: Mat4::identity(),
+        }
+    }
+
+    pub fn add_shape<S: Shape + 'static>(&mut self, shape: S) {
+        self.add_shape_with_material(shape, Material::default());
+    }
+
+    pub fn add_shape_with_material<S: Shape + 'static>(&mut self, shape: S, material: Material) {
+        self.materials.resize(self.shapes.len(), Material::default());
+        self.shapes.push(Box::new(shape));
+        self.materials.push(material);
+    }
+
+    pub fn material(&self, shape_index: usize) -> Material {
+        self.materials.get(shape_index).copied().unwrap_or_default()
+    }
+
+    // changes the material of one shape, so for example each face of a cube can look different
+    pub fn set_shape_material(&mut self, shape_index: usize, material: Material) {
+        self.materials.resize(self.shapes.len(), Material::default());
+        self.materials[shape_index] = material;
+    }
+
+    // gives every shape of the object the same material
+    pub fn set_material(&mut self, material: Material) {
+        self.materials = vec![material; self.shapes.len()];
+    }
+
+    // axis aligned cube centered at (0, 0, 0), every face spans from -half_size to half_size.
+    // Face normals point out of the cube
+    pub fn cube(half_size: f64) -> Object {
+        let bounds = (-half_size, half_size);
+        let front = Surface::new_vw(
+            Vector::new(0.0, 0.0, half_size),
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            bounds,
+            bounds,
+            Vector::new(0.0, 0.0, 1.0));
+        let back = Surface::new_vw(
+            Vector::new(0.0, 0.0, -half_size),
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            bounds,
+            bounds,
+            Vector::new(0.0, 0.0, -1.0));
+        let left = Surface::new_vw(
+            Vector::new(-half_size, 0.0, 0.0),
+            Vector::new(0.0, 0.0, 1.0),
+            Vector::new(0.0, 1.0, 0.0),
+            bounds,
+            bounds,
+            Vector::new(-1.0, 0.0, 0.0));
+        let right = Surface::new_vw(
+            Vector::new(half_size, 0.0, 0.0),
+            Vector::new(0.0, 0.0, 1.0),
+            Vector::new(0.0, 1.0, 0.0),
+            bounds,
+            bounds,
+            Vector::new(1.0, 0.0, 0.0));
+        let top = Surface::new_vw(
+            Vector::new(0.0, half_size, 0.0),
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 0.0, 1.0),
+            bounds,
+            bounds,
+            Vector::new(0.0, 1.0, 0.0));
+        let bottom = Surface::new_vw(
+            Vector::new(0.0, -half_size, 0.0),
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 0.0, 1.0),
+            bounds,
+            bounds,
+            Vector::new(0.0, -1.0, 0.0));
+        Object::new(vec![front, back, left, right, top, bottom])
+    }
+
+    // smaller cube surrounded by one of each primitive, sized to fit a 60x60 viewport.
+    // When ray traced the cube is plastic, the sphere a mirror, the cylinder glass and the torus gold
+    pub fn showcase() -> Object {
+        let mut object = Object::cube(8.0);
+        object.set_material(Material::cook_torrance(Color::new(0.8, 0.1, 0.1), 0.4, 0.0));
+        let mut torus_axis = Vector::new(0.0, 0.5, 1.0);
+        torus_axis.normalize();
+        object.add_shape_with_material(Sphere::new(Vector::new(-21.0, 0.0, 0.0), 6.0), Material::mirror(Color::new(0.9, 0.9, 0.9), 0.8));
+        object.add_shape_with_material(Cylinder::new(Vector::new(21.0, -5.0, 0.0), Vector::new(0.0, 1.0, 0.0), 4.0, 10.0, true), Material::glass(Color::white(), 1.5));
+        object.add_shape_with_material(Cone::new(Vector::new(0.0, 25.0, 0.0), Vector::new(0.0, -1.0, 0.0), 5.0, 9.0, true), Material::blinn_phong(Color::new(0.1, 0.6, 0.1), Color::new(0.5, 0.5, 0.5), 60.0));
+        object.add_shape_with_material(Disk::new(Vector::new(0.0, -21.0, 0.0), Vector::new(0.0, 0.0, 1.0), 6.0), Material::new(Color::new(0.1, 0.2, 0.7)));
+        object.add_shape_with_material(Torus::new(Vector::new(-18.0, 18.0, 0.0), torus_axis, 5.0, 2.0), Material::cook_torrance(Color::new(1.0, 0.78, 0.34), 0.35, 1.0));
+        object
+    }
+
+    // box containing every shape of the object in world space, empty if the object has no shapes
+    pub fn bounds(&self) -> Aabb {
+        self.shapes.iter().fold(Aabb::empty(), |bounds, shape| bounds.union(&shape.bounds())).transform(&self.matrix)
+    }
+
+    // acceleration structure over the shapes in object space. It has to be rebuilt after shapes are changed,
+    // added or removed, but not when the object is moved
+    pub fn bvh(&self) -> Bvh {
+        Bvh::new(&self.shapes)
+    }
+
+    pub fn transform(&self) -> Transform {
+        self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+        self.matrix = transform.matrix();
+        self.inverse = transform.inverse_matrix();
+        self.normal_matrix = match self.inverse {
+            Some(mut m) => {
+                m.transpose();
+                m
+            }
+            None => Mat4::identity(),
+        };
+    }
+
+    // back to the pose the shapes were created in
+    pub fn reset_transform(&mut self) {
+        self.set_transform(Transform::identity());
+    }
+
+    // model matrix, from object space to world space
+    pub fn matrix(&self) -> Mat4 {
+        self.matrix
     }
 
+    // rotates the object around (0, 0, 0)
     pub fn rotate(&mut self, q: &Quaternion) {
-        for surface in &mut self.surfaces {
-            surface.rotate(q);
+        let mut transform = self.transform;
+        transform.rotate(q);
+        self.set_transform(transform);
+    }
+
+    // moves the object in world space
+    pub fn translate(&mut self, offset: &Vector) {
+        let mut transform = self.transform;
+        transform.translation += *offset;
+        self.set_transform(transform);
+    }
+
+    // scales the object along its own axes, by a different amount along each. The object stays where it is,
+    // scaling happens before the rotation so a rotated object is stretched along its rotated axes
+    pub fn scale(&mut self, s: &Vector) {
+        let mut transform = self.transform;
+        transform.scale = Vector::new(transform.scale.x * s.x, transform.scale.y * s.y, transform.scale.z * s.z);
+        self.set_transform(transform);
+    }
+
+    // the line in object space. The direction isn't normalized, so t is the same in both spaces.
+    // None if the object is flattened by a scale of 0 and can't be hit
+    pub fn to_object_space(&self, line: &Line) -> Option<Line> {
+        let inverse = self.inverse.as_ref()?;
+        Some(Line::new(inverse.transform_point(&line.point), inverse.transform_direction(&line.direction)))
+    }
+
+    // normal in object space to world space, normalized.
+    // Normals use the inverse transpose of the model matrix so they stay perpendicular to scaled shapes
+    pub fn normal_to_world(&self, normal: &Vector) -> Vector {
+        let mut normal = self.normal_matrix.transform_direction(normal);
+        normal.normalize();
+        normal
+    }
+
+    // hit record in world space for the hit at t on shape index. line is in world space, local_line is the same line in object space
+    pub fn world_hit(&self, line: &Line, local_line: &Line, index: usize, t: f64) -> HitRecord {
+        let shape = self.shapes[index].as_ref();
+        let local_point = local_line.point_on_line(&t);
+        let normal = self.normal_to_world(&shape.normal_at(&local_point));
+        let mut hit = HitRecord::new(&line.direction, line.point_on_line(&t), t, normal, shape.uv_at(&local_point));
+        hit.shape_index = index;
+        hit
+    }
+
+    // true if any shape is hit by the world space line before max_t, for shadow rays. bvh has to be built from the shapes
+    pub fn any_hit(&self, bvh: &Bvh, line: &Line, max_t: f64) -> bool {
+        match self.to_object_space(line) {
+            Some(local) => bvh.any_hit(&self.shapes, &local, max_t),
+            None => false,
+        }
+    }
+
+    // the shapes as triangles in world space, each with the material of its shape, for exporting.
+    // Curves are split into segments pieces per full turn. Fails for shapes that can't be made of triangles.
+    // If any shape is a SmoothTriangle the mesh gets its vertex normals, the other triangles their face normal
+    pub fn to_mesh(&self, segments: usize) -> Result<Mesh, String> {
+        let mut mesh = Mesh::new();
+        mesh.materials = (0..self.shapes.len()).map(|index| self.material(index)).collect();
+        let mut smooth = false;
+        for (index, shape) in self.shapes.iter().enumerate() {
+            let any: &dyn Any = shape.as_ref();
+            let triangles = match any.downcast_ref::<SmoothTriangle>() {
+                Some(s) => {
+                    smooth = true;
+                    let mut s = *s;
+                    s.transform(&self.matrix);
+                    vec![(s.triangle, s.normals)]
+                }
+                None => {
+                    let triangles = shape.triangles(segments).ok_or(format!("shape {} can't be made of triangles", index))?;
+                    triangles.into_iter().map(|mut triangle| {
+                        triangle.transform(&self.matrix);
+                        (triangle, [triangle.normal(); 3])
+                    }).collect()
+                }
+            };
+            for (triangle, normals) in triangles {
+                let first = mesh.positions.len();
+                mesh.positions.extend([triangle.a, triangle.b, triangle.c]);
+                mesh.normals.extend(normals);
+                mesh.indices.push([first, first + 1, first + 2]);
+                mesh.material_indices.push(index);
+            }
+        }
+        if !smooth {
+            mesh.normals.clear();
+        }
+        Ok(mesh)
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::as_radians;
+    use crate::triangle::Triangle;
+
+    fn rays() -> Vec<Line> {
+        let mut rays = Vec::new();
+        for i in -12..12 {
+            for j in -12..12 {
+                let origin = Vector::new(j as f64 * 2.5, i as f64 * 2.5, 60.0);
+                let mut direction = Vector::new(0.01 * i as f64, -0.005 * j as f64, -1.0);
+                direction.normalize();
+                rays.push(Line::new(origin, direction));
+            }
         }
+        rays
     }
 
-    pub fn scale(&mut self, s: &f64) {
-        for surface in &mut self.surfaces {
-            surface.scale(s);
+    #[test]
+    fn transform_matches_moved_shapes_test() {
+        let mut q = Quaternion::identity();
+        q.rotate(as_radians(35.0), Vector::new(1.0, 0.0, 0.0));
+        q.rotate(as_radians(-20.0), Vector::new(0.0, 1.0, 0.0));
+
+        // rotating the shapes themselves and rotating the object have to give the same picture
+        let mut moved = Object::showcase();
+        for shape in &mut moved.shapes {
+            shape.rotate(&q);
         }
+        let mut transformed = Object::showcase();
+        transformed.rotate(&q);
+        let (moved_bvh, transformed_bvh) = (moved.bvh(), transformed.bvh());
+
+        let mut hits = 0;
+        for line in rays() {
+            let a = line.intersection_bvh(&moved, &moved_bvh, &false);
+            let b = line.intersection_bvh(&transformed, &transformed_bvh, &false);
+            assert_eq!(a.is_some(), b.is_some(), "{:?}", line);
+            if let (Some(a), Some(b)) = (a.hit(), b.hit()) {
+                assert_eq!(a.shape_index, b.shape_index);
+                assert!((a.t - b.t).abs() < 1e-9);
+                assert!((a.point - b.point).length() < 1e-9);
+                assert!((a.normal - b.normal).length() < 1e-9);
+                hits += 1;
+            }
+            let brute = line.intersection_object(&transformed, &line.point, &false);
+            assert_eq!(brute.t(), b.t());
+        }
+        assert!(hits > 100);
+    }
+
+    #[test]
+    fn non_uniform_scale_test() {
+        // unit sphere stretched along x into an ellipsoid x^2 / 4 + y^2 + z^2 = 1
+        let mut object = Object::from_shapes(Vec::new());
+        object.add_shape(Sphere::new(Vector::new(0.0, 0.0, 0.0), 1.0));
+        object.set_transform(Transform::new(Vector::new(0.0, 0.0, 0.0), Quaternion::identity(), Vector::new(2.0, 1.0, 1.0)));
+        let bvh = object.bvh();
+
+        let line = Line::new(Vector::new(10.0, 0.5, 0.0), Vector::new(-1.0, 0.0, 0.0));
+        let hit = *line.intersection_bvh(&object, &bvh, &false).hit().unwrap();
+        assert!((hit.point - Vector::new(3.0f64.sqrt(), 0.5, 0.0)).length() < 1e-12);
+        assert!((hit.t - (10.0 - 3.0f64.sqrt())).abs() < 1e-12);
+        // the normal is the gradient (x / 2, 2y, 2z), not the scaled normal of the sphere
+        let mut expected = Vector::new(3.0f64.sqrt() / 2.0, 1.0, 0.0);
+        expected.normalize();
+        assert!((hit.normal - expected).length() < 1e-12);
+
+        assert!((object.bounds().max - Vector::new(2.0, 1.0, 1.0)).length() < 1e-12);
+        assert!(object.any_hit(&bvh, &line, 9.0));
+        assert!(!object.any_hit(&bvh, &line, 8.0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn translate_and_scale_test() {
+        let mut object = Object::cube(1.0);
+        let mut q = Quaternion::identity();
+        q.rotate(as_radians(90.0), Vector::new(0.0, 0.0, 1.0));
+        object.rotate(&q);
+        object.scale(&Vector::new(3.0, 1.0, 1.0));
+        object.translate(&Vector::new(0.0, 0.0, -10.0));
+        let bvh = object.bvh();
+
+        // stretched along its own x axis, which points along y after the rotation
+        let bounds = object.bounds();
+        assert!((bounds.min - Vector::new(-1.0, -3.0, -11.0)).length() < 1e-12);
+        assert!((bounds.max - Vector::new(1.0, 3.0, -9.0)).length() < 1e-12);
+
+        let line = Line::new(Vector::new(0.0, 10.0, -10.0), Vector::new(0.0, -1.0, 0.0));
+        let hit = *line.intersection_bvh(&object, &bvh, &false).hit().unwrap();
+        assert!((hit.t - 7.0).abs() < 1e-12);
+        assert!((hit.normal - Vector::new(0.0, 1.0, 0.0)).length() < 1e-12);
+        assert_eq!(object.transform().translation, Vector::new(0.0, 0.0, -10.0));
+    }
+
+    #[test]
+    fn reset_transform_test() {
+        let mut object = Object::cube(5.0);
+        let bvh = object.bvh();
+        let before: Vec<Aabb> = object.shapes.iter().map(|shape| shape.bounds()).collect();
+        let line = Line::new(Vector::new(1.0, 2.0, 30.0), Vector::new(0.0, 0.0, -1.0));
+        let start = line.intersection_bvh(&object, &bvh, &false).t();
+
+        let mut q = Quaternion::identity();
+        q.rotate(as_radians(7.0), Vector::new(1.0, 1.0, 0.0));
+        for _ in 0..1000 {
+            object.rotate(&q);
+        }
+        object.scale(&Vector::new(2.0, 2.0, 2.0));
+        assert_ne!(line.intersection_bvh(&object, &bvh, &false).t(), start);
+
+        // the shapes never moved, so the original pose comes back exactly
+        assert_eq!(object.shapes.iter().map(|shape| shape.bounds()).collect::<Vec<Aabb>>(), before);
+        object.reset_transform();
+        assert_eq!(line.intersection_bvh(&object, &bvh, &false).t(), start);
+    }
+
+    #[test]
+    fn to_mesh_test() {
+        let mut object = Object::cube(1.0);
+        object.set_shape_material(2, Material::new(Color::new(1.0, 0.0, 0.0)));
+        object.translate(&Vector::new(0.0, 0.0, 10.0));
+        object.scale(&Vector::new(-1.0, 2.0, 1.0));
+        let mesh = object.to_mesh(16).unwrap();
+        assert!(mesh.validate().is_ok());
+        assert_eq!(mesh.triangle_count(), 12);
+        assert_eq!(mesh.bounds(), Aabb::new(Vector::new(-1.0, -2.0, 9.0), Vector::new(1.0, 2.0, 11.0)));
+        assert_eq!(mesh.material(4).albedo, Color::new(1.0, 0.0, 0.0));
+
+        // the mirroring scale doesn't turn the triangles inside out
+        let center = Vector::new(0.0, 0.0, 10.0);
+        for index in 0..mesh.triangle_count() {
+            let triangle = mesh.triangle(index);
+            let centroid = triangle.point_from_barycentric((1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0));
+            assert!(triangle.normal().dot(&(centroid - center)) > 0.0);
+        }
+
+        object.add_shape(Surface::new_normal(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0)));
+        assert_eq!(object.to_mesh(16).unwrap_err(), "shape 6 can't be made of triangles");
+    }
+
+    #[test]
+    fn to_mesh_normals_test() {
+        // flat shapes don't give the mesh normals
+        let mesh = Object::cube(1.0).to_mesh(16).unwrap();
+        assert!(mesh.normals.is_empty());
+
+        // the vertex normals of smooth triangles survive the round trip, moved with the object
+        let mut mesh = Mesh::new();
+        mesh.positions = vec![Vector::new(0.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0)];
+        mesh.normals = vec![Vector::new(0.0, 0.0, 1.0), Vector::new(1.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0)];
+        mesh.indices = vec![[0, 1, 2]];
+        let round_trip = mesh.to_object().to_mesh(16).unwrap();
+        assert!(round_trip.validate().is_ok());
+        assert_eq!(round_trip.positions, mesh.positions);
+        assert_eq!(round_trip.normals, mesh.normals);
+
+        let mut q = Quaternion::identity();
+        q.rotate(as_radians(90.0), Vector::new(0.0, 0.0, 1.0));
+        let mut object = mesh.to_object();
+        object.translate(&Vector::new(0.0, 0.0, 5.0));
+        object.rotate(&q);
+        let moved = object.to_mesh(16).unwrap();
+        for (normal, expected) in moved.normals.iter().zip(&mesh.normals) {
+            let mut expected = *expected;
+            expected.rotate_by_quaternion(&q);
+            assert!((*normal - expected).length() < 1e-9);
+        }
+
+        // mixed with flat shapes, those get their face normal at every vertex
+        object.add_shape(Triangle::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0), Vector::new(0.0, 0.0, 1.0)));
+        let mixed = object.to_mesh(16).unwrap();
+        assert!(mixed.validate().is_ok());
+        assert_eq!(mixed.normals.len(), 6);
+        assert!(mixed.normals[3..].iter().all(|normal| *normal == mixed.triangle(1).normal()));
+    }
+}
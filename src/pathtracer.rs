@@ -0,0 +1,288 @@
+use std::f64::consts::PI;
+
+use crate::camera::{Camera, RenderStats};
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::light::LightSample;
+use crate::line::Line;
+use crate::material::Material;
+use crate::random::{cosine_hemisphere, Rng};
+use crate::raycasthit::HitRecord;
+use crate::raytracer::{fresnel, BIAS};
+use crate::scene::{Scene, SceneBvh};
+use crate::vector::Vector;
+
+// unidirectional Monte Carlo path tracer.
+// Every pixel follows one random path per frame, bouncing off the shapes until it leaves the scene,
+// gets too long or is ended by Russian roulette. The light found along the path is averaged over many frames
+// with an Accumulator. Diffuse bounces sample the BRDF with a cosine weighted hemisphere and also sample every
+// light directly (next event estimation), area lights found both ways are weighted with multiple importance sampling.
+// see: https://www.pbr-book.org/3ed-2018/Light_Transport_I_Surface_Reflection/Path_Tracing
+pub struct PathTracer {
+    // the longest path, in bounces
+    pub max_depth: u32,
+    // bounces before Russian roulette can end a path
+    pub min_depth: u32,
+    // changes the random numbers of every pixel, renders with the same seed are identical
+    pub seed: u64,
+}
+
+impl PathTracer {
+    pub fn new() -> PathTracer {
+        PathTracer {
+            max_depth: 8,
+            min_depth: 3,
+            seed: 0,
+        }
+    }
+
+    // one path for each of the camera.samples rays per pixel. frame picks the random numbers, so adding
+    // frames 0, 1, 2, ... to an Accumulator converges to the final image. With a jittered sample pattern
+    // every frame also uses other spots in the pixels, so the edges get anti-aliased along the way.
+    // bvh has to be built from the scene, the same one can be used for every frame
+    pub fn render(&self, camera: &mut Camera, scene: &Scene, bvh: &SceneBvh, frame: u32) -> Framebuffer {
+        let colors = camera.render_samples(self.seed.wrapping_add(frame as u64), |line, _, rng, stats| {
+            let mut direction = line.direction;
+            direction.normalize();
+            self.radiance(scene, bvh, &Line::new(line.point, direction), rng, stats)
+        });
+        Framebuffer::from_render(camera.render_width as usize, camera.render_height as usize, colors)
+    }
+
+    // estimate of the light arriving along the line, which must have a normalized direction
+    pub fn radiance(&self, scene: &Scene, bvh: &SceneBvh, line: &Line, rng: &mut Rng, stats: &mut RenderStats) -> Color {
+        let mut color = Color::black();
+        // how much of the light found at the current bounce makes it back to the camera
+        let mut throughput = Color::white();
+        let mut line = *line;
+        // pdf of the direction of the last diffuse bounce, None after mirrors, glass and for the camera ray.
+        // Those can't be sampled by next event estimation, so lights they hit count fully
+        let mut bsdf_pdf: Option<f64> = None;
+
+        for depth in 0..=self.max_depth {
+            let hit = scene.closest_hit(bvh, &line);
+            let hit_t = hit.map_or(f64::INFINITY, |hit| hit.t);
+
+            // area lights aren't shapes, rays pass through them but pick up their light on the way
+            for light in &scene.lights {
+                if let Some((t, emitted)) = light.intersect(&line) {
+                    if t < hit_t {
+                        let weight = match bsdf_pdf {
+                            Some(pdf) => {
+                                let sample = LightSample { direction: line.direction, distance: t, radiance: emitted };
+                                power_heuristic(pdf, light.pdf(&sample))
+                            }
+                            None => 1.0,
+                        };
+                        color += throughput * emitted * weight;
+                    }
+                }
+            }
+
+            let hit = match hit {
+                Some(hit) => hit,
+                None => {
+                    color += throughput * scene.background;
+                    break;
+                }
+            };
+            if depth == 0 {
+                stats.hits += 1;
+            }
+
+            let material = scene.material(&hit);
+            color += throughput * material.emission;
+            if depth == self.max_depth {
+                break;
+            }
+
+            // pick what happens at the hit in proportion to the material, the weights of the other choices
+            // are made up for by dividing by the probability of the one that was picked
+            let total = material.reflectivity + material.transparency + material.diffuse();
+            if total <= 0.0 {
+                break;
+            }
+            let choice = rng.next_f64() * total;
+            let (origin, direction) = if choice < material.reflectivity {
+                throughput = throughput * material.albedo * total;
+                bsdf_pdf = None;
+                (hit.point + hit.normal * BIAS, line.direction.reflect(&hit.normal))
+            } else if choice < material.reflectivity + material.transparency {
+                throughput = throughput * material.albedo * total;
+                bsdf_pdf = None;
+                self.dielectric(&line, &hit, &material, rng)
+            } else {
+                color += throughput * self.next_event(scene, bvh, &line, &hit, &material, rng, stats) * total;
+
+                let direction = cosine_hemisphere(&hit.normal, rng.next_f64(), rng.next_f64());
+                let cos = direction.dot(&hit.normal);
+                let pdf = cos / PI;
+                if pdf <= 0.0 {
+                    break;
+                }
+                let brdf = material.brdf(&hit.normal, &direction, &(line.direction * -1.0));
+                throughput = throughput * brdf * (cos / pdf * total);
+                bsdf_pdf = Some(pdf);
+                (hit.point + hit.normal * BIAS, direction)
+            };
+
+            // paths that can't carry much light anymore are ended at random, the ones that survive carry more
+            if depth + 1 >= self.min_depth {
+                let survive = throughput.r.max(throughput.g).max(throughput.b).min(0.95);
+                if rng.next_f64() >= survive {
+                    break;
+                }
+                throughput *= 1.0 / survive;
+            }
+
+            let mut direction = direction;
+            direction.normalize();
+            line = Line::new(origin, direction);
+            stats.rays += 1;
+        }
+        color
+    }
+
+    // light reaching the hit straight from the lights and reflected towards the line start.
+    // Area lights are sampled at a random spot and weighted against finding them by sampling the BRDF
+    #[allow(clippy::too_many_arguments)]
+    fn next_event(&self, scene: &Scene, bvh: &SceneBvh, line: &Line, hit: &HitRecord, material: &Material, rng: &mut Rng, stats: &mut RenderStats) -> Color {
+        let mut color = Color::black();
+        let origin = hit.point + hit.normal * BIAS;
+        let to_viewer = line.direction * -1.0;
+        for light in &scene.lights {
+            let sample = match light.sample(&origin, rng.next_f64(), rng.next_f64()) {
+                Some(sample) => sample,
+                None => continue,
+            };
+            let cos = hit.normal.dot(&sample.direction);
+            if cos <= 0.0 {
+                continue;
+            }
+            stats.rays += 1;
+            if scene.any_hit(bvh, &Line::new(origin, sample.direction), sample.distance) {
+                continue;
+            }
+            let weight = if light.is_delta() { 1.0 } else { power_heuristic(light.pdf(&sample), cos / PI) };
+            color += material.brdf(&hit.normal, &sample.direction, &to_viewer) * sample.radiance * (cos * weight);
+        }
+        color
+    }
+
+    // reflects or refracts at a transparent material, picking one at random by the Fresnel reflectance
+    fn dielectric(&self, line: &Line, hit: &HitRecord, material: &Material, rng: &mut Rng) -> (Vector, Vector) {
+        let (eta_i, eta_t) = if hit.front_face { (1.0, material.ior) } else { (material.ior, 1.0) };
+        let kr = fresnel(-line.direction.dot(&hit.normal), eta_i, eta_t);
+        if rng.next_f64() >= kr {
+            if let Some(direction) = line.direction.refract(&hit.normal, eta_i / eta_t) {
+                return (hit.point - hit.normal * BIAS, direction);
+            }
+        }
+        (hit.point + hit.normal * BIAS, line.direction.reflect(&hit.normal))
+    }
+}
+
+impl Default for PathTracer {
+    fn default() -> PathTracer {
+        PathTracer::new()
+    }
+}
+
+// weight of a sample taken with probability density pdf when the same light could also have been found
+// with density other_pdf, see: https://www.pbr-book.org/3ed-2018/Monte_Carlo_Integration/Importance_Sampling
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b == 0.0 {
+        return 0.0;
+    }
+    a / (a + b)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::framebuffer::Accumulator;
+    use crate::light::Light;
+    use crate::object::Object;
+    use crate::raytracer::RayTracer;
+    use crate::sphere::Sphere;
+    use crate::surface::Surface;
+
+    fn average(tracer: &PathTracer, scene: &Scene, line: &Line, samples: u32) -> Color {
+        let bvh = scene.bvh();
+        let mut rng = Rng::new(1);
+        let mut sum = Color::black();
+        for _ in 0..samples {
+            sum += tracer.radiance(scene, &bvh, line, &mut rng, &mut RenderStats::default());
+        }
+        sum * (1.0 / samples as f64)
+    }
+
+    #[test]
+    fn furnace_test() {
+        // a diffuse sphere under an evenly white sky reflects its albedo of the sky, every single sample
+        let mut object = Object::from_shapes(Vec::new());
+        object.add_shape_with_material(Sphere::new(Vector::new(0.0, 0.0, 0.0), 1.0), Material::new(Color::new(0.5, 0.5, 0.5)));
+        let mut scene = Scene::from_object("sphere", object);
+        scene.background = Color::white();
+        let tracer = PathTracer::new();
+        let bvh = scene.bvh();
+        let mut rng = Rng::new(3);
+        for offset in [0.0, 0.5, 0.9] {
+            let line = Line::new(Vector::new(offset, 0.0, 10.0), Vector::new(0.0, 0.0, -1.0));
+            let color = tracer.radiance(&scene, &bvh, &line, &mut rng, &mut RenderStats::default());
+            assert!((color.r - 0.5).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn converges_to_direct_light_test() {
+        // floor under an area light, there are no other bounces so the path tracer has to agree with
+        // the direct light of the Whitted tracer, no matter how the samples are split between light and BRDF sampling
+        let mut scene = Scene::from_object("floor", Object::new(vec![Surface::new_normal(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0))]));
+        for half_size in [0.5, 4.0, 20.0] {
+            let panel = Surface::new_vw(
+                Vector::new(0.0, 0.0, 5.0),
+                Vector::new(1.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+                (-half_size, half_size),
+                (-half_size, half_size),
+                Vector::new(0.0, 0.0, -1.0));
+            scene.lights = vec![Light::area(panel, Color::white(), 100.0, 32).unwrap()];
+            let mut whitted = RayTracer::new();
+            whitted.ambient = Color::black();
+
+            let mut direction = Vector::new(1.0, 0.0, -1.0);
+            direction.normalize();
+            let line = Line::new(Vector::new(-1.0, 0.0, 1.0), direction);
+            let expected = whitted.trace(&scene, &scene.bvh(), &line, 0, &mut RenderStats::default());
+            let color = average(&PathTracer::new(), &scene, &line, 4000);
+            assert!((color.r - expected.r).abs() < 0.03 * expected.r, "{} {:?} {:?}", half_size, color, expected);
+        }
+    }
+
+    #[test]
+    fn progressive_render_test() {
+        let mut scene = Scene::from_object("showcase", Object::showcase());
+        scene.add_light(Light::point(Vector::new(0.0, 30.0, 60.0), Color::white(), 20000.0));
+        scene.background = Color::new(0.2, 0.2, 0.2);
+        let mut camera = Camera::new_orthographic(Vector::new(0.0, 0.0, 50.0), Vector::new(0.0, 0.0, -1.0), 24, 24, Vector::new(0.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0), (60.0, 60.0));
+        let tracer = PathTracer::new();
+
+        // the same frame is the same image, on any number of threads
+        let bvh = scene.bvh();
+        let first = tracer.render(&mut camera, &scene, &bvh, 0);
+        camera.threads = 3;
+        assert_eq!(tracer.render(&mut camera, &scene, &bvh, 0).color, first.color);
+        // other frames use other random numbers
+        assert_ne!(tracer.render(&mut camera, &scene, &bvh, 1).color, first.color);
+
+        let mut accumulator = Accumulator::new(24, 24);
+        for frame in 0..4 {
+            accumulator.add(&tracer.render(&mut camera, &scene, &bvh, frame));
+        }
+        assert_eq!(accumulator.frames(), 4);
+        // corners only see the background
+        assert_eq!(accumulator.image().get(0, 0), scene.background);
+    }
+}
@@ -0,0 +1,455 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::color::Color;
+use crate::mesh::Mesh;
+use crate::vector::Vector;
+
+// PLY, the polygon format of 3D scanners, see: https://paulbourke.net/dataformats/ply/
+// A header lists the elements of the file with their properties, followed by the data as text or as
+// little- or big-endian binary. Vertices need x, y and z and can have a normal (nx, ny, nz), a color
+// (red, green, blue) and texture coordinates (u, v or s, t). Faces are lists of vertex indices called
+// vertex_indices or vertex_index, polygons are split into triangles. Other elements and properties are skipped
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Type {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl Type {
+    fn parse(name: &str) -> Option<Type> {
+        match name {
+            "char" | "int8" => Some(Type::Int8),
+            "uchar" | "uint8" => Some(Type::UInt8),
+            "short" | "int16" => Some(Type::Int16),
+            "ushort" | "uint16" => Some(Type::UInt16),
+            "int" | "int32" => Some(Type::Int32),
+            "uint" | "uint32" => Some(Type::UInt32),
+            "float" | "float32" => Some(Type::Float32),
+            "double" | "float64" => Some(Type::Float64),
+            _ => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Type::Int8 | Type::UInt8 => 1,
+            Type::Int16 | Type::UInt16 => 2,
+            Type::Int32 | Type::UInt32 | Type::Float32 => 4,
+            Type::Float64 => 8,
+        }
+    }
+
+    // colors stored as integers go from 0 to the largest value of the type, as floats from 0 to 1
+    fn color_scale(&self) -> f64 {
+        match self {
+            Type::Int8 => i8::MAX as f64,
+            Type::UInt8 => u8::MAX as f64,
+            Type::Int16 => i16::MAX as f64,
+            Type::UInt16 => u16::MAX as f64,
+            Type::Int32 => i32::MAX as f64,
+            Type::UInt32 => u32::MAX as f64,
+            Type::Float32 | Type::Float64 => 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Property {
+    Scalar { name: String, kind: Type },
+    // number of items, then the items
+    List { name: String, count: Type, item: Type },
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Property::Scalar { name, .. } | Property::List { name, .. } => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn scalar(&self, name: &str) -> Option<usize> {
+        self.properties.iter().position(|p| matches!(p, Property::Scalar { name: n, .. } if n == name))
+    }
+
+    // indices of all three properties, or None if one is missing
+    fn scalars(&self, names: [&str; 3]) -> Option<[usize; 3]> {
+        Some([self.scalar(names[0])?, self.scalar(names[1])?, self.scalar(names[2])?])
+    }
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+    // lines, including end_header
+    line_count: usize,
+    // length in bytes, the data starts behind it
+    length: usize,
+}
+
+fn parse_header(bytes: &[u8]) -> Result<Header, String> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut position = 0;
+    let mut number = 0;
+    loop {
+        let end = bytes[position..].iter().position(|&b| b == b'\n').map(|i| position + i).ok_or("unexpected end of file in the header")?;
+        let line = std::str::from_utf8(&bytes[position..end]).map_err(|_| format!("line {}: invalid text", number + 1))?;
+        position = end + 1;
+        number += 1;
+        let error = |message: String| format!("line {}: {}", number, message);
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if number == 1 {
+            if tokens != ["ply"] {
+                return Err(String::from("not a PLY file"));
+            }
+            continue;
+        }
+        match tokens.as_slice() {
+            [] | ["comment", ..] | ["obj_info", ..] => {}
+            ["format", name, version] => {
+                if *version != "1.0" {
+                    return Err(error(format!("unsupported version: {}", version)));
+                }
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(error(format!("unknown format: {}", name))),
+                });
+            }
+            ["element", name, count] => {
+                let count = count.parse().map_err(|_| error(format!("invalid element count: {}", count)))?;
+                elements.push(Element { name: name.to_string(), count, properties: Vec::new() });
+            }
+            ["property", "list", count, item, name] => {
+                let element = elements.last_mut().ok_or_else(|| error(String::from("property before the first element")))?;
+                let parse_type = |name: &str| Type::parse(name).ok_or_else(|| error(format!("unknown type: {}", name)));
+                element.properties.push(Property::List { name: name.to_string(), count: parse_type(count)?, item: parse_type(item)? });
+            }
+            ["property", kind, name] => {
+                let element = elements.last_mut().ok_or_else(|| error(String::from("property before the first element")))?;
+                let kind = Type::parse(kind).ok_or_else(|| error(format!("unknown type: {}", kind)))?;
+                element.properties.push(Property::Scalar { name: name.to_string(), kind });
+            }
+            ["end_header"] => break,
+            _ => return Err(error(format!("unexpected {}", line.trim()))),
+        }
+    }
+    let format = format.ok_or("missing format in the header")?;
+    Ok(Header { format, elements, line_count: number, length: position })
+}
+
+// reads the values of the elements one after the other, from text or binary
+enum Data<'a> {
+    Ascii { lines: std::str::Lines<'a>, line: usize, tokens: Vec<&'a str>, next: usize },
+    Binary { bytes: &'a [u8], position: usize, big_endian: bool },
+}
+
+impl Data<'_> {
+    // where the last value was read, for errors
+    fn location(&self) -> String {
+        match self {
+            Data::Ascii { line, .. } => format!("line {}", line),
+            Data::Binary { position, .. } => format!("byte {}", position),
+        }
+    }
+
+    // every element is on its own line in ASCII files
+    fn start_element(&mut self) -> Result<(), String> {
+        if let Data::Ascii { lines, line, tokens, next } = self {
+            loop {
+                let text = lines.next().ok_or("unexpected end of file")?;
+                *line += 1;
+                if !text.trim().is_empty() {
+                    *tokens = text.split_whitespace().collect();
+                    *next = 0;
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn value(&mut self, kind: Type) -> Result<f64, String> {
+        match self {
+            Data::Ascii { tokens, next, .. } => {
+                let token = tokens.get(*next).ok_or("too few values")?;
+                *next += 1;
+                token.parse().map_err(|_| format!("invalid number: {}", token))
+            }
+            Data::Binary { bytes, position, big_endian } => {
+                let value = bytes.get(*position..*position + kind.size()).ok_or("unexpected end of file")?;
+                *position += kind.size();
+                // big-endian values are reversed, so all of them can be read as little-endian
+                let mut buffer = [0u8; 8];
+                buffer[..value.len()].copy_from_slice(value);
+                if *big_endian {
+                    buffer[..value.len()].reverse();
+                }
+                Ok(match kind {
+                    Type::Int8 => buffer[0] as i8 as f64,
+                    Type::UInt8 => buffer[0] as f64,
+                    Type::Int16 => i16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+                    Type::UInt16 => u16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+                    Type::Int32 => i32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+                    Type::UInt32 => u32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+                    Type::Float32 => f32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+                    Type::Float64 => f64::from_le_bytes(buffer),
+                })
+            }
+        }
+    }
+
+    // values of one element, a list for every property, with one value for scalar properties
+    fn element(&mut self, element: &Element) -> Result<Vec<Vec<f64>>, String> {
+        self.start_element()?;
+        let mut values = Vec::with_capacity(element.properties.len());
+        for property in &element.properties {
+            values.push(match property {
+                Property::Scalar { kind, .. } => vec![self.value(*kind)?],
+                Property::List { count, item, .. } => {
+                    let count = self.value(*count)?;
+                    if count < 0.0 || count.fract() != 0.0 {
+                        return Err(format!("invalid list length: {}", count));
+                    }
+                    (0..count as usize).map(|_| self.value(*item)).collect::<Result<_, _>>()?
+                }
+            });
+        }
+        Ok(values)
+    }
+}
+
+// reads an ASCII or binary PLY file. Vertices with the same position and attributes are welded
+// and faces without area are left out
+pub fn parse(bytes: &[u8]) -> Result<Mesh, String> {
+    let header = parse_header(bytes)?;
+    let body = &bytes[header.length..];
+    let mut data = match header.format {
+        Format::Ascii => {
+            let text = std::str::from_utf8(body).map_err(|_| String::from("ASCII PLY that isn't valid text"))?;
+            Data::Ascii { lines: text.lines(), line: header.line_count, tokens: Vec::new(), next: 0 }
+        }
+        Format::BinaryLittleEndian => Data::Binary { bytes, position: header.length, big_endian: false },
+        Format::BinaryBigEndian => Data::Binary { bytes, position: header.length, big_endian: true },
+    };
+
+    let vertex_count = header.elements.iter().find(|e| e.name == "vertex").map_or(0, |e| e.count);
+    let mut mesh = Mesh::new();
+    for element in &header.elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(&mut mesh, element, &mut data)?,
+            "face" => read_faces(&mut mesh, element, vertex_count, &mut data)?,
+            _ => {
+                for _ in 0..element.count {
+                    data.element(element).map_err(|e| format!("{}: {}", data.location(), e))?;
+                }
+            }
+        }
+    }
+    mesh.weld(0.0);
+    mesh.remove_degenerate();
+    Ok(mesh)
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Mesh> {
+    let path = path.as_ref();
+    let bytes = fs::read(path)?;
+    parse(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+}
+
+fn read_vertices(mesh: &mut Mesh, element: &Element, data: &mut Data) -> Result<(), String> {
+    let position = element.scalars(["x", "y", "z"]).ok_or("vertex element needs the properties x, y and z")?;
+    let normal = element.scalars(["nx", "ny", "nz"]);
+    let color = element.scalars(["red", "green", "blue"]).or_else(|| element.scalars(["diffuse_red", "diffuse_green", "diffuse_blue"]));
+    let uv = [["u", "v"], ["s", "t"], ["texture_u", "texture_v"]]
+        .iter()
+        .find_map(|[u, v]| Some([element.scalar(u)?, element.scalar(v)?]));
+    let color_scale = color.map(|[red, ..]| match element.properties[red] {
+        Property::Scalar { kind, .. } => kind.color_scale(),
+        Property::List { .. } => 1.0,
+    });
+
+    for index in 0..element.count {
+        let values = data.element(element).map_err(|e| format!("{}: vertex {}: {}", data.location(), index, e))?;
+        let vector = |[x, y, z]: [usize; 3]| Vector::new(values[x][0], values[y][0], values[z][0]);
+        mesh.positions.push(vector(position));
+        if let Some(normal) = normal {
+            mesh.normals.push(vector(normal));
+        }
+        if let (Some([r, g, b]), Some(scale)) = (color, color_scale) {
+            mesh.colors.push(Color::from_srgb(values[r][0] / scale, values[g][0] / scale, values[b][0] / scale));
+        }
+        if let Some([u, v]) = uv {
+            mesh.uvs.push((values[u][0], values[v][0]));
+        }
+    }
+    Ok(())
+}
+
+fn read_faces(mesh: &mut Mesh, element: &Element, vertex_count: usize, data: &mut Data) -> Result<(), String> {
+    let property = element
+        .properties
+        .iter()
+        .position(|p| matches!(p, Property::List { .. }) && matches!(p.name(), "vertex_indices" | "vertex_index"))
+        .ok_or("face element needs a vertex_indices list")?;
+
+    for index in 0..element.count {
+        let values = data.element(element).map_err(|e| format!("{}: face {}: {}", data.location(), index, e))?;
+        let mut corners = Vec::with_capacity(values[property].len());
+        for &value in &values[property] {
+            if value < 0.0 || value >= vertex_count as f64 {
+                return Err(format!("{}: face {} uses vertex {}, there are {} vertices", data.location(), index, value, vertex_count));
+            }
+            corners.push(value as usize);
+        }
+        // faces with less than three corners are skipped like the ones without area
+        for i in 1..corners.len().saturating_sub(1) {
+            mesh.indices.push([corners[0], corners[i], corners[i + 1]]);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ASCII: &str = "ply
+format ascii 1.0
+comment a square and a triangle on a line
+element vertex 5
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property uchar red
+property uchar green
+property uchar blue
+element face 2
+property list uchar int vertex_indices
+end_header
+0 0 0 0 0 1 255 0 0
+1 0 0 0 0 1 255 0 0
+1 1 0 0 0 1 0 0 255
+0 1 0 0 0 1 0 0 255
+2 0 0 0 0 1 0 0 0
+4 0 1 2 3
+3 0 1 4
+";
+
+    // the ASCII file as binary, with the vertices in the given byte order
+    fn binary(big_endian: bool) -> Vec<u8> {
+        let format = if big_endian { "binary_big_endian" } else { "binary_little_endian" };
+        let (header, body) = ASCII.split_once("end_header\n").unwrap();
+        let mut bytes = format!("{}end_header\n", header.replace("ascii", format)).into_bytes();
+        for line in body.lines() {
+            let values: Vec<f64> = line.split_whitespace().map(|v| v.parse().unwrap()).collect();
+            if values.len() == 9 {
+                for &v in &values[..6] {
+                    let v = v as f32;
+                    bytes.extend_from_slice(&if big_endian { v.to_be_bytes() } else { v.to_le_bytes() });
+                }
+                bytes.extend(values[6..].iter().map(|&v| v as u8));
+            } else {
+                bytes.push(values[0] as u8);
+                for &v in &values[1..] {
+                    let v = v as i32;
+                    bytes.extend_from_slice(&if big_endian { v.to_be_bytes() } else { v.to_le_bytes() });
+                }
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn parse_test() {
+        for bytes in [ASCII.as_bytes().to_vec(), binary(false), binary(true)] {
+            let mesh = parse(&bytes).unwrap();
+            assert!(mesh.validate().is_ok());
+            // the square is split in two, the triangle on a line is left out together with the vertex only it used
+            assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+            assert_eq!(mesh.positions[2], Vector::new(1.0, 1.0, 0.0));
+            assert_eq!(mesh.normals, vec![Vector::new(0.0, 0.0, 1.0); 4]);
+            assert_eq!(mesh.colors[0], Color::new(1.0, 0.0, 0.0));
+            assert_eq!(mesh.colors[3], Color::new(0.0, 0.0, 1.0));
+            assert!(mesh.uvs.is_empty());
+        }
+    }
+
+    #[test]
+    fn other_elements_test() {
+        let text = "ply
+format ascii 1.0
+element material 1
+property list uchar uchar name
+element vertex 3
+property double x
+property double y
+property double z
+property double s
+property double t
+property short quality
+element face 1
+property uchar flags
+property list uchar uint vertex_index
+end_header
+3 1 2 3
+0 0 0 0 0 7
+1 0 0 1 0 7
+0 1 0 0 1 7
+5 3 0 1 2
+";
+        let mesh = parse(text.as_bytes()).unwrap();
+        assert_eq!(mesh.indices, vec![[0, 1, 2]]);
+        assert_eq!(mesh.uvs, vec![(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]);
+        assert!(mesh.normals.is_empty());
+        assert!(mesh.colors.is_empty());
+    }
+
+    #[test]
+    fn errors_test() {
+        let error = |text: &str| parse(text.as_bytes()).unwrap_err();
+        assert_eq!(error("solid"), "unexpected end of file in the header");
+        assert_eq!(error("obj\nend_header\n"), "not a PLY file");
+        assert_eq!(error(&ASCII.replace("format ascii 1.0", "format ascii 2.0")), "line 2: unsupported version: 2.0");
+        assert_eq!(error(&ASCII.replace("property float nx", "property half nx")), "line 8: unknown type: half");
+        assert_eq!(error(&ASCII.replace("property float x\n", "")), "vertex element needs the properties x, y and z");
+        assert_eq!(error(&ASCII.replace("1 1 0 0 0 1 0 0 255", "1 1 0 0 0 1 0 0")), "line 19: vertex 2: too few values");
+        assert_eq!(error(&ASCII.replace("1 1 0 0 0 1 0 0 255", "1 x 0 0 0 1 0 0 255")), "line 19: vertex 2: invalid number: x");
+        assert_eq!(error(&ASCII.replace("3 0 1 4", "3 0 1 5")), "line 23: face 1 uses vertex 5, there are 5 vertices");
+        assert_eq!(error(&ASCII.replace("3 0 1 4\n", "")), "line 22: face 1: unexpected end of file");
+
+        let mut bytes = binary(false);
+        bytes.pop();
+        let length = bytes.len();
+        assert_eq!(parse(&bytes).unwrap_err(), format!("byte {}: face 1: unexpected end of file", length - 3));
+    }
+}
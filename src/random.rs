@@ -0,0 +1,98 @@
+use std::f64::consts::PI;
+
+use crate::vector::Vector;
+
+// small PCG32 random number generator, so renders are reproducible without pulling in a crate.
+// see: https://www.pcg-random.org/download.html
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+    increment: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        let mut rng = Rng { state: 0, increment: (splitmix(seed) << 1) | 1 };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(splitmix(seed ^ 0x9e37_79b9_7f4a_7c15));
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(self.increment);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rotation = (old >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+
+    // uniformly distributed in range [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        let bits = ((self.next_u32() as u64) << 21) ^ (self.next_u32() as u64 >> 11);
+        bits as f64 / (1u64 << 53) as f64
+    }
+}
+
+// mixes the bits of x, so similar seeds (like neighbouring pixels) give unrelated sequences
+pub fn splitmix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// direction in the hemisphere around normal (which must be normalized), more likely close to the normal.
+// The probability density is cos(angle to the normal) / PI, u and v are uniformly random in range [0, 1)
+// see: https://www.pbr-book.org/3ed-2018/Monte_Carlo_Integration/2D_Sampling_with_Multidimensional_Transformations
+pub fn cosine_hemisphere(normal: &Vector, u: f64, v: f64) -> Vector {
+    let r = u.sqrt();
+    let phi = 2.0 * PI * v;
+    let (t, s) = normal.orthonormal_basis();
+    let mut direction = t * (r * phi.cos()) + s * (r * phi.sin()) + *normal * (1.0 - u).max(0.0).sqrt();
+    direction.normalize();
+    direction
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rng_test() {
+        // same seed, same numbers
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        let mut c = Rng::new(43);
+        let first: Vec<u32> = (0..10).map(|_| a.next_u32()).collect();
+        assert_eq!(first, (0..10).map(|_| b.next_u32()).collect::<Vec<u32>>());
+        assert_ne!(first, (0..10).map(|_| c.next_u32()).collect::<Vec<u32>>());
+
+        let n = 100_000;
+        let mut sum = 0.0;
+        for _ in 0..n {
+            let x = a.next_f64();
+            assert!((0.0..1.0).contains(&x));
+            sum += x;
+        }
+        assert!((sum / n as f64 - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn cosine_hemisphere_test() {
+        let mut rng = Rng::new(7);
+        let mut normal = Vector::new(1.0, 2.0, -0.5);
+        normal.normalize();
+        let n = 100_000;
+        let mut sum = 0.0;
+        for _ in 0..n {
+            let direction = cosine_hemisphere(&normal, rng.next_f64(), rng.next_f64());
+            assert!((direction.length() - 1.0).abs() < 1e-9);
+            let cos = direction.dot(&normal);
+            assert!(cos >= 0.0);
+            sum += cos;
+        }
+        // the average cosine of a cosine weighted hemisphere is 2/3
+        assert!((sum / n as f64 - 2.0 / 3.0).abs() < 0.01);
+    }
+}
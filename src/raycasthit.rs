@@ -1,18 +1,62 @@
-use crate::Vector;
+use crate::vector::Vector;
+
+// everything known about the point where a ray hit a shape
+#[derive(Debug, Clone, Copy)]
+pub struct HitRecord {
+    // point of intersection in world space
+    pub point: Vector,
+    // line parameter of the hit, point = line.point + line.direction * t
+    pub t: f64,
+    // world-space shading normal, normalized and always facing against the ray
+    pub normal: Vector,
+    // parametric surface coordinates of the hit, see Shape::uv_at
+    pub uv: (f64, f64),
+    // angle between the ray direction and the outward normal of the shape, in radians
+    pub angle: f64,
+    // true if the ray hit the outside of the shape, false if it came from the inside or hit a back face
+    pub front_face: bool,
+    // index of the shape that was hit in Object::shapes
+    pub shape_index: usize,
+    // index of the object that was hit, 0 if only a single object was tested
+    pub object_index: usize,
+}
+
+impl HitRecord {
+    // creates a hit record from the outward normal of the hit shape, the normal gets flipped to face the ray
+    pub fn new(direction: &Vector, point: Vector, t: f64, outward_normal: Vector, uv: (f64, f64)) -> HitRecord {
+        let mut outward_normal = outward_normal;
+        outward_normal.normalize();
+        let front_face = direction.dot(&outward_normal) < 0.0;
+        HitRecord {
+            point,
+            t,
+            normal: if front_face { outward_normal } else { outward_normal * -1.0 },
+            uv,
+            angle: direction.angle_radians(&outward_normal),
+            front_face,
+            shape_index: 0,
+            object_index: 0,
+        }
+    }
+}
 
 pub struct RayCastHit {
-    hit: Option<(Vector, f64)>,
+    hit: Option<HitRecord>,
     pub pos_on_screen: (i32, i32),
 }
 
 impl RayCastHit {
-    pub fn new(hit: Option<(Vector, f64)>) -> RayCastHit {
+    pub fn new(hit: Option<HitRecord>) -> RayCastHit {
         RayCastHit {
             hit,
             pos_on_screen: (0, 0),
         }
     }
 
+    pub fn miss() -> RayCastHit {
+        RayCastHit::new(None)
+    }
+
     pub fn is_some(&self) -> bool {
         self.hit.is_some()
     }
@@ -20,11 +64,66 @@ impl RayCastHit {
         self.hit.is_none()
     }
 
-    pub fn unwrap(&self) -> (Vector, f64) {
-        self.hit.unwrap()
+    pub fn hit(&self) -> Option<&HitRecord> {
+        self.hit.as_ref()
+    }
+
+    pub fn hit_mut(&mut self) -> Option<&mut HitRecord> {
+        self.hit.as_mut()
+    }
+
+    pub fn point(&self) -> Option<Vector> {
+        self.hit.map(|hit| hit.point)
+    }
+
+    pub fn t(&self) -> Option<f64> {
+        self.hit.map(|hit| hit.t)
+    }
+
+    pub fn normal(&self) -> Option<Vector> {
+        self.hit.map(|hit| hit.normal)
+    }
+
+    pub fn uv(&self) -> Option<(f64, f64)> {
+        self.hit.map(|hit| hit.uv)
+    }
+
+    pub fn angle(&self) -> Option<f64> {
+        self.hit.map(|hit| hit.angle)
+    }
+
+    pub fn front_face(&self) -> Option<bool> {
+        self.hit.map(|hit| hit.front_face)
     }
 
-    pub fn angle(&self) -> f64 {
-        self.hit.unwrap().1
+    // brightness of the hit in range [0, 1], calculated as sqrt(|cos(angle)|). 0 if nothing was hit
+    pub fn brightness(&self) -> f64 {
+        self.angle().map_or(0.0, |angle| angle.cos().abs().sqrt())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn front_face_test() {
+        let direction = Vector::new(0.0, 0.0, -1.0);
+        let front = HitRecord::new(&direction, Vector::new(0.0, 0.0, 1.0), 4.0, Vector::new(0.0, 0.0, 2.0), (0.0, 0.0));
+        assert!(front.front_face);
+        assert_eq!(front.normal, Vector::new(0.0, 0.0, 1.0));
+
+        let back = HitRecord::new(&direction, Vector::new(0.0, 0.0, 1.0), 4.0, Vector::new(0.0, 0.0, -1.0), (0.0, 0.0));
+        assert!(!back.front_face);
+        assert_eq!(back.normal, Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(back.angle, 0.0);
+    }
+
+    #[test]
+    fn miss_test() {
+        let miss = RayCastHit::miss();
+        assert!(miss.point().is_none());
+        assert!(miss.angle().is_none());
+        assert_eq!(miss.brightness(), 0.0);
+    }
+}
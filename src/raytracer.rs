@@ -0,0 +1,292 @@
+use crate::camera::{Camera, RenderStats};
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::line::Line;
+use crate::material::Material;
+use crate::raycasthit::HitRecord;
+use crate::scene::{Scene, SceneBvh};
+
+// distance rays leaving a surface start away from it, so they don't hit the surface they start on
+pub const BIAS: f64 = 1e-4;
+
+// recursive Whitted-style ray tracer.
+// Every hit is lit by the lights of the scene that are not blocked by other shapes, mirrors and transparent
+// materials send out more rays until max_depth bounces are reached. Rays that miss get the scene background.
+// see: https://www.scratchapixel.com/lessons/3d-basic-rendering/introduction-to-shading/reflection-refraction-fresnel.html
+pub struct RayTracer {
+    pub max_depth: u32,
+    // light reaching every point even in shadow, so shadows aren't completely black
+    pub ambient: Color,
+}
+
+impl RayTracer {
+    pub fn new() -> RayTracer {
+        RayTracer {
+            max_depth: 5,
+            ambient: Color::new(0.05, 0.05, 0.05),
+        }
+    }
+
+    // traces the primary rays of every pixel of the camera, camera.samples of them per pixel.
+    // bvh has to be built from the scene
+    pub fn render(&self, camera: &mut Camera, scene: &Scene, bvh: &SceneBvh) -> Framebuffer {
+        let colors = camera.render_samples(0, |line, _, _, stats| {
+            let mut direction = line.direction;
+            direction.normalize();
+            self.trace(scene, bvh, &Line::new(line.point, direction), 0, stats)
+        });
+        Framebuffer::from_render(camera.render_width as usize, camera.render_height as usize, colors)
+    }
+
+    // color seen along the line, which must have a normalized direction. depth is the number of bounces so far
+    pub fn trace(&self, scene: &Scene, bvh: &SceneBvh, line: &Line, depth: u32, stats: &mut RenderStats) -> Color {
+        let hit = match scene.closest_hit(bvh, line) {
+            Some(hit) => hit,
+            None => return scene.background,
+        };
+        if depth == 0 {
+            stats.hits += 1;
+        }
+
+        let material = scene.material(&hit);
+        let mut color = material.emission + self.direct_light(scene, bvh, &hit, &material, line, stats) * material.diffuse();
+        if depth >= self.max_depth {
+            return color;
+        }
+
+        if material.reflectivity > 0.0 {
+            let reflected = self.reflected(scene, bvh, line, &hit, depth, stats);
+            color += reflected * material.albedo * material.reflectivity;
+        }
+
+        if material.transparency > 0.0 {
+            // the normal faces the ray, so a ray hitting the back face is leaving the material
+            let (eta_i, eta_t) = if hit.front_face { (1.0, material.ior) } else { (material.ior, 1.0) };
+            let cos_i = -line.direction.dot(&hit.normal);
+            let kr = fresnel(cos_i, eta_i, eta_t);
+            let mut transmitted = self.reflected(scene, bvh, line, &hit, depth, stats) * kr;
+            if let Some(mut direction) = line.direction.refract(&hit.normal, eta_i / eta_t) {
+                direction.normalize();
+                let refracted = Line::new(hit.point - hit.normal * BIAS, direction);
+                stats.rays += 1;
+                transmitted += self.trace(scene, bvh, &refracted, depth + 1, stats) * (1.0 - kr);
+            }
+            color += transmitted * material.albedo * material.transparency;
+        }
+        color
+    }
+
+    // light from the lights reflected by the material at the hit back along the line, with shadows.
+    // Area lights give soft shadows because some of their samples are blocked and others are not
+    fn direct_light(&self, scene: &Scene, bvh: &SceneBvh, hit: &HitRecord, material: &Material, line: &Line, stats: &mut RenderStats) -> Color {
+        let mut color = self.ambient * material.albedo;
+        let origin = hit.point + hit.normal * BIAS;
+        let to_viewer = line.direction * -1.0;
+        for light in &scene.lights {
+            for sample in light.samples(&origin) {
+                let cos = hit.normal.dot(&sample.direction);
+                if cos <= 0.0 {
+                    continue;
+                }
+                stats.rays += 1;
+                let shadow = Line::new(origin, sample.direction);
+                if !scene.any_hit(bvh, &shadow, sample.distance) {
+                    color += material.brdf(&hit.normal, &sample.direction, &to_viewer) * sample.radiance * cos;
+                }
+            }
+        }
+        color
+    }
+
+    fn reflected(&self, scene: &Scene, bvh: &SceneBvh, line: &Line, hit: &HitRecord, depth: u32, stats: &mut RenderStats) -> Color {
+        let mut direction = line.direction.reflect(&hit.normal);
+        direction.normalize();
+        stats.rays += 1;
+        self.trace(scene, bvh, &Line::new(hit.point + hit.normal * BIAS, direction), depth + 1, stats)
+    }
+}
+
+impl Default for RayTracer {
+    fn default() -> RayTracer {
+        RayTracer::new()
+    }
+}
+
+// fraction of unpolarized light that is reflected when going from a medium with index of refraction eta_i
+// into one with eta_t, cos_i is the cosine of the angle between the ray and the normal.
+// The rest of the light is refracted, past the critical angle everything is reflected
+// see: https://en.wikipedia.org/wiki/Fresnel_equations
+pub fn fresnel(cos_i: f64, eta_i: f64, eta_t: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin_t = eta_i / eta_t * (1.0 - cos_i * cos_i).sqrt();
+    if sin_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin_t * sin_t).sqrt();
+    let parallel = (eta_t * cos_i - eta_i * cos_t) / (eta_t * cos_i + eta_i * cos_t);
+    let perpendicular = (eta_i * cos_i - eta_t * cos_t) / (eta_i * cos_i + eta_t * cos_t);
+    (parallel * parallel + perpendicular * perpendicular) / 2.0
+}
+
+#[cfg(test)]
+mod test {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::light::Light;
+    use crate::material::Brdf;
+    use crate::object::Object;
+    use crate::sphere::Sphere;
+    use crate::surface::Surface;
+    use crate::vector::Vector;
+
+    fn scene(object: Object, lights: Vec<Light>) -> Scene {
+        let mut scene = Scene::from_object("object", object);
+        scene.lights = lights;
+        scene
+    }
+
+    fn trace(tracer: &RayTracer, scene: &Scene, from: Vector, to: Vector) -> Color {
+        let mut direction = to - from;
+        direction.normalize();
+        let bvh = scene.bvh();
+        tracer.trace(scene, &bvh, &Line::new(from, direction), 0, &mut RenderStats::default())
+    }
+
+    #[test]
+    fn fresnel_test() {
+        // head on into glass 4% is reflected
+        assert!((fresnel(1.0, 1.0, 1.5) - 0.04).abs() < 1e-12);
+        // same from the inside
+        assert!((fresnel(1.0, 1.5, 1.0) - 0.04).abs() < 1e-12);
+        // everything is reflected at grazing angles and past the critical angle
+        assert!((fresnel(0.0, 1.0, 1.5) - 1.0).abs() < 1e-12);
+        assert_eq!(fresnel(0.5, 1.5, 1.0), 1.0);
+    }
+
+    #[test]
+    fn shadow_test() {
+        // white floor with a sphere between it and the light
+        let mut object = Object::new(vec![Surface::new_normal(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0))]);
+        object.add_shape(Sphere::new(Vector::new(0.0, 0.0, 5.0), 1.0));
+        let scene = scene(object, vec![Light::point(Vector::new(0.0, 0.0, 10.0), Color::white(), 100.0)]);
+        let mut tracer = RayTracer::new();
+        tracer.ambient = Color::black();
+
+        let eye = Vector::new(20.0, 0.0, 20.0);
+        assert_eq!(trace(&tracer, &scene, eye, Vector::new(0.0, 0.0, 0.0)), Color::black());
+        // outside of the shadow, lit at 45 degrees from 10 * sqrt(2) away by a white Lambert floor
+        let lit = trace(&tracer, &scene, eye, Vector::new(10.0, 0.0, 0.0));
+        let expected = 100.0 / 200.0 * 0.5f64.sqrt() / PI;
+        assert!((lit.r - expected).abs() < 1e-4, "{:?} {}", lit, expected);
+    }
+
+    #[test]
+    fn soft_shadow_test() {
+        // 4x4 area light above a floor, with a sphere in between
+        let floor = Object::new(vec![Surface::new_normal(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0))]);
+        let mut object = Object::new(vec![Surface::new_normal(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0))]);
+        object.add_shape(Sphere::new(Vector::new(0.0, 0.0, 5.0), 2.0));
+        let panel = Surface::new_vw(
+            Vector::new(0.0, 0.0, 10.0),
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            (-2.0, 2.0),
+            (-2.0, 2.0),
+            Vector::new(0.0, 0.0, -1.0));
+        let light = Light::area(panel, Color::white(), 100.0, 8).unwrap();
+        let (object, floor) = (scene(object, vec![light]), scene(floor, vec![light]));
+        let mut tracer = RayTracer::new();
+        tracer.ambient = Color::black();
+
+        let eye = Vector::new(0.0, -30.0, 30.0);
+        let brightness = |scene: &Scene, x: f64| trace(&tracer, scene, eye, Vector::new(x, 0.0, 0.0)).r;
+        // the sphere hides the whole light from the middle, part of it a bit further out and none of it far away
+        assert_eq!(brightness(&object, 0.0), 0.0);
+        let penumbra = brightness(&object, 3.0);
+        assert!(penumbra > 0.0 && penumbra < brightness(&floor, 3.0) * 0.9);
+        assert_eq!(brightness(&object, 8.0), brightness(&floor, 8.0));
+    }
+
+    #[test]
+    fn mirror_test() {
+        // a mirror facing up reflects the red floor-to-ceiling wall next to it
+        let mut object = Object::from_shapes(Vec::new());
+        object.add_shape_with_material(
+            Surface::new_normal(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0)),
+            Material::mirror(Color::white(), 1.0));
+        object.add_shape_with_material(
+            Surface::new_normal(Vector::new(10.0, 0.0, 0.0), Vector::new(-1.0, 0.0, 0.0)),
+            Material::new(Color::new(1.0, 0.0, 0.0)));
+        let mut scene = scene(object, Vec::new());
+        scene.background = Color::new(0.0, 0.0, 1.0);
+        let mut tracer = RayTracer::new();
+        tracer.ambient = Color::white();
+
+        // down at 45 degrees onto the mirror and from there into the wall
+        assert_eq!(trace(&tracer, &scene, Vector::new(0.0, 0.0, 5.0), Vector::new(5.0, 0.0, 0.0)), Color::new(1.0, 0.0, 0.0));
+        // straight down bounces back up into the background
+        assert_eq!(trace(&tracer, &scene, Vector::new(0.0, 0.0, 5.0), Vector::new(0.0, 0.0, 0.0)), Color::new(0.0, 0.0, 1.0));
+        // without bounces only the diffuse part is left, which a perfect mirror doesn't have
+        tracer.max_depth = 0;
+        assert_eq!(trace(&tracer, &scene, Vector::new(0.0, 0.0, 5.0), Vector::new(5.0, 0.0, 0.0)), Color::black());
+    }
+
+    #[test]
+    fn glass_test() {
+        let mut object = Object::from_shapes(Vec::new());
+        object.add_shape_with_material(Sphere::new(Vector::new(0.0, 0.0, 0.0), 2.0), Material::glass(Color::white(), 1.5));
+        let mut scene = scene(object, Vec::new());
+        scene.background = Color::white();
+        let mut tracer = RayTracer::new();
+        tracer.ambient = Color::black();
+        tracer.max_depth = 20;
+
+        // clear glass doesn't absorb anything, all light ends up in the background one way or another.
+        // Near the edge a little is lost in bounces past max_depth
+        for offset in [0.0, 0.7, 1.5, 1.99] {
+            let color = trace(&tracer, &scene, Vector::new(offset, 0.0, 10.0), Vector::new(offset, 0.0, 0.0));
+            assert!((color.r - 1.0).abs() < 1e-4, "{} {:?}", offset, color);
+        }
+
+        // a ray through the center isn't bent, so it sees the red wall behind the sphere.
+        // The 8% reflected at the two sides of the sphere goes to the white background
+        let mut wall = Object::from_shapes(Vec::new());
+        wall.add_shape_with_material(
+            Surface::new_normal(Vector::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0)),
+            Material::new(Color::new(1.0, 0.0, 0.0)));
+        scene.add_object("wall", wall);
+        tracer.ambient = Color::white();
+        let color = trace(&tracer, &scene, Vector::new(0.0, 0.0, 10.0), Vector::new(0.0, 0.0, 0.0));
+        assert!((color.r - 1.0).abs() < 1e-6);
+        assert!((color.g - (1.0 - 0.96 * 0.96)).abs() < 0.01);
+    }
+
+    #[test]
+    fn material_test() {
+        // the same light and view on different materials
+        let floor = Surface::new_normal(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
+        let mut tracer = RayTracer::new();
+        tracer.ambient = Color::black();
+        let shade_with = |lights: Vec<Light>, material: Material, to: Vector| {
+            let mut object = Object::from_shapes(Vec::new());
+            object.add_shape_with_material(floor, material);
+            trace(&tracer, &scene(object, lights), Vector::new(10.0, 0.0, 10.0), to)
+        };
+        let shade = |material: Material, to: Vector| shade_with(vec![Light::point(Vector::new(-10.0, 0.0, 10.0), Color::white(), 1000.0)], material, to);
+        let center = Vector::new(0.0, 0.0, 0.0);
+        let aside = Vector::new(0.0, 5.0, 0.0);
+
+        // a diffuse floor looks the same from everywhere, a shiny one has a highlight where the light is mirrored
+        let lambert = Material::new(Color::white());
+        assert!((shade(lambert, center).r - shade(lambert, aside).r).abs() < 0.2 * shade(lambert, center).r);
+        for shiny in [Material::blinn_phong(Color::white(), Color::white(), 100.0), Material::cook_torrance(Color::white(), 0.2, 0.0)] {
+            assert_ne!(shiny.brdf, Brdf::Lambert);
+            assert!(shade(shiny, center).r > shade(lambert, center).r * 2.0);
+            assert!(shade(shiny, aside).r < shade(shiny, center).r * 0.2);
+        }
+
+        // emission is added even without any light
+        assert_eq!(shade_with(Vec::new(), Material::emissive(Color::new(0.0, 2.0, 0.0)), center), Color::new(0.0, 2.0, 0.0));
+    }
+}
@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+
+use crate::random::Rng;
+
+// where the rays of a pixel go through it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SamplePattern {
+    // evenly spaced grid, the same for every pixel. A single sample goes through the center
+    Regular,
+    // one random spot in each cell of the grid, trades the staircase on edges for noise
+    Jittered,
+    // Halton sequence in bases 2 and 3, shifted by a random offset per pixel so neighbouring pixels differ.
+    // Covers the pixel evenly for any number of samples, not just square ones
+    // see: https://en.wikipedia.org/wiki/Halton_sequence
+    Halton,
+}
+
+// weights the samples of a pixel by their distance to the pixel center.
+// Filters wider than half a pixel spread the samples of a pixel over its neighbours, which blurs
+// a bit but hides the edges of the pixels better
+// see: https://www.pbr-book.org/3ed-2018/Sampling_and_Reconstruction/Image_Reconstruction
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Filter {
+    // every sample inside the pixel counts the same
+    Box,
+    // falls off linearly, 0 at radius pixels from the center
+    Tent { radius: f64 },
+    // bell curve with standard deviation sigma pixels, cut off and shifted to 0 at radius pixels
+    Gaussian { radius: f64, sigma: f64 },
+}
+
+impl SamplePattern {
+    // count points in the unit square [0, 1) x [0, 1). Regular and Jittered use a grid of
+    // ceil(sqrt(count)) columns, the last row is only partly filled if count is not a square number
+    pub fn points(&self, count: usize, rng: &mut Rng) -> Vec<(f64, f64)> {
+        let columns = (count as f64).sqrt().ceil().max(1.0) as usize;
+        let rows = count.div_ceil(columns);
+        let cell = |k: usize, u: f64, v: f64| (((k % columns) as f64 + u) / columns as f64, ((k / columns) as f64 + v) / rows as f64);
+        match self {
+            SamplePattern::Regular => (0..count).map(|k| cell(k, 0.5, 0.5)).collect(),
+            SamplePattern::Jittered => (0..count).map(|k| cell(k, rng.next_f64(), rng.next_f64())).collect(),
+            SamplePattern::Halton => {
+                let (du, dv) = (rng.next_f64(), rng.next_f64());
+                (0..count as u64)
+                    .map(|k| ((radical_inverse(k, 2) + du).fract(), (radical_inverse(k, 3) + dv).fract()))
+                    .collect()
+            }
+        }
+    }
+}
+
+impl Filter {
+    // distance in pixels from the pixel center past which samples don't count. 0 for invalid filters
+    pub fn radius(&self) -> f64 {
+        match *self {
+            Filter::Box => 0.5,
+            // max turns NaN into 0 as well
+            Filter::Tent { radius } | Filter::Gaussian { radius, .. } => radius.max(0.0),
+        }
+    }
+
+    // the radius and sigma have to be positive, weight divides by them
+    pub fn validate(&self) -> Result<(), String> {
+        if self.is_valid() {
+            Ok(())
+        } else {
+            Err(format!("invalid filter {:?}, the radius and sigma have to be positive", self))
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        match *self {
+            Filter::Box => true,
+            Filter::Tent { radius } => radius > 0.0,
+            Filter::Gaussian { radius, sigma } => radius > 0.0 && sigma > 0.0,
+        }
+    }
+
+    // weight of a sample dx, dy pixels away from the pixel center. Invalid filters weigh every sample 0,
+    // so the camera counts all of them the same
+    pub fn weight(&self, dx: f64, dy: f64) -> f64 {
+        if !self.is_valid() {
+            return 0.0;
+        }
+        match *self {
+            Filter::Box => {
+                if dx.abs() <= 0.5 && dy.abs() <= 0.5 { 1.0 } else { 0.0 }
+            }
+            Filter::Tent { radius } => (1.0 - dx.abs() / radius).max(0.0) * (1.0 - dy.abs() / radius).max(0.0),
+            Filter::Gaussian { radius, sigma } => {
+                let gaussian = |d: f64| ((-d * d / (2.0 * sigma * sigma)).exp() - (-radius * radius / (2.0 * sigma * sigma)).exp()).max(0.0);
+                gaussian(dx) * gaussian(dy)
+            }
+        }
+    }
+}
+
+// digits of index in the given base mirrored around the decimal point, 0.d1 d2 d3 ... for index ... d3 d2 d1
+pub fn radical_inverse(index: u64, base: u64) -> f64 {
+    let mut index = index;
+    let mut result = 0.0;
+    let mut scale = 1.0 / base as f64;
+    while index > 0 {
+        result += (index % base) as f64 * scale;
+        index /= base;
+        scale /= base as f64;
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn radical_inverse_test() {
+        assert_eq!(radical_inverse(0, 2), 0.0);
+        assert_eq!(radical_inverse(1, 2), 0.5);
+        assert_eq!(radical_inverse(6, 2), 0.375);
+        assert!((radical_inverse(5, 3) - 7.0 / 9.0).abs() < 1e-15);
+    }
+
+    #[test]
+    fn pattern_test() {
+        let mut rng = Rng::new(5);
+        assert_eq!(SamplePattern::Regular.points(1, &mut rng), vec![(0.5, 0.5)]);
+        assert_eq!(SamplePattern::Regular.points(4, &mut rng), vec![(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]);
+
+        for pattern in [SamplePattern::Regular, SamplePattern::Jittered, SamplePattern::Halton] {
+            for count in [1, 3, 16] {
+                let points = pattern.points(count, &mut rng);
+                assert_eq!(points.len(), count);
+                assert!(points.iter().all(|&(u, v)| (0.0..1.0).contains(&u) && (0.0..1.0).contains(&v)));
+            }
+            // every half of the pixel gets about half of the samples
+            let points = pattern.points(16, &mut rng);
+            let left = points.iter().filter(|&&(u, _)| u < 0.5).count();
+            let bottom = points.iter().filter(|&&(_, v)| v < 0.5).count();
+            assert!((7..=9).contains(&left) && (7..=9).contains(&bottom), "{:?} {} {}", pattern, left, bottom);
+        }
+        // jittered points stay in their cell
+        for (k, (u, v)) in SamplePattern::Jittered.points(9, &mut rng).into_iter().enumerate() {
+            assert_eq!(((u * 3.0) as usize, (v * 3.0) as usize), (k % 3, k / 3));
+        }
+    }
+
+    #[test]
+    fn filter_test() {
+        let filters = [Filter::Box, Filter::Tent { radius: 1.0 }, Filter::Gaussian { radius: 1.5, sigma: 0.5 }];
+        for filter in filters {
+            assert!(filter.weight(0.0, 0.0) > 0.0);
+            assert_eq!(filter.weight(filter.radius() + 0.01, 0.0), 0.0);
+            // symmetric and largest in the center
+            assert_eq!(filter.weight(0.3, -0.2), filter.weight(-0.3, 0.2));
+            assert!(filter.weight(0.4, 0.1) <= filter.weight(0.0, 0.0));
+        }
+        assert_eq!(Filter::Tent { radius: 1.0 }.weight(0.5, 0.5), 0.25);
+
+        // no NaN from filters that don't validate
+        let invalid = [Filter::Tent { radius: 0.0 }, Filter::Tent { radius: -1.0 }, Filter::Gaussian { radius: 1.0, sigma: 0.0 }, Filter::Gaussian { radius: f64::NAN, sigma: 1.0 }];
+        for filter in invalid {
+            assert!(filter.validate().is_err());
+            assert!(filter.radius() >= 0.0);
+            assert_eq!(filter.weight(0.0, 0.0), 0.0);
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use std::fs;
+use std::io;
+
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::light::Light;
+use crate::line::Line;
+use crate::material::Material;
+use crate::mesh::Mesh;
+use crate::object::Object;
+use crate::raycasthit::HitRecord;
+use crate::scenefile::SceneFile;
+
+// object of a scene and the name it can be looked up by
+pub struct SceneObject {
+    pub name: String,
+    pub object: Object,
+}
+
+// everything that is rendered together: the objects, the lights shining on them,
+// the light coming from behind them and the cameras looking at them
+pub struct Scene {
+    pub objects: Vec<SceneObject>,
+    pub lights: Vec<Light>,
+    // light coming from everywhere rays leave the scene to
+    pub background: Color,
+    // cameras keep render state, so render with a clone: scene.cameras[0].clone()
+    pub cameras: Vec<Camera>,
+}
+
+// acceleration structures of a scene, the bvh of every object and the world space box around it.
+// Build it once with Scene::bvh and pass it to every render. It has to be rebuilt after shapes or objects
+// are changed, added or removed. After objects are only moved, update_bounds is enough
+pub struct SceneBvh {
+    bvhs: Vec<Bvh>,
+    bounds: Vec<Aabb>,
+}
+
+impl Scene {
+    pub fn new() -> Scene {
+        Scene {
+            objects: Vec::new(),
+            lights: Vec::new(),
+            background: Color::black(),
+            cameras: Vec::new(),
+        }
+    }
+
+    // scene with just this object in it
+    pub fn from_object(name: &str, object: Object) -> Scene {
+        let mut scene = Scene::new();
+        scene.add_object(name, object);
+        scene
+    }
+
+    // adds the object and returns its index, which is the object_index of its hits.
+    // An object that already has the name is replaced and keeps its index
+    pub fn add_object(&mut self, name: &str, object: Object) -> usize {
+        if let Some(index) = self.object_index(name) {
+            self.objects[index].object = object;
+            return index;
+        }
+        self.objects.push(SceneObject { name: String::from(name), object });
+        self.objects.len() - 1
+    }
+
+    pub fn remove_object(&mut self, name: &str) -> Option<Object> {
+        let index = self.object_index(name)?;
+        Some(self.objects.remove(index).object)
+    }
+
+    pub fn object_index(&self, name: &str) -> Option<usize> {
+        self.objects.iter().position(|o| o.name == name)
+    }
+
+    pub fn object(&self, name: &str) -> Option<&Object> {
+        self.objects.iter().find(|o| o.name == name).map(|o| &o.object)
+    }
+
+    pub fn object_mut(&mut self, name: &str) -> Option<&mut Object> {
+        self.objects.iter_mut().find(|o| o.name == name).map(|o| &mut o.object)
+    }
+
+    pub fn add_light(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+
+    pub fn add_camera(&mut self, camera: Camera) {
+        self.cameras.push(camera);
+    }
+
+    // box containing every object in world space, empty if there are none
+    pub fn bounds(&self) -> Aabb {
+        self.objects.iter().fold(Aabb::empty(), |bounds, o| bounds.union(&o.object.bounds()))
+    }
+
+    pub fn bvh(&self) -> SceneBvh {
+        SceneBvh {
+            bvhs: self.objects.iter().map(|o| o.object.bvh()).collect(),
+            bounds: self.objects.iter().map(|o| o.object.bounds()).collect(),
+        }
+    }
+
+    // closest hit of the line on any object, None if nothing is hit. object_index and shape_index
+    // of the hit tell which shape it was. bvh has to be built from this scene
+    pub fn closest_hit(&self, bvh: &SceneBvh, line: &Line) -> Option<HitRecord> {
+        let mut closest: Option<HitRecord> = None;
+        for (index, o) in self.objects.iter().enumerate() {
+            let t_max = closest.map_or(f64::INFINITY, |hit| hit.t);
+            if bvh.bounds[index].intersect(line, 0.0, t_max).is_none() {
+                continue;
+            }
+            if let Some(mut hit) = line.intersection_bvh(&o.object, &bvh.bvhs[index], &false).hit().copied() {
+                if hit.t < t_max {
+                    hit.object_index = index;
+                    closest = Some(hit);
+                }
+            }
+        }
+        closest
+    }
+
+    // true if any object is hit by the line before max_t, for shadow rays
+    pub fn any_hit(&self, bvh: &SceneBvh, line: &Line, max_t: f64) -> bool {
+        self.objects.iter().enumerate().any(|(index, o)| {
+            bvh.bounds[index].intersect(line, 0.0, max_t).is_some() && o.object.any_hit(&bvh.bvhs[index], line, max_t)
+        })
+    }
+
+    // material of the shape that was hit
+    pub fn material(&self, hit: &HitRecord) -> Material {
+        self.objects[hit.object_index].object.material(hit.shape_index)
+    }
+
+    // every object as triangles in world space, see Object::to_mesh
+    pub fn to_mesh(&self, segments: usize) -> Result<Mesh, String> {
+        let mut mesh = Mesh::new();
+        for o in &self.objects {
+            mesh.append(&o.object.to_mesh(segments).map_err(|e| format!("{}: {}", o.name, e))?);
+        }
+        Ok(mesh)
+    }
+
+    // reads a scene file, see SceneFile for the format
+    pub fn load(path: &str) -> io::Result<Scene> {
+        let text = fs::read_to_string(path)?;
+        let file = SceneFile::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e)))?;
+        Ok(file.to_scene())
+    }
+
+    // writes the scene to a scene file, fails with InvalidData if it has shapes that can't be saved
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let text = SceneFile::from_scene(self).and_then(|file| file.to_ron()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, text)
+    }
+}
+
+impl SceneBvh {
+    // recomputes the world space boxes for objects that were moved, the bvhs are in object space and stay the same
+    pub fn update_bounds(&mut self, scene: &Scene) {
+        self.bounds = scene.objects.iter().map(|o| o.object.bounds()).collect();
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Scene {
+        Scene::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sphere::Sphere;
+    use crate::surface::Surface;
+    use crate::vector::Vector;
+
+    // a floor, a wall and a ball resting on the floor
+    fn room() -> Scene {
+        let mut scene = Scene::new();
+        let floor = Surface::new_normal(Vector::new(0.0, -5.0, 0.0), Vector::new(0.0, 1.0, 0.0));
+        scene.add_object("floor", Object::new(vec![floor]));
+        let wall = Surface::new_vw(Vector::new(0.0, 0.0, -10.0), Vector::new(1.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0), (-20.0, 20.0), (-5.0, 20.0), Vector::new(0.0, 0.0, 1.0));
+        scene.add_object("wall", Object::new(vec![wall]));
+        let mut ball = Object::from_shapes(Vec::new());
+        ball.add_shape_with_material(Sphere::new(Vector::new(0.0, 0.0, 0.0), 2.0), Material::new(Color::new(1.0, 0.0, 0.0)));
+        ball.translate(&Vector::new(0.0, -3.0, 0.0));
+        scene.add_object("ball", ball);
+        scene
+    }
+
+    #[test]
+    fn closest_hit_test() {
+        let scene = room();
+        let bvh = scene.bvh();
+
+        // straight at the ball, which is in front of the wall
+        let hit = scene.closest_hit(&bvh, &Line::new(Vector::new(0.0, -3.0, 20.0), Vector::new(0.0, 0.0, -1.0))).unwrap();
+        assert_eq!(hit.object_index, scene.object_index("ball").unwrap());
+        assert!((hit.t - 18.0).abs() < 1e-12);
+        assert_eq!(scene.material(&hit).albedo, Color::new(1.0, 0.0, 0.0));
+
+        // past the ball onto the wall, and down onto the floor
+        let hit = scene.closest_hit(&bvh, &Line::new(Vector::new(10.0, 0.0, 20.0), Vector::new(0.0, 0.0, -1.0))).unwrap();
+        assert_eq!(scene.objects[hit.object_index].name, "wall");
+        assert_eq!(hit.t, 30.0);
+        let hit = scene.closest_hit(&bvh, &Line::new(Vector::new(10.0, 0.0, 0.0), Vector::new(0.0, -1.0, 0.0))).unwrap();
+        assert_eq!(scene.objects[hit.object_index].name, "floor");
+        assert!(scene.closest_hit(&bvh, &Line::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0))).is_none());
+
+        let shadow = Line::new(Vector::new(0.0, -3.0, 20.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(scene.any_hit(&bvh, &shadow, 19.0));
+        assert!(!scene.any_hit(&bvh, &shadow, 17.0));
+    }
+
+    #[test]
+    fn update_bounds_test() {
+        let mut scene = room();
+        let mut bvh = scene.bvh();
+        let line = Line::new(Vector::new(10.0, -3.0, 20.0), Vector::new(0.0, 0.0, -1.0));
+        assert_eq!(scene.objects[scene.closest_hit(&bvh, &line).unwrap().object_index].name, "wall");
+
+        // the ball moved in front of the line is missed until the boxes are updated
+        scene.object_mut("ball").unwrap().translate(&Vector::new(10.0, 0.0, 0.0));
+        assert_eq!(scene.objects[scene.closest_hit(&bvh, &line).unwrap().object_index].name, "wall");
+        bvh.update_bounds(&scene);
+        let hit = scene.closest_hit(&bvh, &line).unwrap();
+        assert_eq!(scene.objects[hit.object_index].name, "ball");
+        assert!((hit.t - 18.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn objects_test() {
+        let mut scene = room();
+        assert_eq!(scene.objects.len(), 3);
+        assert!(scene.object("ceiling").is_none());
+
+        // adding under a name that is taken replaces the object
+        let index = scene.object_index("ball").unwrap();
+        assert_eq!(scene.add_object("ball", Object::cube(1.0)), index);
+        assert_eq!(scene.object("ball").unwrap().shapes.len(), 6);
+
+        scene.object_mut("ball").unwrap().translate(&Vector::new(0.0, 0.0, 100.0));
+        // the floor is infinite
+        assert_eq!(scene.bounds().max.z, f64::INFINITY);
+        scene.remove_object("floor");
+        assert!((scene.bounds().max.z - 101.0).abs() < 1e-12);
+        assert!(scene.remove_object("ball").is_some());
+        assert!(scene.remove_object("ball").is_none());
+        assert_eq!(scene.objects.len(), 1);
+    }
+}
@@ -0,0 +1,797 @@
+use std::any::Any;
+
+use serde::{Deserialize, Serialize};
+
+use crate::camera::{Camera, Projection};
+use crate::color::Color;
+use crate::cone::Cone;
+use crate::cylinder::Cylinder;
+use crate::disk::Disk;
+use crate::light::Light;
+use crate::material::{Brdf, Material};
+use crate::math::{as_degrees, as_radians};
+use crate::object::Object;
+use crate::quaternion::Quaternion;
+use crate::sampling::{Filter, SamplePattern};
+use crate::scene::Scene;
+use crate::shape::Shape;
+use crate::sphere::Sphere;
+use crate::surface::Surface;
+use crate::torus::Torus;
+use crate::transform::Transform;
+use crate::triangle::{SmoothTriangle, Triangle};
+use crate::vector::Vector;
+
+// x, y, z of points and directions, r, g, b of colors
+pub type Vec3 = (f64, f64, f64);
+
+// text version of a scene, written in RON (https://github.com/ron-rs/ron) so it can be edited by hand.
+// Every field can be left out and gets a default, a minimal file looks like this:
+//
+// (
+//     objects: [
+//         (
+//             name: "ball",
+//             material: (albedo: (1, 0, 0)),
+//             shapes: [Sphere(center: (0, 0, 0), radius: 10)],
+//         ),
+//     ],
+//     lights: [Point(position: (0, 50, 50), color: (1, 1, 1), intensity: 20000)],
+// )
+//
+// Angles are in degrees, shapes are in object space and placed by the transform of their object
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneFile {
+    pub background: Vec3,
+    pub objects: Vec<ObjectDescription>,
+    pub lights: Vec<LightDescription>,
+    pub cameras: Vec<CameraDescription>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ObjectDescription {
+    pub name: String,
+    pub transform: TransformDescription,
+    // material of the shapes that don't have their own
+    pub material: MaterialDescription,
+    pub shapes: Vec<ShapeDescription>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransformDescription {
+    pub translation: Vec3,
+    // rotation by degrees around axis, which doesn't have to be normalized
+    pub axis: Vec3,
+    pub degrees: f64,
+    pub scale: Vec3,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ShapeDescription {
+    // bounded surface, a parallelogram
+    Surface {
+        point: Vec3,
+        v: Vec3,
+        w: Vec3,
+        v_range: (f64, f64),
+        w_range: (f64, f64),
+        normal: Vec3,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialDescription>,
+    },
+    // infinite surface
+    Plane {
+        point: Vec3,
+        normal: Vec3,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialDescription>,
+    },
+    Triangle {
+        a: Vec3,
+        b: Vec3,
+        c: Vec3,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialDescription>,
+    },
+    // triangle shaded with the normals at a, b and c, which don't have to be normalized
+    SmoothTriangle {
+        a: Vec3,
+        b: Vec3,
+        c: Vec3,
+        normals: (Vec3, Vec3, Vec3),
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialDescription>,
+    },
+    Sphere {
+        center: Vec3,
+        radius: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialDescription>,
+    },
+    Cylinder {
+        base: Vec3,
+        axis: Vec3,
+        radius: f64,
+        height: f64,
+        capped: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialDescription>,
+    },
+    Cone {
+        apex: Vec3,
+        axis: Vec3,
+        radius: f64,
+        height: f64,
+        capped: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialDescription>,
+    },
+    Disk {
+        center: Vec3,
+        normal: Vec3,
+        radius: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialDescription>,
+    },
+    Torus {
+        center: Vec3,
+        axis: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialDescription>,
+    },
+}
+
+// same fields as Material, left out fields are the ones of a white diffuse material
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaterialDescription {
+    pub brdf: Brdf,
+    pub albedo: Vec3,
+    pub specular: Vec3,
+    pub shininess: f64,
+    pub roughness: f64,
+    pub metalness: f64,
+    pub emission: Vec3,
+    pub reflectivity: f64,
+    pub transparency: f64,
+    pub ior: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LightDescription {
+    Point { position: Vec3, color: Vec3, intensity: f64 },
+    Directional { direction: Vec3, color: Vec3, intensity: f64 },
+    Spot { position: Vec3, direction: Vec3, color: Vec3, intensity: f64, inner_angle: f64, outer_angle: f64 },
+    // rectangle point + t * v + s * w for t in v_range and s in w_range, shining to the side normal points to
+    Area {
+        point: Vec3,
+        v: Vec3,
+        w: Vec3,
+        v_range: (f64, f64),
+        w_range: (f64, f64),
+        normal: Vec3,
+        color: Vec3,
+        intensity: f64,
+        samples: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ProjectionDescription {
+    // world-space size of the viewport
+    Orthographic { width: f64, height: f64 },
+    // vertical field of view, the aspect ratio comes from the size of the camera
+    Perspective { fov: f64 },
+}
+
+// left out fields are the ones of the viewer camera
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraDescription {
+    pub position: Vec3,
+    pub direction: Vec3,
+    pub up: Vec3,
+    pub right: Vec3,
+    // render size in pixels
+    pub size: (i32, i32),
+    pub projection: ProjectionDescription,
+    pub backface_culling: bool,
+    pub samples: usize,
+    pub pattern: SamplePattern,
+    pub filter: Filter,
+}
+
+impl SceneFile {
+    // fails for text that isn't a scene file and for files that describe shapes, lights or cameras that can't be made
+    pub fn parse(text: &str) -> Result<SceneFile, String> {
+        let file: SceneFile = ron::from_str(text).map_err(|e| e.to_string())?;
+        file.validate()?;
+        Ok(file)
+    }
+
+    // checks every shape, light and camera, to_scene expects a valid file
+    pub fn validate(&self) -> Result<(), String> {
+        for object in &self.objects {
+            for shape in &object.shapes {
+                shape.validate().map_err(|e| format!("object {}: {}", object.name, e))?;
+            }
+        }
+        for light in &self.lights {
+            light.validate()?;
+        }
+        for camera in &self.cameras {
+            camera.filter.validate()?;
+        }
+        Ok(())
+    }
+
+    pub fn to_ron(&self) -> Result<String, String> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new()).map_err(|e| e.to_string())
+    }
+
+    // description of every object, light and camera of the scene. Fails for shapes that aren't part of the
+    // library, they can't be described. Camera rotations are applied to their vectors
+    pub fn from_scene(scene: &Scene) -> Result<SceneFile, String> {
+        let objects = scene.objects.iter()
+            .map(|o| ObjectDescription::from_object(&o.name, &o.object))
+            .collect::<Result<Vec<ObjectDescription>, String>>()?;
+        Ok(SceneFile {
+            background: rgb(&scene.background),
+            objects,
+            lights: scene.lights.iter()
+                .map(|light| LightDescription::from_light(light).ok_or(String::from("area lights without any area can't be saved")))
+                .collect::<Result<Vec<LightDescription>, String>>()?,
+            cameras: scene.cameras.iter().map(CameraDescription::from_camera).collect(),
+        })
+    }
+
+    pub fn to_scene(&self) -> Scene {
+        let mut scene = Scene::new();
+        scene.background = color(self.background);
+        for description in &self.objects {
+            scene.add_object(&description.name, description.to_object());
+        }
+        scene.lights = self.lights.iter().map(LightDescription::to_light).collect();
+        scene.cameras = self.cameras.iter().map(CameraDescription::to_camera).collect();
+        scene
+    }
+}
+
+impl Default for SceneFile {
+    fn default() -> SceneFile {
+        SceneFile::from_scene(&Scene::new()).expect("an empty scene has no shapes")
+    }
+}
+
+impl ObjectDescription {
+    pub fn from_object(name: &str, object: &Object) -> Result<ObjectDescription, String> {
+        let material = MaterialDescription::from_material(&object.material(0));
+        let shapes = object.shapes.iter().enumerate()
+            .map(|(index, shape)| {
+                let own = MaterialDescription::from_material(&object.material(index));
+                let own = if own == material { None } else { Some(own) };
+                ShapeDescription::from_shape(shape.as_ref(), own).ok_or(format!("object {} has a shape that can't be saved", name))
+            })
+            .collect::<Result<Vec<ShapeDescription>, String>>()?;
+        Ok(ObjectDescription {
+            name: String::from(name),
+            transform: TransformDescription::from_transform(&object.transform()),
+            material,
+            shapes,
+        })
+    }
+
+    pub fn to_object(&self) -> Object {
+        let mut object = Object::from_shapes(Vec::new());
+        for shape in &self.shapes {
+            let material = shape.material().unwrap_or(self.material).to_material();
+            match *shape {
+                ShapeDescription::Surface { point, v, w, v_range, w_range, normal, .. } => {
+                    object.add_shape_with_material(Surface::new_vw(vector(point), vector(v), vector(w), v_range, w_range, unit(normal)), material);
+                }
+                ShapeDescription::Plane { point, normal, .. } => {
+                    object.add_shape_with_material(Surface::new_normal(vector(point), unit(normal)), material);
+                }
+                ShapeDescription::Triangle { a, b, c, .. } => {
+                    object.add_shape_with_material(Triangle::new(vector(a), vector(b), vector(c)), material);
+                }
+                ShapeDescription::SmoothTriangle { a, b, c, normals, .. } => {
+                    let triangle = Triangle::new(vector(a), vector(b), vector(c));
+                    object.add_shape_with_material(SmoothTriangle::new(triangle, [unit(normals.0), unit(normals.1), unit(normals.2)]), material);
+                }
+                ShapeDescription::Sphere { center, radius, .. } => {
+                    object.add_shape_with_material(Sphere::new(vector(center), radius), material);
+                }
+                ShapeDescription::Cylinder { base, axis, radius, height, capped, .. } => {
+                    object.add_shape_with_material(Cylinder::new(vector(base), unit(axis), radius, height, capped), material);
+                }
+                ShapeDescription::Cone { apex, axis, radius, height, capped, .. } => {
+                    object.add_shape_with_material(Cone::new(vector(apex), unit(axis), radius, height, capped), material);
+                }
+                ShapeDescription::Disk { center, normal, radius, .. } => {
+                    object.add_shape_with_material(Disk::new(vector(center), unit(normal), radius), material);
+                }
+                ShapeDescription::Torus { center, axis, major_radius, minor_radius, .. } => {
+                    object.add_shape_with_material(Torus::new(vector(center), unit(axis), major_radius, minor_radius), material);
+                }
+            }
+        }
+        object.set_transform(self.transform.to_transform());
+        object
+    }
+}
+
+impl Default for ObjectDescription {
+    fn default() -> ObjectDescription {
+        ObjectDescription {
+            name: String::from("object"),
+            transform: TransformDescription::default(),
+            material: MaterialDescription::default(),
+            shapes: Vec::new(),
+        }
+    }
+}
+
+impl TransformDescription {
+    pub fn from_transform(transform: &Transform) -> TransformDescription {
+        let mut q = transform.rotation;
+        q.normalize();
+        // q = (cos(angle / 2), sin(angle / 2) * axis)
+        let sin = (1.0 - q.real * q.real).max(0.0).sqrt();
+        let (axis, degrees) = if sin < 1e-12 {
+            ((0.0, 1.0, 0.0), 0.0)
+        } else {
+            let mut axis = q.ivec;
+            axis.normalize();
+            (vec3(&axis), as_degrees(2.0 * q.real.clamp(-1.0, 1.0).acos()))
+        };
+        TransformDescription { translation: vec3(&transform.translation), axis, degrees, scale: vec3(&transform.scale) }
+    }
+
+    pub fn to_transform(&self) -> Transform {
+        let mut rotation = Quaternion::identity();
+        if self.degrees != 0.0 {
+            rotation.rotate(as_radians(self.degrees), unit(self.axis));
+        }
+        Transform::new(vector(self.translation), rotation, vector(self.scale))
+    }
+}
+
+impl Default for TransformDescription {
+    fn default() -> TransformDescription {
+        TransformDescription::from_transform(&Transform::identity())
+    }
+}
+
+impl ShapeDescription {
+    // None for shapes defined outside of the library
+    pub fn from_shape(shape: &dyn Shape, material: Option<MaterialDescription>) -> Option<ShapeDescription> {
+        let shape: &dyn Any = shape;
+        if let Some(s) = shape.downcast_ref::<Surface>() {
+            return Some(match (s.v, s.w, s.max_v, s.max_w) {
+                (Some(v), Some(w), Some(v_range), Some(w_range)) => {
+                    ShapeDescription::Surface { point: vec3(&s.point), v: vec3(&v), w: vec3(&w), v_range, w_range, normal: vec3(&s.normal), material }
+                }
+                _ => ShapeDescription::Plane { point: vec3(&s.point), normal: vec3(&s.normal), material },
+            });
+        }
+        if let Some(t) = shape.downcast_ref::<Triangle>() {
+            return Some(ShapeDescription::Triangle { a: vec3(&t.a), b: vec3(&t.b), c: vec3(&t.c), material });
+        }
+        if let Some(s) = shape.downcast_ref::<SmoothTriangle>() {
+            let (t, [na, nb, nc]) = (s.triangle, s.normals);
+            return Some(ShapeDescription::SmoothTriangle { a: vec3(&t.a), b: vec3(&t.b), c: vec3(&t.c), normals: (vec3(&na), vec3(&nb), vec3(&nc)), material });
+        }
+        if let Some(s) = shape.downcast_ref::<Sphere>() {
+            return Some(ShapeDescription::Sphere { center: vec3(&s.center), radius: s.radius, material });
+        }
+        if let Some(c) = shape.downcast_ref::<Cylinder>() {
+            return Some(ShapeDescription::Cylinder { base: vec3(&c.base), axis: vec3(&c.axis), radius: c.radius, height: c.height, capped: c.capped, material });
+        }
+        if let Some(c) = shape.downcast_ref::<Cone>() {
+            return Some(ShapeDescription::Cone { apex: vec3(&c.apex), axis: vec3(&c.axis), radius: c.radius, height: c.height, capped: c.capped, material });
+        }
+        if let Some(d) = shape.downcast_ref::<Disk>() {
+            return Some(ShapeDescription::Disk { center: vec3(&d.center), normal: vec3(&d.normal), radius: d.radius, material });
+        }
+        if let Some(t) = shape.downcast_ref::<Torus>() {
+            return Some(ShapeDescription::Torus { center: vec3(&t.center), axis: vec3(&t.axis), major_radius: t.major_radius, minor_radius: t.minor_radius, material });
+        }
+        None
+    }
+
+    // fails for directions of length 0 and for sizes that aren't positive
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            ShapeDescription::Surface { normal, .. } | ShapeDescription::Plane { normal, .. } => non_zero("surface normal", normal),
+            ShapeDescription::Triangle { .. } | ShapeDescription::SmoothTriangle { .. } => Ok(()),
+            ShapeDescription::Sphere { radius, .. } => positive("sphere radius", radius),
+            ShapeDescription::Cylinder { axis, radius, height, .. } => {
+                non_zero("cylinder axis", axis)?;
+                positive("cylinder radius", radius)?;
+                positive("cylinder height", height)
+            }
+            ShapeDescription::Cone { axis, radius, height, .. } => {
+                non_zero("cone axis", axis)?;
+                positive("cone radius", radius)?;
+                positive("cone height", height)
+            }
+            ShapeDescription::Disk { normal, radius, .. } => {
+                non_zero("disk normal", normal)?;
+                positive("disk radius", radius)
+            }
+            ShapeDescription::Torus { axis, major_radius, minor_radius, .. } => {
+                non_zero("torus axis", axis)?;
+                positive("torus major radius", major_radius)?;
+                positive("torus minor radius", minor_radius)
+            }
+        }
+    }
+
+    // the material of the shape, None if it uses the one of its object
+    pub fn material(&self) -> Option<MaterialDescription> {
+        match *self {
+            ShapeDescription::Surface { material, .. }
+            | ShapeDescription::Plane { material, .. }
+            | ShapeDescription::Triangle { material, .. }
+            | ShapeDescription::SmoothTriangle { material, .. }
+            | ShapeDescription::Sphere { material, .. }
+            | ShapeDescription::Cylinder { material, .. }
+            | ShapeDescription::Cone { material, .. }
+            | ShapeDescription::Disk { material, .. }
+            | ShapeDescription::Torus { material, .. } => material,
+        }
+    }
+}
+
+impl MaterialDescription {
+    pub fn from_material(m: &Material) -> MaterialDescription {
+        MaterialDescription {
+            brdf: m.brdf,
+            albedo: rgb(&m.albedo),
+            specular: rgb(&m.specular),
+            shininess: m.shininess,
+            roughness: m.roughness,
+            metalness: m.metalness,
+            emission: rgb(&m.emission),
+            reflectivity: m.reflectivity,
+            transparency: m.transparency,
+            ior: m.ior,
+        }
+    }
+
+    pub fn to_material(&self) -> Material {
+        Material {
+            brdf: self.brdf,
+            albedo: color(self.albedo),
+            specular: color(self.specular),
+            shininess: self.shininess,
+            roughness: self.roughness,
+            metalness: self.metalness,
+            emission: color(self.emission),
+            reflectivity: self.reflectivity,
+            transparency: self.transparency,
+            ior: self.ior,
+        }
+    }
+}
+
+impl Default for MaterialDescription {
+    fn default() -> MaterialDescription {
+        MaterialDescription::from_material(&Material::default())
+    }
+}
+
+impl LightDescription {
+    // None for area lights on unbounded or flat surfaces, to_light couldn't make them again
+    pub fn from_light(light: &Light) -> Option<LightDescription> {
+        match *light {
+            Light::Point { position, color, intensity } => Some(LightDescription::Point { position: vec3(&position), color: rgb(&color), intensity }),
+            Light::Directional { direction, color, intensity } => Some(LightDescription::Directional { direction: vec3(&direction), color: rgb(&color), intensity }),
+            Light::Spot { position, direction, color, intensity, inner_angle, outer_angle } => Some(LightDescription::Spot {
+                position: vec3(&position),
+                direction: vec3(&direction),
+                color: rgb(&color),
+                intensity,
+                inner_angle: as_degrees(inner_angle),
+                outer_angle: as_degrees(outer_angle),
+            }),
+            Light::Area { surface, color, intensity, samples } => match (surface.v, surface.w, surface.max_v, surface.max_w) {
+                (Some(v), Some(w), Some(v_range), Some(w_range)) if rectangle_area(&v, &w, v_range, w_range) > 0.0 => Some(LightDescription::Area {
+                    point: vec3(&surface.point),
+                    v: vec3(&v),
+                    w: vec3(&w),
+                    v_range,
+                    w_range,
+                    normal: vec3(&surface.normal),
+                    color: rgb(&color),
+                    intensity,
+                    samples,
+                }),
+                _ => None,
+            },
+        }
+    }
+
+    // fails for area lights without any area, their light would be divided by 0
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            LightDescription::Area { v, w, v_range, w_range, normal, .. } => {
+                if rectangle_area(&vector(v), &vector(w), v_range, w_range) > 0.0 {
+                    non_zero("area light normal", normal)
+                } else {
+                    Err(String::from("area light without any area"))
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // the description has to be valid, see validate
+    pub fn to_light(&self) -> Light {
+        match *self {
+            LightDescription::Point { position, color: c, intensity } => Light::point(vector(position), color(c), intensity),
+            LightDescription::Directional { direction, color: c, intensity } => Light::directional(vector(direction), color(c), intensity),
+            LightDescription::Spot { position, direction, color: c, intensity, inner_angle, outer_angle } => {
+                Light::spot(vector(position), vector(direction), color(c), intensity, as_radians(inner_angle), as_radians(outer_angle))
+            }
+            LightDescription::Area { point, v, w, v_range, w_range, normal, color: c, intensity, samples } => {
+                let surface = Surface::new_vw(vector(point), vector(v), vector(w), v_range, w_range, unit(normal));
+                Light::area(surface, color(c), intensity, samples).expect("surfaces made from v and w are bounded")
+            }
+        }
+    }
+}
+
+impl CameraDescription {
+    pub fn from_camera(camera: &Camera) -> CameraDescription {
+        let view = camera.view_line();
+        let (up, right) = camera.view_axes();
+        CameraDescription {
+            position: vec3(&view.point),
+            direction: vec3(&view.direction),
+            up: vec3(&up),
+            right: vec3(&right),
+            size: (camera.render_width, camera.render_height),
+            projection: match camera.projection {
+                Projection::Orthographic { width, height } => ProjectionDescription::Orthographic { width, height },
+                Projection::Perspective { fov_y, .. } => ProjectionDescription::Perspective { fov: as_degrees(fov_y) },
+            },
+            backface_culling: camera.backface_culling,
+            samples: camera.samples,
+            pattern: camera.pattern,
+            filter: camera.filter,
+        }
+    }
+
+    pub fn to_camera(&self) -> Camera {
+        let (width, height) = (self.size.0.max(1), self.size.1.max(1));
+        let mut camera = Camera::new(vector(self.position), vector(self.direction), width, height, vector(self.up), vector(self.right));
+        camera.projection = match self.projection {
+            ProjectionDescription::Orthographic { width, height } => Projection::Orthographic { width, height },
+            ProjectionDescription::Perspective { fov } => Projection::Perspective { fov_y: as_radians(fov), aspect: width as f64 / height as f64 },
+        };
+        camera.backface_culling = self.backface_culling;
+        camera.samples = self.samples.max(1);
+        camera.pattern = self.pattern;
+        camera.filter = self.filter;
+        camera
+    }
+}
+
+impl Default for CameraDescription {
+    // the camera of the viewer, 60 x 60 pixels looking at 60 x 60 units around (0, 0, 0) from +z
+    fn default() -> CameraDescription {
+        CameraDescription {
+            position: (0.0, 0.0, 50.0),
+            direction: (0.0, 0.0, -1.0),
+            up: (0.0, 1.0, 0.0),
+            right: (1.0, 0.0, 0.0),
+            size: (60, 60),
+            projection: ProjectionDescription::Orthographic { width: 60.0, height: 60.0 },
+            backface_culling: false,
+            samples: 1,
+            pattern: SamplePattern::Regular,
+            filter: Filter::Box,
+        }
+    }
+}
+
+fn vector((x, y, z): Vec3) -> Vector {
+    Vector::new(x, y, z)
+}
+
+// vectors that are normalized already are kept as they are, normalizing them again could change the last digits
+fn unit(v: Vec3) -> Vector {
+    let mut v = vector(v);
+    if (v.length_squared() - 1.0).abs() > 1e-12 {
+        v.normalize();
+    }
+    v
+}
+
+// size of the rectangle spanned by v and w over the ranges
+fn rectangle_area(v: &Vector, w: &Vector, v_range: (f64, f64), w_range: (f64, f64)) -> f64 {
+    v.cross(w).length() * (v_range.1 - v_range.0).abs() * (w_range.1 - w_range.0).abs()
+}
+
+fn non_zero(name: &str, v: Vec3) -> Result<(), String> {
+    if vector(v).length() > 0.0 {
+        Ok(())
+    } else {
+        Err(format!("{} has length 0", name))
+    }
+}
+
+fn positive(name: &str, value: f64) -> Result<(), String> {
+    if value > 0.0 {
+        Ok(())
+    } else {
+        Err(format!("{} has to be positive, got {}", name, value))
+    }
+}
+
+fn vec3(v: &Vector) -> Vec3 {
+    (v.x, v.y, v.z)
+}
+
+fn color((r, g, b): Vec3) -> Color {
+    Color::new(r, g, b)
+}
+
+fn rgb(c: &Color) -> Vec3 {
+    (c.r, c.g, c.b)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::line::Line;
+
+    #[test]
+    fn parse_test() {
+        let file = SceneFile::parse(r#"
+            // comments are allowed
+            (
+                background: (0, 0, 1),
+                objects: [
+                    (
+                        name: "ball",
+                        transform: (translation: (0, 5, 0), axis: (0, 0, 1), degrees: 90, scale: (2, 1, 1)),
+                        material: (albedo: (1, 0, 0)),
+                        shapes: [
+                            Sphere(center: (0, 0, 0), radius: 1),
+                            Plane(point: (0, -1, 0), normal: (0, 2, 0), material: Some((brdf: CookTorrance, roughness: 0.3))),
+                        ],
+                    ),
+                ],
+                lights: [Spot(position: (0, 0, 10), direction: (0, 0, -1), color: (1, 1, 1), intensity: 100, inner_angle: 10, outer_angle: 20)],
+                cameras: [(size: (30, 20), projection: Perspective(fov: 60))],
+            )
+        "#).unwrap();
+        let mut scene = file.to_scene();
+        assert_eq!(scene.background, Color::new(0.0, 0.0, 1.0));
+        assert_eq!(scene.lights.len(), 1);
+
+        let ball = scene.object("ball").unwrap();
+        assert_eq!(ball.shapes.len(), 2);
+        assert_eq!(ball.material(0).albedo, Color::new(1.0, 0.0, 0.0));
+        // left out fields are defaults, not the ones of the object
+        assert_eq!(ball.material(1).brdf, Brdf::CookTorrance);
+        assert_eq!(ball.material(1).albedo, Color::white());
+
+        // stretched along x, then turned so that is y, then moved up
+        let bvh = scene.bvh();
+        let hit = scene.closest_hit(&bvh, &Line::new(Vector::new(0.0, 20.0, 0.0), Vector::new(0.0, -1.0, 0.0))).unwrap();
+        assert!((hit.point - Vector::new(0.0, 7.0, 0.0)).length() < 1e-9);
+
+        let mut camera = scene.cameras.remove(0);
+        assert_eq!(camera.projection, Projection::Perspective { fov_y: as_radians(60.0), aspect: 1.5 });
+        assert_eq!(camera.render(&scene, &bvh).len(), 30 * 20);
+    }
+
+    #[test]
+    fn axis_test() {
+        // axes and normals don't have to be normalized in the file
+        let file = SceneFile::parse("(objects: [(shapes: [
+            Cylinder(base: (0, 0, 0), axis: (0, 0, 2), radius: 1, height: 3, capped: true),
+            Torus(center: (10, 0, 0), axis: (0, 0, 5), major_radius: 3, minor_radius: 1),
+        ])])").unwrap();
+        let object = file.objects[0].to_object();
+        let line = Line::new(Vector::new(0.0, 0.0, 10.0), Vector::new(0.0, 0.0, -1.0));
+        assert_eq!(object.shapes[0].intersect(&line), Some(7.0));
+        let line = Line::new(Vector::new(13.0, 0.0, 10.0), Vector::new(0.0, 0.0, -1.0));
+        assert!((object.shapes[1].intersect(&line).unwrap() - 9.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn round_trip_test() {
+        let mut scene = Scene::from_object("showcase", Object::showcase());
+        let mut q = Quaternion::identity();
+        q.rotate(as_radians(30.0), Vector::new(1.0, 0.0, 0.0));
+        scene.object_mut("showcase").unwrap().rotate(&q);
+        scene.add_object("cube", Object::cube(3.0));
+        scene.object_mut("cube").unwrap().translate(&Vector::new(0.0, 0.0, 20.0));
+        scene.add_light(Light::point(Vector::new(0.0, 30.0, 60.0), Color::white(), 20000.0));
+        let mut camera = Camera::new_perspective(Vector::new(0.0, 0.0, 80.0), Vector::new(0.0, 0.0, -1.0), 40, 30, Vector::new(0.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0), as_radians(50.0));
+        camera.samples = 4;
+        camera.filter = Filter::Tent { radius: 1.0 };
+        scene.add_camera(camera);
+
+        let text = SceneFile::from_scene(&scene).unwrap().to_ron().unwrap();
+        let loaded = SceneFile::parse(&text).unwrap();
+        assert_eq!(loaded, SceneFile::from_scene(&loaded.to_scene()).unwrap());
+
+        // the loaded scene looks the same through its camera
+        let loaded = loaded.to_scene();
+        assert_eq!(loaded.objects.len(), 2);
+        let mut before = scene.cameras[0].clone();
+        let mut after = loaded.cameras[0].clone();
+        assert_eq!(after.samples, 4);
+        let (a, b) = (before.render(&scene, &scene.bvh()), after.render(&loaded, &loaded.bvh()));
+        assert!(a.iter().any(|hit| hit.is_some()));
+        for (a, b) in a.iter().zip(b.iter()) {
+            assert_eq!(a.hit().map(|hit| (hit.object_index, hit.shape_index)), b.hit().map(|hit| (hit.object_index, hit.shape_index)));
+            assert!((a.t().unwrap_or(0.0) - b.t().unwrap_or(0.0)).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn smooth_triangle_test() {
+        let text = "(objects: [(shapes: [SmoothTriangle(a: (0, 0, 0), b: (1, 0, 0), c: (0, 1, 0), normals: ((0, 0, 2), (1, 0, 1), (0, 1, 1)))])])";
+        let file = SceneFile::parse(text).unwrap();
+        let object = file.objects[0].to_object();
+        let shape: &dyn Any = object.shapes[0].as_ref();
+        let t = shape.downcast_ref::<SmoothTriangle>().unwrap();
+        assert_eq!(t.normals[0], Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(t.normals[1], Vector::new(1.0, 0.0, 1.0) * (1.0 / 2.0f64.sqrt()));
+        assert_eq!(ObjectDescription::from_object("smooth", &object).unwrap().to_object().shapes.len(), 1);
+    }
+
+    #[test]
+    fn errors_test() {
+        assert!(SceneFile::parse("(objects: [(shapes: [Cube(size: 1)])])").is_err());
+        assert!(SceneFile::parse("(background: (1, 2))").is_err());
+        assert_eq!(SceneFile::parse("()").unwrap(), SceneFile::default());
+        assert!(SceneFile::parse("(cameras: [(filter: Tent(radius: 0))])").is_err());
+        assert!(SceneFile::parse("(cameras: [(filter: Gaussian(radius: 2, sigma: -1))])").is_err());
+        assert!(SceneFile::parse("(cameras: [(filter: Gaussian(radius: 2, sigma: 0.5))])").is_ok());
+        assert!(SceneFile::parse("(objects: [(shapes: [Torus(center: (0, 0, 0), axis: (0, 0, 0), major_radius: 3, minor_radius: 1)])])").is_err());
+        assert!(SceneFile::parse("(objects: [(shapes: [Cylinder(base: (0, 0, 0), axis: (0, 1, 0), radius: 1, height: 0, capped: true)])])").is_err());
+        assert!(SceneFile::parse("(objects: [(shapes: [Sphere(center: (0, 0, 0), radius: -1)])])").is_err());
+
+        // area lights without any area would be lost on loading
+        let light = "Area(point: (0, 10, 0), v: (1, 0, 0), w: (2, 0, 0), v_range: (-1, 1), w_range: (-1, 1), normal: (0, -1, 0), color: (1, 1, 1), intensity: 1, samples: 1)";
+        assert!(SceneFile::parse(&format!("(lights: [{}])", light)).is_err());
+        assert_eq!(SceneFile::parse(&format!("(lights: [{}])", light.replace("w: (2, 0, 0)", "w: (0, 0, 1)"))).unwrap().to_scene().lights.len(), 1);
+
+        // shapes from outside the library can't be written down
+        struct Point;
+        impl Shape for Point {
+            fn intersect(&self, _line: &Line) -> Option<f64> {
+                None
+            }
+            fn normal_at(&self, _point: &Vector) -> Vector {
+                Vector::new(0.0, 0.0, 1.0)
+            }
+            fn bounds(&self) -> crate::aabb::Aabb {
+                crate::aabb::Aabb::empty()
+            }
+            fn rotate(&mut self, _q: &Quaternion) {}
+            fn scale(&mut self, _s: &f64) {}
+        }
+        let scene = Scene::from_object("custom", Object::from_shapes(vec![Box::new(Point)]));
+        assert!(SceneFile::from_scene(&scene).is_err());
+
+        // neither can area lights on an infinite plane, they would be lost on loading
+        let mut scene = Scene::new();
+        let surface = Surface::new_normal(Vector::new(0.0, 10.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        scene.lights.push(Light::Area { surface, color: Color::white(), intensity: 1.0, samples: 1 });
+        assert!(SceneFile::from_scene(&scene).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use std::any::Any;
+
+use crate::aabb::Aabb;
+use crate::line::Line;
+use crate::quaternion::Quaternion;
+use crate::triangle::Triangle;
+use crate::vector::Vector;
+
+// hits closer to the start of the line than this are ignored by Shape::intersect, so a line starting on the surface
+// doesn't hit it again
+pub const MIN_T: f64 = 1e-9;
+
+// anything that can be put into an Object and hit by a ray.
+// Surfaces, triangles and the analytic primitives implement it, custom shapes can too.
+// Any lets the scene file find out which shape it is
+pub trait Shape: Send + Sync + Any {
+    // returns the smallest t > MIN_T for which line.point_on_line(t) lies on the shape, None if the line misses it
+    fn intersect(&self, line: &Line) -> Option<f64>;
+
+    // outward facing normal at a point on the shape
+    fn normal_at(&self, point: &Vector) -> Vector;
+
+    // parametric surface coordinates of a point on the shape
+    fn uv_at(&self, _point: &Vector) -> (f64, f64) {
+        (0.0, 0.0)
+    }
+
+    // axis aligned box containing the whole shape. Unbounded shapes return infinite corners
+    fn bounds(&self) -> Aabb;
+
+    // rotates the shape around (0, 0, 0)
+    fn rotate(&mut self, q: &Quaternion);
+
+    // scales the shape relative to (0, 0, 0)
+    fn scale(&mut self, s: &f64);
+
+    // triangles approximating the shape, curves are split into segments pieces per full turn.
+    // Their front faces point outwards. None for shapes that can't be made of triangles, like infinite planes
+    fn triangles(&self, _segments: usize) -> Option<Vec<Triangle>> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::f64::consts::PI;
+
+    use crate::cone::Cone;
+    use crate::cylinder::Cylinder;
+    use crate::disk::Disk;
+    use crate::object::Object;
+    use crate::sphere::Sphere;
+    use crate::surface::Surface;
+    use crate::torus::Torus;
+
+    // shape defined outside of the library, the plane z = 0 facing +z
+    struct Floor;
+
+    impl Shape for Floor {
+        fn intersect(&self, line: &Line) -> Option<f64> {
+            let t = -line.point.z / line.direction.z;
+            if t > 0.0 { Some(t) } else { None }
+        }
+
+        fn normal_at(&self, _point: &Vector) -> Vector {
+            Vector::new(0.0, 0.0, 1.0)
+        }
+
+        fn bounds(&self) -> Aabb {
+            Aabb::new(Vector::new(f64::NEG_INFINITY, f64::NEG_INFINITY, 0.0), Vector::new(f64::INFINITY, f64::INFINITY, 0.0))
+        }
+
+        fn rotate(&mut self, _q: &Quaternion) {}
+
+        fn scale(&mut self, _s: &f64) {}
+    }
+
+    #[test]
+    fn mixed_object_test() {
+        let mut object = Object::from_shapes(vec![Box::new(Floor)]);
+        object.add_shape(Sphere::new(Vector::new(0.0, 0.0, 0.0), 2.0));
+
+        // the sphere is in front of the floor
+        let line = Line::new(Vector::new(0.0, 0.0, 10.0), Vector::new(0.0, 0.0, -1.0));
+        let hit = line.intersection_object(&object, &line.point, &false);
+        assert_eq!(hit.point(), Some(Vector::new(0.0, 0.0, 2.0)));
+        assert_eq!(hit.t(), Some(8.0));
+        assert_eq!(hit.hit().unwrap().shape_index, 1);
+        assert_eq!(hit.front_face(), Some(true));
+
+        // next to the sphere only the floor is hit
+        let line = Line::new(Vector::new(5.0, 0.0, 10.0), Vector::new(0.0, 0.0, -1.0));
+        let hit = line.intersection_object(&object, &line.point, &false);
+        assert_eq!(hit.point(), Some(Vector::new(5.0, 0.0, 0.0)));
+        assert_eq!(hit.hit().unwrap().shape_index, 0);
+
+        // from below the floor is seen from the back
+        let line = Line::new(Vector::new(5.0, 0.0, -10.0), Vector::new(0.0, 0.0, 1.0));
+        let hit = line.intersection_object(&object, &line.point, &false);
+        assert_eq!(hit.front_face(), Some(false));
+        assert_eq!(hit.normal(), Some(Vector::new(0.0, 0.0, -1.0)));
+        assert!(line.intersection_object(&object, &line.point, &true).is_none());
+    }
+
+    #[test]
+    fn cube_culling_test() {
+        // the cube faces are front faces from the outside, so backface culling keeps the near face when looking at
+        // the cube and drops every face seen from the inside
+        let cube = Object::cube(1.0);
+        for direction in [Vector::new(1.0, 0.0, 0.0), Vector::new(0.0, -1.0, 0.0), Vector::new(0.0, 0.0, 1.0)] {
+            let outside = Line::new(direction * -5.0, direction);
+            assert!(outside.intersection_object(&cube, &outside.point, &true).is_some());
+            let inside = Line::new(Vector::new(0.0, 0.0, 0.0), direction);
+            assert!(inside.intersection_object(&cube, &inside.point, &false).is_some());
+            assert!(inside.intersection_object(&cube, &inside.point, &true).is_none());
+        }
+    }
+
+    #[test]
+    fn bounds_test() {
+        let sphere = Sphere::new(Vector::new(1.0, 2.0, 3.0), 1.0);
+        assert_eq!(sphere.bounds(), Aabb::new(Vector::new(0.0, 1.0, 2.0), Vector::new(2.0, 3.0, 4.0)));
+
+        let disk = Disk::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0), 2.0);
+        assert_eq!(disk.bounds(), Aabb::new(Vector::new(-2.0, 0.0, -2.0), Vector::new(2.0, 0.0, 2.0)));
+
+        let torus = Torus::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0), 3.0, 1.0);
+        assert_eq!(torus.bounds(), Aabb::new(Vector::new(-4.0, -4.0, -1.0), Vector::new(4.0, 4.0, 1.0)));
+
+        let cube = Object::cube(1.0);
+        let unit = Aabb::new(Vector::new(-1.0, -1.0, -1.0), Vector::new(1.0, 1.0, 1.0));
+        for shape in &cube.shapes {
+            let bounds = shape.bounds();
+            assert!(unit.contains(&bounds.min) && unit.contains(&bounds.max));
+        }
+        assert_eq!(cube.bounds(), unit);
+    }
+
+    #[test]
+    fn triangles_test() {
+        let mut axis = Vector::new(1.0, 2.0, 0.5);
+        axis.normalize();
+        let center = Vector::new(1.0, -2.0, 3.0);
+        let shapes: Vec<(Box<dyn Shape>, f64)> = vec![
+            (Box::new(Sphere::new(center, 2.0)), 4.0 * PI * 4.0),
+            (Box::new(Cylinder::new(center, axis, 1.0, 3.0, true)), 2.0 * PI * 3.0 + 2.0 * PI),
+            (Box::new(Cone::new(center, axis, 3.0, 4.0, true)), PI * 3.0 * 5.0 + PI * 9.0),
+            (Box::new(Disk::new(center, axis, 2.0)), PI * 4.0),
+            (Box::new(Torus::new(center, axis, 3.0, 1.0)), 4.0 * PI * PI * 3.0),
+            (Box::new(Surface::new_vw(center, Vector::new(0.0, 0.0, 1.0), Vector::new(0.0, 1.0, 0.0), (-1.0, 1.0), (0.0, 2.0), Vector::new(1.0, 0.0, 0.0))), 4.0),
+        ];
+        for (shape, area) in &shapes {
+            let triangles = shape.triangles(256).unwrap();
+            let total: f64 = triangles.iter().map(|t| t.area()).sum();
+            assert!((total - area).abs() < area * 1e-3, "{} != {}", total, area);
+            // every triangle faces the way the shape does
+            for t in &triangles {
+                let centroid = t.point_from_barycentric((1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0));
+                assert!(t.normal().dot(&shape.normal_at(&centroid)) > 0.9);
+            }
+        }
+        assert!(Surface::new_normal(center, axis).triangles(16).is_none());
+        assert!(Floor.triangles(16).is_none());
+        assert_eq!(Sphere::new(center, 1.0).triangles(8).unwrap().len(), 8 * 4 * 2 - 2 * 8);
+    }
+}
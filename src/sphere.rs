@@ -0,0 +1,109 @@
+use std::f64::consts::PI;
+
+use crate::aabb::Aabb;
+use crate::disk::circle_points;
+use crate::line::Line;
+use crate::math::solve_quadratic;
+use crate::quaternion::Quaternion;
+use crate::shape::{Shape, MIN_T};
+use crate::triangle::{triangulate_grid, Triangle};
+use crate::vector::Vector;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Sphere {
+    pub center: Vector,
+    pub radius: f64,
+}
+
+impl Sphere {
+    pub fn new(center: Vector, radius: f64) -> Sphere {
+        Sphere { center, radius }
+    }
+}
+
+impl Shape for Sphere {
+    // returns the smallest t > 0 for which line.point_on_line(t) lies on the sphere
+    fn intersect(&self, line: &Line) -> Option<f64> {
+        let oc = line.point - self.center;
+        let a = line.direction.length_squared();
+        let b = 2.0 * oc.dot(&line.direction);
+        let c = oc.length_squared() - self.radius * self.radius;
+        solve_quadratic([c, b, a]).into_iter().find(|t| *t > MIN_T)
+    }
+
+    // outward facing normal at a point on the sphere
+    fn normal_at(&self, point: &Vector) -> Vector {
+        let mut n = *point - self.center;
+        n.normalize();
+        n
+    }
+
+    // u goes around the y axis, v goes from the bottom (0) to the top (1) of the sphere
+    fn uv_at(&self, point: &Vector) -> (f64, f64) {
+        let d = self.normal_at(point);
+        let u = 0.5 + d.z.atan2(d.x) / (2.0 * PI);
+        let v = 0.5 + d.y.clamp(-1.0, 1.0).asin() / PI;
+        (u, v)
+    }
+
+    fn bounds(&self) -> Aabb {
+        let r = Vector::new(self.radius, self.radius, self.radius);
+        Aabb::new(self.center - r, self.center + r)
+    }
+
+    fn rotate(&mut self, q: &Quaternion) {
+        self.center.rotate_by_quaternion(q);
+    }
+
+    fn scale(&mut self, s: &f64) {
+        self.center *= *s;
+        self.radius *= s.abs();
+    }
+
+    // segments columns around the y axis and segments / 2 rows from the bottom to the top
+    fn triangles(&self, segments: usize) -> Option<Vec<Triangle>> {
+        let segments = segments.max(3);
+        let rows = (segments / 2).max(2);
+        let y = Vector::new(0.0, 1.0, 0.0);
+        let grid: Vec<Vec<Vector>> = (0..=rows)
+            .map(|i| {
+                if i == 0 || i == rows {
+                    // the poles are single points, so the triangles touching them are left out
+                    let pole = self.center + y * (if i == 0 { -self.radius } else { self.radius });
+                    return vec![pole; segments + 1];
+                }
+                let angle = PI * (i as f64 / rows as f64 - 0.5);
+                circle_points(&(self.center + y * (self.radius * angle.sin())), &y, self.radius * angle.cos(), segments)
+            })
+            .collect();
+        Some(triangulate_grid(&grid))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn intersect_test() {
+        let sphere = Sphere::new(Vector::new(0.0, 0.0, 0.0), 2.0);
+        let line = Line::new(Vector::new(0.0, 0.0, 10.0), Vector::new(0.0, 0.0, -1.0));
+        let t = sphere.intersect(&line).unwrap();
+        assert_eq!(t, 8.0);
+        assert_eq!(sphere.normal_at(&line.point_on_line(&t)), Vector::new(0.0, 0.0, 1.0));
+        // from the inside only the far side is hit
+        let inside = Line::new(Vector::new(0.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        assert_eq!(sphere.intersect(&inside), Some(2.0));
+        let miss = Line::new(Vector::new(0.0, 3.0, 10.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(sphere.intersect(&miss).is_none());
+    }
+
+    #[test]
+    fn uv_test() {
+        let sphere = Sphere::new(Vector::new(1.0, 1.0, 1.0), 1.0);
+        let (_, v) = sphere.uv_at(&Vector::new(1.0, 2.0, 1.0));
+        assert_eq!(v, 1.0);
+        let (u, v) = sphere.uv_at(&Vector::new(2.0, 1.0, 1.0));
+        assert_eq!((u, v), (0.5, 0.5));
+    }
+}
@@ -0,0 +1,256 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::mesh::Mesh;
+use crate::vector::Vector;
+
+// STL, the format of CAD programs and 3D printers, see: https://en.wikipedia.org/wiki/STL_(file_format)
+// It's a list of triangles with a normal each, vertices aren't shared and there are no materials
+
+// reads binary or ASCII STL. The corners of the triangles are welded into shared vertices and triangles
+// without area are left out. The facet normals are skipped, the front faces come from the order of the corners
+pub fn parse(bytes: &[u8]) -> Result<Mesh, String> {
+    let mut mesh = if binary_size(bytes) == Some(bytes.len()) {
+        parse_binary(bytes)
+    } else if bytes.trim_ascii_start().starts_with(b"solid") {
+        let text = std::str::from_utf8(bytes).map_err(|_| String::from("ASCII STL that isn't valid text"))?;
+        parse_ascii(text)?
+    } else {
+        return Err(match binary_size(bytes) {
+            Some(size) => format!("binary STL with {} triangles needs {} bytes, got {}", (size - 84) / 50, size, bytes.len()),
+            None => format!("too short for STL, got {} bytes", bytes.len()),
+        });
+    };
+    mesh.weld(0.0);
+    mesh.remove_degenerate();
+    Ok(mesh)
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Mesh> {
+    let path = path.as_ref();
+    let bytes = fs::read(path)?;
+    parse(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+}
+
+// size of a binary STL file with the triangle count in its header
+fn binary_size(bytes: &[u8]) -> Option<usize> {
+    let count = u32::from_le_bytes(bytes.get(80..84)?.try_into().ok()?);
+    Some(84 + 50 * count as usize)
+}
+
+fn parse_binary(bytes: &[u8]) -> Mesh {
+    let mut mesh = Mesh::new();
+    let float = |offset: usize| f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as f64;
+    for record in (84..bytes.len()).step_by(50) {
+        let first = mesh.positions.len();
+        // the normal comes first
+        for corner in 1..4 {
+            let offset = record + corner * 12;
+            mesh.positions.push(Vector::new(float(offset), float(offset + 4), float(offset + 8)));
+        }
+        mesh.indices.push([first, first + 1, first + 2]);
+    }
+    mesh
+}
+
+// solid, then for every triangle: facet normal, outer loop, three vertex lines, endloop and endfacet.
+// Facets with more corners are split into triangles
+fn parse_ascii(text: &str) -> Result<Mesh, String> {
+    let mut mesh = Mesh::new();
+    // corners of the facet that is being read, None between facets
+    let mut facet: Option<Vec<usize>> = None;
+    let mut solid = false;
+    for (number, line) in text.lines().enumerate() {
+        let error = |message: String| format!("line {}: {}", number + 1, message);
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some(&keyword) = tokens.first() else { continue };
+        match (keyword, facet.as_mut()) {
+            ("solid", None) if !solid => solid = true,
+            ("endsolid", None) if solid => solid = false,
+            ("facet", None) if solid => {
+                if tokens.get(1) != Some(&"normal") {
+                    return Err(error(String::from("expected facet normal")));
+                }
+                parse_vector(&tokens[2..]).map_err(error)?;
+                facet = Some(Vec::new());
+            }
+            ("outer", Some(_)) if tokens.get(1) == Some(&"loop") => {}
+            ("vertex", Some(corners)) => {
+                corners.push(mesh.positions.len());
+                mesh.positions.push(parse_vector(&tokens[1..]).map_err(error)?);
+            }
+            ("endloop", Some(_)) => {}
+            ("endfacet", Some(corners)) => {
+                if corners.len() < 3 {
+                    return Err(error(format!("facet needs at least 3 vertices, got {}", corners.len())));
+                }
+                for i in 1..corners.len() - 1 {
+                    mesh.indices.push([corners[0], corners[i], corners[i + 1]]);
+                }
+                facet = None;
+            }
+            _ => return Err(error(format!("unexpected {}", keyword))),
+        }
+    }
+    if facet.is_some() || solid {
+        return Err(String::from("unexpected end of file, missing endfacet or endsolid"));
+    }
+    Ok(mesh)
+}
+
+fn parse_vector(values: &[&str]) -> Result<Vector, String> {
+    if values.len() != 3 {
+        return Err(format!("expected 3 numbers, got {}", values.len()));
+    }
+    let mut v = [0.0; 3];
+    for (number, value) in v.iter_mut().zip(values) {
+        *number = value.parse::<f64>().map_err(|_| format!("invalid number: {}", value))?;
+    }
+    Ok(Vector::new(v[0], v[1], v[2]))
+}
+
+// facet normal of a triangle, (0, 0, 0) for triangles without area like STL asks for
+fn facet_normal(mesh: &Mesh, index: usize) -> Vector {
+    let triangle = mesh.triangle(index);
+    if triangle.area() > 0.0 {
+        triangle.normal()
+    } else {
+        Vector::new(0.0, 0.0, 0.0)
+    }
+}
+
+pub fn write_ascii<W: Write>(mesh: &Mesh, name: &str, writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "solid {}", name)?;
+    for (index, triangle) in mesh.indices.iter().enumerate() {
+        let n = facet_normal(mesh, index);
+        writeln!(writer, "  facet normal {} {} {}", n.x, n.y, n.z)?;
+        writeln!(writer, "    outer loop")?;
+        for &i in triangle {
+            let p = mesh.positions[i];
+            writeln!(writer, "      vertex {} {} {}", p.x, p.y, p.z)?;
+        }
+        writeln!(writer, "    endloop")?;
+        writeln!(writer, "  endfacet")?;
+    }
+    writeln!(writer, "endsolid {}", name)
+}
+
+// 80 byte header, the number of triangles and 50 bytes per triangle: normal and vertices as 32 bit floats
+// and a 16 bit attribute that is left 0. Everything is little-endian
+pub fn write_binary<W: Write>(mesh: &Mesh, writer: &mut W) -> io::Result<()> {
+    let count = u32::try_from(mesh.triangle_count()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many triangles for STL"))?;
+    // the header must not start with "solid", readers would take the file for ASCII STL
+    let mut header = [0u8; 80];
+    let text = b"binary STL";
+    header[..text.len()].copy_from_slice(text);
+    writer.write_all(&header)?;
+    writer.write_all(&count.to_le_bytes())?;
+    for (index, triangle) in mesh.indices.iter().enumerate() {
+        let n = facet_normal(mesh, index);
+        let vertices = triangle.map(|i| mesh.positions[i]);
+        for v in std::iter::once(n).chain(vertices) {
+            for value in [v.x, v.y, v.z] {
+                writer.write_all(&(value as f32).to_le_bytes())?;
+            }
+        }
+        writer.write_all(&0u16.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::object::Object;
+
+    #[test]
+    fn ascii_test() {
+        let mesh = Object::cube(1.0).to_mesh(16).unwrap();
+        let mut out = Vec::new();
+        write_ascii(&mesh, "cube", &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("solid cube\n  facet normal 0 0 1\n    outer loop\n      vertex "));
+        assert!(text.ends_with("endsolid cube\n"));
+        assert_eq!(text.matches("facet normal").count(), 12);
+        assert_eq!(text.matches("vertex").count(), 36);
+    }
+
+    #[test]
+    fn binary_test() {
+        let mut mesh = Object::cube(1.0).to_mesh(16).unwrap();
+        // a triangle without area
+        mesh.indices.push([0, 0, 1]);
+        let mut out = Vec::new();
+        write_binary(&mesh, &mut out).unwrap();
+        assert_eq!(out.len(), 84 + 13 * 50);
+        assert!(!out.starts_with(b"solid"));
+        assert_eq!(u32::from_le_bytes(out[80..84].try_into().unwrap()), 13);
+
+        let float = |offset: usize| f32::from_le_bytes(out[offset..offset + 4].try_into().unwrap());
+        // normal and first vertex of the first triangle, the front of the cube
+        assert_eq!([float(84), float(88), float(92)], [0.0, 0.0, 1.0]);
+        let a = mesh.positions[mesh.indices[0][0]];
+        assert_eq!([float(96), float(100), float(104)], [a.x as f32, a.y as f32, a.z as f32]);
+        let last = 84 + 12 * 50;
+        assert_eq!([float(last), float(last + 4), float(last + 8)], [0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn read_test() {
+        let cube = Object::cube(1.0).to_mesh(16).unwrap();
+        let mut binary = Vec::new();
+        write_binary(&cube, &mut binary).unwrap();
+        let mut ascii = Vec::new();
+        write_ascii(&cube, "cube", &mut ascii).unwrap();
+
+        for bytes in [binary, ascii] {
+            let mesh = parse(&bytes).unwrap();
+            assert!(mesh.validate().is_ok());
+            // the 36 corners are welded into the 8 corners of the cube
+            assert_eq!(mesh.vertex_count(), 8);
+            assert_eq!(mesh.triangle_count(), 12);
+            for index in 0..12 {
+                assert_eq!(mesh.triangle(index).normal(), cube.triangle(index).normal());
+            }
+        }
+    }
+
+    #[test]
+    fn read_errors_test() {
+        let ascii = "solid test
+            facet normal 0 0 1
+              outer loop
+                vertex 0 0 0
+                vertex 1 0 0
+                vertex 1 1 0
+                vertex 0 1 0
+              endloop
+            endfacet
+            facet normal 0 0 0
+              outer loop
+                vertex 0 0 0
+                vertex 1 0 0
+                vertex 2 0 0
+              endloop
+            endfacet
+            endsolid test";
+        // the quad is split in two, the triangle on a line is dropped together with the corner only it used
+        let mesh = parse(ascii.as_bytes()).unwrap();
+        assert_eq!(mesh.triangle_count(), 2);
+        assert_eq!(mesh.vertex_count(), 4);
+
+        let error = |text: &str| parse(text.as_bytes()).unwrap_err();
+        assert_eq!(error(&ascii.replace("vertex 1 1 0", "vertex 1 x 0")), "line 6: invalid number: x");
+        assert_eq!(error(&ascii.replace("vertex 1 1 0", "vertex 1 0")), "line 6: expected 3 numbers, got 2");
+        assert_eq!(error(&ascii.replace("vertex 1 0 0\n                vertex 2 0 0", "vertex 1 0 0")), "line 15: facet needs at least 3 vertices, got 2");
+        assert_eq!(error(&ascii.replace("facet normal 0 0 1", "")), "line 3: unexpected outer");
+        assert_eq!(error(&ascii.replace("endsolid test", "")), "unexpected end of file, missing endfacet or endsolid");
+        assert_eq!(error("hello"), "too short for STL, got 5 bytes");
+
+        let mut binary = Vec::new();
+        write_binary(&Object::cube(1.0).to_mesh(16).unwrap(), &mut binary).unwrap();
+        binary.pop();
+        assert_eq!(parse(&binary).unwrap_err(), "binary STL with 12 triangles needs 684 bytes, got 683");
+    }
+}
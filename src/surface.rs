@@ -1,8 +1,16 @@
+use float_cmp::{approx_eq, F64Margin};
+use crate::aabb::Aabb;
+use crate::line::Line;
+use crate::mat4::Mat4;
 use crate::point::Point;
 use crate::quaternion::Quaternion;
+use crate::shape::{Shape, MIN_T};
+use crate::triangle::Triangle;
 use crate::vector::Vector;
 
-// Surface is defined by a point and a normal vector
+// Surface is defined by a point and a normal vector.
+// The normal points out of the front face, the side that faces the camera when backface culling is on. For
+// surfaces that close off an object, like the faces of a cube, it has to point out of the object
 #[derive(Debug, Clone, Copy)]
 pub struct Surface {
     pub point: Vector,
@@ -32,11 +40,18 @@ impl Surface {
         v.dot(&self.normal)
     }
 
+    // t and s of a point on the surface, so that point = Q + tv + sw
     pub fn get_t_s_from_point(&self, point: &Vector) -> (f64, f64) {
-        let v = *point - self.point;
-        let t = v.dot(&self.v.unwrap()) / self.v.unwrap().length_squared();
-        let s = v.dot(&self.w.unwrap()) / self.w.unwrap().length_squared();
-        (t, s)
+        let d = *point - self.point;
+        let (v, w) = (self.v.unwrap(), self.w.unwrap());
+        let vw = v.dot(&w);
+        if vw == 0.0 {
+            return (d.dot(&v) / v.length_squared(), d.dot(&w) / w.length_squared());
+        }
+        // v and w aren't perpendicular after a shear or a non-uniform scale, so solve both at once
+        let (vv, ww, dv, dw) = (v.length_squared(), w.length_squared(), d.dot(&v), d.dot(&w));
+        let det = vv * ww - vw * vw;
+        ((dv * ww - dw * vw) / det, (dw * vv - dv * vw) / det)
     }
 
     // return the point on the surface closest to the given point
@@ -74,19 +89,163 @@ impl Surface {
         self.normal.rotate_by_quaternion(&q);
     }
 
+    // scales the surface relative to (0, 0, 0)
     pub fn scale(&mut self, s: &f64) {
-        self.point.x *= s;
-        self.point.y *= s;
-        self.point.z *= s;
+        self.scale_by(&Vector::new(*s, *s, *s));
+    }
+
+    // scales the surface relative to (0, 0, 0) by a different amount along each axis
+    pub fn scale_by(&mut self, s: &Vector) {
+        let mut m = Mat4::identity();
+        m.scale(*s);
+        self.transform(&m);
+    }
+
+    pub fn translate(&mut self, offset: &Vector) {
+        self.point += *offset;
+    }
+
+    // applies an affine transform. v and w are transformed like the edges of the surface, so the bounds
+    // stay the same and v and w can become longer or stop being perpendicular.
+    // The normal is transformed with the inverse transpose and normalized again. A matrix that flattens
+    // the surface leaves the normal as it was
+    pub fn transform(&mut self, m: &Mat4) {
+        self.point = m.transform_point(&self.point);
+        self.v = self.v.map(|v| m.transform_direction(&v));
+        self.w = self.w.map(|w| m.transform_direction(&w));
+        if let Some(normal_matrix) = m.normal_matrix() {
+            self.normal = normal_matrix.transform_direction(&self.normal);
+            self.normal.normalize();
+        }
+    }
+
+    // true if the surface was defined with v and w, so it's a parallelogram and not an infinite plane
+    pub fn is_bounded(&self) -> bool {
+        self.v.is_some() && self.w.is_some() && self.max_v.is_some() && self.max_w.is_some()
+    }
+
+    // corners of the bounded surface, None for infinite planes
+    pub fn corners(&self) -> Option<[Vector; 4]> {
+        match (self.v, self.w, self.max_v, self.max_w) {
+            (Some(v), Some(w), Some((v0, v1)), Some((w0, w1))) => Some([
+                self.point + v * v0 + w * w0,
+                self.point + v * v1 + w * w0,
+                self.point + v * v1 + w * w1,
+                self.point + v * v0 + w * w1,
+            ]),
+            _ => None,
+        }
+    }
+}
+
+impl Shape for Surface {
+    fn intersect(&self, line: &Line) -> Option<f64> {
+        let denominator = self.normal.dot(&line.direction);
+        if approx_eq!(f64, denominator, 0.0, F64Margin::default()) {
+            return None;
+        }
+        let t = (self.point - line.point).dot(&self.normal) / denominator;
+        if t <= MIN_T {
+            return None;
+        }
+        if self.is_bounded() {
+            let (t_v, s_w) = self.get_t_s_from_point(&line.point_on_line(&t));
+            self.point_on_surface(&t_v, &s_w)?;
+        }
+        Some(t)
+    }
+
+    fn normal_at(&self, _point: &Vector) -> Vector {
+        self.normal
+    }
 
-        let (mut v1, mut v2) = self.max_v.unwrap();
-        v1 *= s;
-        v2 *= s;
-        self.max_v = Some((v1, v2));
-        let (mut v1, mut v2) = self.max_w.unwrap();
-        v1 *= s;
-        v2 *= s;
-        self.max_w = Some((v1, v2));
+    // position along v and w, from 0 to 1 between the bounds. Infinite planes have no uv mapping
+    fn uv_at(&self, point: &Vector) -> (f64, f64) {
+        match (self.max_v, self.max_w) {
+            (Some((v0, v1)), Some((w0, w1))) if self.is_bounded() => {
+                let (t, s) = self.get_t_s_from_point(point);
+                ((t - v0) / (v1 - v0), (s - w0) / (w1 - w0))
+            }
+            _ => (0.0, 0.0),
+        }
+    }
+
+    fn bounds(&self) -> Aabb {
+        match self.corners() {
+            Some(corners) => Aabb::from_points(&corners),
+            None => Aabb::infinite(),
+        }
+    }
+
+    fn rotate(&mut self, q: &Quaternion) {
+        Surface::rotate(self, q);
+    }
+
+    fn scale(&mut self, s: &f64) {
+        Surface::scale(self, s);
+    }
+
+    // two triangles facing the side of the normal, None for infinite planes
+    fn triangles(&self, _segments: usize) -> Option<Vec<Triangle>> {
+        let [a, b, c, d] = self.corners()?;
+        if (b - a).cross(&(d - a)).dot(&self.normal) >= 0.0 {
+            Some(vec![Triangle::new(a, b, c), Triangle::new(a, c, d)])
+        } else {
+            Some(vec![Triangle::new(a, c, b), Triangle::new(a, d, c)])
+        }
+    }
+}
 
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn close(a: Vector, b: Vector) -> bool {
+        (a - b).length() < 1e-12
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn scale_test() {
+        let mut plane = Surface::new_normal(Vector::new(0.0, 0.0, 2.0), Vector::new(0.0, 0.0, 1.0));
+        plane.scale(&3.0);
+        assert!(close(plane.point, Vector::new(0.0, 0.0, 6.0)));
+        assert!(close(plane.normal, Vector::new(0.0, 0.0, 1.0)));
+
+        let v = Vector::new(1.0, 0.0, 0.0);
+        let w = Vector::new(0.0, 1.0, 0.0);
+        let mut square = Surface::new_vw(Vector::new(1.0, 0.0, 0.0), v, w, (-1.0, 1.0), (-1.0, 1.0), Vector::new(0.0, 0.0, 1.0));
+        square.scale_by(&Vector::new(2.0, 3.0, 1.0));
+        assert!(close(square.corners().unwrap()[2], Vector::new(4.0, 3.0, 0.0)));
+        assert_eq!(square.uv_at(&Vector::new(4.0, 3.0, 0.0)), (1.0, 1.0));
+        square.translate(&Vector::new(0.0, 0.0, -1.0));
+        assert_eq!(square.intersect(&Line::new(Vector::new(3.9, 2.9, 5.0), Vector::new(0.0, 0.0, -1.0))), Some(6.0));
+        assert_eq!(square.intersect(&Line::new(Vector::new(4.1, 2.9, 5.0), Vector::new(0.0, 0.0, -1.0))), None);
+    }
+
+    #[test]
+    fn transform_test() {
+        // the plane x + y = 0, squashed along x
+        let mut normal = Vector::new(1.0, 1.0, 0.0);
+        normal.normalize();
+        let v = Vector::new(1.0, -1.0, 0.0);
+        let w = Vector::new(0.0, 0.0, 1.0);
+        let mut surface = Surface::new_vw(Vector::new(0.0, 0.0, 0.0), v, w, (-1.0, 1.0), (-1.0, 1.0), normal);
+        let mut m = Mat4::identity();
+        m.scale(Vector::new(0.5, 1.0, 1.0));
+        surface.transform(&m);
+        // now it's 2x + y = 0 with the normal (2, 1, 0), not the scaled normal (0.5, 1, 0)
+        let mut expected = Vector::new(2.0, 1.0, 0.0);
+        expected.normalize();
+        assert!(close(surface.normal, expected));
+        assert!(surface.normal.dot(&surface.v.unwrap()).abs() < 1e-12);
+
+        // a shear makes v and w not perpendicular, points inside still map back to t and s
+        let mut shear = Mat4::identity();
+        shear.m[0][2] = 1.0;
+        surface.transform(&shear);
+        assert!(surface.v.unwrap().dot(&surface.w.unwrap()).abs() > 0.1);
+        let (t, s) = surface.get_t_s_from_point(&surface.point_on_surface(&0.25, &-0.5).unwrap());
+        assert!((t - 0.25).abs() < 1e-12 && (s + 0.5).abs() < 1e-12);
+        assert!(surface.normal.dot(&surface.w.unwrap()).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,196 @@
+use std::f64::consts::PI;
+
+use crate::aabb::Aabb;
+use crate::disk::{circle_points, disk_bounds};
+use crate::line::Line;
+use crate::math::{solve_quadratic, solve_quartic};
+use crate::quaternion::Quaternion;
+use crate::shape::{Shape, MIN_T};
+use crate::triangle::{triangulate_grid, Triangle};
+use crate::vector::Vector;
+
+// ring around axis (which must be normalized), major_radius is the distance from the center to the middle
+// of the tube and minor_radius is the radius of the tube
+#[derive(Debug, Clone, Copy)]
+pub struct Torus {
+    pub center: Vector,
+    pub axis: Vector,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+}
+
+impl Torus {
+    pub fn new(center: Vector, axis: Vector, major_radius: f64, minor_radius: f64) -> Torus {
+        Torus { center, axis, major_radius, minor_radius }
+    }
+
+    // point in the plane of the torus ring closest to the given point
+    fn ring_point(&self, point: &Vector) -> Vector {
+        let q = *point - self.center;
+        let mut in_plane = q - self.axis * q.dot(&self.axis);
+        in_plane.normalize();
+        self.center + in_plane * self.major_radius
+    }
+}
+
+impl Shape for Torus {
+    // returns the smallest t > 0 for which line.point_on_line(t) lies on the torus
+    fn intersect(&self, line: &Line) -> Option<f64> {
+        let length = line.direction.length();
+        let d = line.direction / length;
+        let o = line.point - self.center;
+
+        // the quartic loses precision when the line starts far away, so start it where it enters the bounding sphere
+        let bound = self.major_radius + self.minor_radius;
+        let entry = solve_quadratic([o.length_squared() - bound * bound, 2.0 * o.dot(&d), 1.0]);
+        let (near, far) = match entry.as_slice() {
+            [near, far] => (*near, *far),
+            _ => return None,
+        };
+        if far <= 0.0 {
+            return None;
+        }
+        let shift = near.max(0.0);
+        let o = o + d * shift;
+
+        // the torus in local coordinates, axis along z: (|p|^2 + R^2 - r^2)^2 = 4R^2(x^2 + y^2)
+        let (e1, e2) = self.axis.orthonormal_basis();
+        let o = Vector::new(o.dot(&e1), o.dot(&e2), o.dot(&self.axis));
+        let d = Vector::new(d.dot(&e1), d.dot(&e2), d.dot(&self.axis));
+        let r2 = self.major_radius * self.major_radius;
+        let four_r2 = 4.0 * r2;
+
+        let a = d.length_squared();
+        let b = 2.0 * o.dot(&d);
+        let c = o.length_squared() + r2 - self.minor_radius * self.minor_radius;
+        let coefficients = [
+            c * c - four_r2 * (o.x * o.x + o.y * o.y),
+            2.0 * b * c - 2.0 * four_r2 * (o.x * d.x + o.y * d.y),
+            b * b + 2.0 * a * c - four_r2 * (d.x * d.x + d.y * d.y),
+            2.0 * a * b,
+            a * a,
+        ];
+
+        solve_quartic(coefficients)
+            .into_iter()
+            .map(|s| (s + shift) / length)
+            .find(|t| *t > MIN_T)
+    }
+
+    // outward facing normal at a point on the torus
+    fn normal_at(&self, point: &Vector) -> Vector {
+        let mut n = *point - self.ring_point(point);
+        n.normalize();
+        n
+    }
+
+    // u is the angle around the axis, v is the angle around the tube
+    fn uv_at(&self, point: &Vector) -> (f64, f64) {
+        let (e1, e2) = self.axis.orthonormal_basis();
+        let q = *point - self.center;
+        let h = q.dot(&self.axis);
+        let in_plane = q - self.axis * h;
+        let u = 0.5 + in_plane.dot(&e2).atan2(in_plane.dot(&e1)) / (2.0 * PI);
+        let v = 0.5 + h.atan2(in_plane.length() - self.major_radius) / (2.0 * PI);
+        (u, v)
+    }
+
+    fn bounds(&self) -> Aabb {
+        // the ring of the torus is a disk of the major radius, the tube adds the minor radius in every direction
+        let ring = disk_bounds(&self.center, &self.axis, self.major_radius);
+        let r = Vector::new(self.minor_radius, self.minor_radius, self.minor_radius);
+        Aabb::new(ring.min - r, ring.max + r)
+    }
+
+    fn rotate(&mut self, q: &Quaternion) {
+        self.center.rotate_by_quaternion(q);
+        self.axis.rotate_by_quaternion(q);
+    }
+
+    fn scale(&mut self, s: &f64) {
+        self.center *= *s;
+        self.major_radius *= s.abs();
+        self.minor_radius *= s.abs();
+    }
+
+    // segments columns around the axis and segments / 2 rows around the tube
+    fn triangles(&self, segments: usize) -> Option<Vec<Triangle>> {
+        let segments = segments.max(3);
+        let rows = (segments / 2).max(3);
+        let grid: Vec<Vec<Vector>> = (0..=rows)
+            .map(|i| {
+                let angle = 2.0 * PI * (i % rows) as f64 / rows as f64;
+                let center = self.center + self.axis * (self.minor_radius * angle.sin());
+                circle_points(&center, &self.axis, self.major_radius + self.minor_radius * angle.cos(), segments)
+            })
+            .collect();
+        Some(triangulate_grid(&grid))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn torus() -> Torus {
+        Torus::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0), 3.0, 1.0)
+    }
+
+    #[test]
+    fn through_hole_test() {
+        // straight through the hole along the axis
+        let line = Line::new(Vector::new(0.0, 0.0, 10.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(torus().intersect(&line).is_none());
+    }
+
+    #[test]
+    fn tube_test() {
+        let line = Line::new(Vector::new(3.0, 0.0, 10.0), Vector::new(0.0, 0.0, -1.0));
+        let t = torus().intersect(&line).unwrap();
+        assert!((t - 9.0).abs() < 1e-9);
+        let n = torus().normal_at(&line.point_on_line(&t));
+        assert!((n - Vector::new(0.0, 0.0, 1.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn near_tangent_test() {
+        // lines that nearly touch the outside of the torus, where the quartic has two nearly double roots.
+        // In the xy plane past the outer rim, and in the plane y = 3 over the top of the tube
+        for k in 2..10 {
+            let offset = 10f64.powi(-k);
+            let y = 4.0 - offset;
+            let line = Line::new(Vector::new(-10.0, y, 0.0), Vector::new(1.0, 0.0, 0.0));
+            let t = torus().intersect(&line).expect("grazing line misses the outer rim");
+            assert!((t - (10.0 - (16.0 - y * y).sqrt())).abs() < 1e-6, "offset {}: t = {}", offset, t);
+
+            let z = 1.0 - offset;
+            let line = Line::new(Vector::new(-10.0, 3.0, z), Vector::new(1.0, 0.0, 0.0));
+            let t = torus().intersect(&line).expect("grazing line misses the top of the tube");
+            let x = ((3.0 + (1.0 - z * z).sqrt()).powi(2) - 9.0).sqrt();
+            assert!((t - (10.0 - x)).abs() < 1e-6, "offset {}: t = {}", offset, t);
+        }
+    }
+
+    #[test]
+    fn in_plane_test() {
+        // along the x axis the line crosses the tube four times, the first hit is at x = 4
+        let line = Line::new(Vector::new(100.0, 0.0, 0.0), Vector::new(-2.0, 0.0, 0.0));
+        let t = torus().intersect(&line).unwrap();
+        assert!((t - 48.0).abs() < 1e-9);
+        // starting inside the hole, the first hit is the inner side of the tube at x = 2
+        let line = Line::new(Vector::new(0.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let t = torus().intersect(&line).unwrap();
+        assert!((t - 2.0).abs() < 1e-9);
+        let n = torus().normal_at(&line.point_on_line(&t));
+        assert!((n - Vector::new(-1.0, 0.0, 0.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn grazing_test() {
+        // tangent to the top of the tube
+        let line = Line::new(Vector::new(-10.0, 0.0, 1.0 + 1e-6), Vector::new(1.0, 0.0, 0.0));
+        assert!(torus().intersect(&line).is_none());
+        let line = Line::new(Vector::new(-10.0, 0.0, 1.0 - 1e-3), Vector::new(1.0, 0.0, 0.0));
+        assert!(torus().intersect(&line).is_some());
+    }
+}
@@ -0,0 +1,102 @@
+use crate::mat4::Mat4;
+use crate::quaternion::Quaternion;
+use crate::vector::Vector;
+
+// position, orientation and size of an object in the world.
+// Points are scaled first, then rotated around (0, 0, 0) and then moved by translation
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+    pub translation: Vector,
+    // has to be a unit quaternion
+    pub rotation: Quaternion,
+    pub scale: Vector,
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform::new(Vector::new(0.0, 0.0, 0.0), Quaternion::identity(), Vector::new(1.0, 1.0, 1.0))
+    }
+
+    pub fn new(translation: Vector, rotation: Quaternion, scale: Vector) -> Transform {
+        Transform { translation, rotation, scale }
+    }
+
+    // model matrix, from object space to world space: translation * rotation * scale
+    pub fn matrix(&self) -> Mat4 {
+        let mut translation = Mat4::identity();
+        translation.translate(self.translation);
+        let mut scale = Mat4::identity();
+        scale.scale(self.scale);
+        translation * self.rotation.to_mat4() * scale
+    }
+
+    // from world space back to object space. Built from the parts instead of inverting the matrix,
+    // so it stays exact. None if a scale component is 0, which flattens the object
+    pub fn inverse_matrix(&self) -> Option<Mat4> {
+        if self.scale.x == 0.0 || self.scale.y == 0.0 || self.scale.z == 0.0 {
+            return None;
+        }
+        let mut translation = Mat4::identity();
+        translation.translate(self.translation * -1.0);
+        let mut rotation = self.rotation;
+        rotation.conjugate();
+        let mut scale = Mat4::identity();
+        scale.scale(Vector::new(1.0 / self.scale.x, 1.0 / self.scale.y, 1.0 / self.scale.z));
+        Some(scale * rotation.to_mat4() * translation)
+    }
+
+    // applies q after this transform, rotating the whole object around (0, 0, 0) like rotating its shapes would
+    pub fn rotate(&mut self, q: &Quaternion) {
+        self.translation.rotate_by_quaternion(q);
+        self.rotation = *q * self.rotation;
+        // keep rounding errors of many small rotations from scaling the object
+        self.rotation.normalize();
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::identity()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::as_radians;
+
+    #[test]
+    fn matrix_test() {
+        let mut rotation = Quaternion::identity();
+        rotation.rotate(as_radians(90.0), Vector::new(0.0, 0.0, 1.0));
+        let transform = Transform::new(Vector::new(10.0, 0.0, 0.0), rotation, Vector::new(2.0, 3.0, 1.0));
+        let m = transform.matrix();
+
+        let close = |a: Vector, b: Vector| (a - b).length() < 1e-12;
+        // scaled to (2, 0, 0), rotated to (0, 2, 0) and moved
+        assert!(close(m.transform_point(&Vector::new(1.0, 0.0, 0.0)), Vector::new(10.0, 2.0, 0.0)));
+        assert!(close(m.transform_point(&Vector::new(0.0, 1.0, 0.0)), Vector::new(7.0, 0.0, 0.0)));
+        // directions aren't moved
+        assert!(close(m.transform_direction(&Vector::new(0.0, 0.0, 1.0)), Vector::new(0.0, 0.0, 1.0)));
+
+        let inverse = transform.inverse_matrix().unwrap();
+        let p = Vector::new(1.5, -2.0, 7.0);
+        assert!(close(inverse.transform_point(&m.transform_point(&p)), p));
+        assert!(Transform::new(Vector::new(0.0, 0.0, 0.0), rotation, Vector::new(1.0, 0.0, 1.0)).inverse_matrix().is_none());
+    }
+
+    #[test]
+    fn rotate_test() {
+        let mut transform = Transform::identity();
+        transform.translation = Vector::new(1.0, 0.0, 0.0);
+        let mut q = Quaternion::identity();
+        q.rotate(as_radians(1.0), Vector::new(0.0, 0.0, 1.0));
+        for _ in 0..360 {
+            transform.rotate(&q);
+        }
+        // a full turn in small steps ends up where it started
+        let m = transform.matrix();
+        assert!((m.transform_point(&Vector::new(0.0, 1.0, 0.0)) - Vector::new(1.0, 1.0, 0.0)).length() < 1e-9);
+        assert!((transform.rotation.length() - 1.0).abs() < 1e-12);
+    }
+}
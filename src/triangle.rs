@@ -0,0 +1,356 @@
+use crate::aabb::Aabb;
+use crate::line::Line;
+use crate::mat4::Mat4;
+use crate::quaternion::Quaternion;
+use crate::shape::{Shape, MIN_T};
+use crate::vector::Vector;
+
+// determinants smaller than this are treated as a line parallel to the triangle
+const EPSILON: f64 = 1e-12;
+
+// triangle defined by three vertices, the front face is the one where a, b, c go counter-clockwise
+#[derive(Debug, Clone, Copy)]
+pub struct Triangle {
+    pub a: Vector,
+    pub b: Vector,
+    pub c: Vector,
+}
+
+// result of a line-triangle intersection
+#[derive(Debug, Clone, Copy)]
+pub struct TriangleHit {
+    // the hit point is line.point + line.direction * t, t is negative if the hit is behind line.point
+    pub t: f64,
+    // barycentric coordinates of the hit point, weights of a, b and c. They always sum to 1
+    pub barycentric: (f64, f64, f64),
+    // geometric normal of the triangle, (b - a) x (c - a) normalized
+    pub normal: Vector,
+}
+
+impl Triangle {
+    pub fn new(a: Vector, b: Vector, c: Vector) -> Triangle {
+        Triangle { a, b, c }
+    }
+
+    pub fn normal(&self) -> Vector {
+        let mut n = (self.b - self.a).cross(&(self.c - self.a));
+        n.normalize();
+        n
+    }
+
+    pub fn area(&self) -> f64 {
+        (self.b - self.a).cross(&(self.c - self.a)).length() * 0.5
+    }
+
+    // returns a * w + b * u + c * v for barycentric coordinates (w, u, v)
+    pub fn point_from_barycentric(&self, (w, u, v): (f64, f64, f64)) -> Vector {
+        self.a * w + self.b * u + self.c * v
+    }
+
+    // barycentric coordinates (w, u, v) of a point lying in the plane of the triangle
+    pub fn barycentric_at(&self, point: &Vector) -> (f64, f64, f64) {
+        let n = (self.b - self.a).cross(&(self.c - self.a));
+        let area = n.length_squared();
+        let u = (*point - self.a).cross(&(self.c - self.a)).dot(&n) / area;
+        let v = (self.b - self.a).cross(&(*point - self.a)).dot(&n) / area;
+        (1.0 - u - v, u, v)
+    }
+
+    // applies an affine transform to the vertices. A mirroring matrix would turn the triangle around,
+    // so b and c are swapped to keep the front face where the transformed normal points
+    pub fn transform(&mut self, m: &Mat4) {
+        self.a = m.transform_point(&self.a);
+        self.b = m.transform_point(&self.b);
+        self.c = m.transform_point(&self.c);
+        if mirrors(m) {
+            std::mem::swap(&mut self.b, &mut self.c);
+        }
+    }
+
+    // Möller–Trumbore intersection, see:
+    // https://en.wikipedia.org/wiki/M%C3%B6ller%E2%80%93Trumbore_intersection_algorithm
+    // Returns None if the line is parallel to the triangle or misses it.
+    pub fn intersect(&self, line: &Line) -> Option<TriangleHit> {
+        let edge1 = self.b - self.a;
+        let edge2 = self.c - self.a;
+        let p = line.direction.cross(&edge2);
+        let det = edge1.dot(&p);
+        if det.abs() < EPSILON {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let s = line.point - self.a;
+        let u = s.dot(&p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(&edge1);
+        let v = line.direction.dot(&q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = edge2.dot(&q) * inv_det;
+        Some(TriangleHit {
+            t,
+            barycentric: (1.0 - u - v, u, v),
+            normal: self.normal(),
+        })
+    }
+}
+
+impl Shape for Triangle {
+    fn intersect(&self, line: &Line) -> Option<f64> {
+        Triangle::intersect(self, line).map(|hit| hit.t).filter(|t| *t > MIN_T)
+    }
+
+    // front face normal
+    fn normal_at(&self, _point: &Vector) -> Vector {
+        self.normal()
+    }
+
+    // barycentric weights of b and c
+    fn uv_at(&self, point: &Vector) -> (f64, f64) {
+        let (_, u, v) = self.barycentric_at(point);
+        (u, v)
+    }
+
+    fn bounds(&self) -> Aabb {
+        Aabb::from_points(&[self.a, self.b, self.c])
+    }
+
+    fn rotate(&mut self, q: &Quaternion) {
+        self.a.rotate_by_quaternion(q);
+        self.b.rotate_by_quaternion(q);
+        self.c.rotate_by_quaternion(q);
+    }
+
+    fn scale(&mut self, s: &f64) {
+        self.a *= *s;
+        self.b *= *s;
+        self.c *= *s;
+    }
+
+    fn triangles(&self, _segments: usize) -> Option<Vec<Triangle>> {
+        Some(vec![*self])
+    }
+}
+
+// triangle with a normal at every vertex, for meshes approximating curved surfaces. Hits get the vertex normals
+// interpolated with their barycentric coordinates, so the mesh is shaded smoothly while its outline stays flat
+#[derive(Debug, Clone, Copy)]
+pub struct SmoothTriangle {
+    pub triangle: Triangle,
+    // normalized normals at a, b and c
+    pub normals: [Vector; 3],
+}
+
+impl SmoothTriangle {
+    pub fn new(triangle: Triangle, normals: [Vector; 3]) -> SmoothTriangle {
+        SmoothTriangle { triangle, normals }
+    }
+
+    // like Triangle::transform, the normals are transformed with the inverse transpose of m
+    pub fn transform(&mut self, m: &Mat4) {
+        self.triangle.transform(m);
+        if mirrors(m) {
+            self.normals.swap(1, 2);
+        }
+        if let Some(normal_matrix) = m.normal_matrix() {
+            for normal in self.normals.iter_mut() {
+                *normal = normal_matrix.transform_direction(normal);
+                normal.normalize();
+            }
+        }
+    }
+}
+
+impl Shape for SmoothTriangle {
+    fn intersect(&self, line: &Line) -> Option<f64> {
+        Shape::intersect(&self.triangle, line)
+    }
+
+    // interpolated vertex normal, the front face normal where the vertex normals cancel out
+    fn normal_at(&self, point: &Vector) -> Vector {
+        let (w, u, v) = self.triangle.barycentric_at(point);
+        let [a, b, c] = self.normals;
+        let mut normal = a * w + b * u + c * v;
+        if normal.length_squared() == 0.0 || normal.length_squared().is_nan() {
+            return self.triangle.normal();
+        }
+        normal.normalize();
+        normal
+    }
+
+    fn uv_at(&self, point: &Vector) -> (f64, f64) {
+        self.triangle.uv_at(point)
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.triangle.bounds()
+    }
+
+    fn rotate(&mut self, q: &Quaternion) {
+        self.triangle.rotate(q);
+        for normal in self.normals.iter_mut() {
+            normal.rotate_by_quaternion(q);
+        }
+    }
+
+    fn scale(&mut self, s: &f64) {
+        self.triangle.scale(s);
+    }
+
+    fn triangles(&self, _segments: usize) -> Option<Vec<Triangle>> {
+        Some(vec![self.triangle])
+    }
+}
+
+// true if m turns space inside out, like a mirror, so counter-clockwise corners become clockwise
+fn mirrors(m: &Mat4) -> bool {
+    let x = m.transform_direction(&Vector::new(1.0, 0.0, 0.0));
+    let y = m.transform_direction(&Vector::new(0.0, 1.0, 0.0));
+    let z = m.transform_direction(&Vector::new(0.0, 0.0, 1.0));
+    x.cross(&y).dot(&z) < 0.0
+}
+
+// two triangles for every cell of a grid of points rows[i][j], with their front faces on the side
+// (rows[i][j + 1] - rows[i][j]) x (rows[i + 1][j] - rows[i][j]) points to.
+// Triangles without any area, like the ones at the poles of a sphere, are left out
+pub fn triangulate_grid(rows: &[Vec<Vector>]) -> Vec<Triangle> {
+    let mut triangles = Vec::new();
+    for (row, next) in rows.iter().zip(rows.iter().skip(1)) {
+        for j in 0..row.len().min(next.len()).saturating_sub(1) {
+            for triangle in [Triangle::new(row[j], row[j + 1], next[j + 1]), Triangle::new(row[j], next[j + 1], next[j])] {
+                if triangle.area() > 0.0 {
+                    triangles.push(triangle);
+                }
+            }
+        }
+    }
+    triangles
+}
+
+// triangles from center to every edge of a polyline, facing the side it goes counter-clockwise around
+pub fn triangulate_fan(center: &Vector, ring: &[Vector]) -> Vec<Triangle> {
+    ring.windows(2).map(|edge| Triangle::new(*center, edge[0], edge[1])).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn triangle() -> Triangle {
+        Triangle::new(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0))
+    }
+
+    #[test]
+    fn hit_test() {
+        let line = Line::new(Vector::new(0.25, 0.25, 5.0), Vector::new(0.0, 0.0, -1.0));
+        let hit = triangle().intersect(&line).unwrap();
+        assert_eq!(hit.t, 5.0);
+        assert_eq!(hit.barycentric, (0.5, 0.25, 0.25));
+        assert_eq!(hit.normal, Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(triangle().point_from_barycentric(hit.barycentric), line.point_on_line(&hit.t));
+        assert_eq!(triangle().barycentric_at(&line.point_on_line(&hit.t)), hit.barycentric);
+    }
+
+    #[test]
+    fn hit_behind_test() {
+        let line = Line::new(Vector::new(0.25, 0.25, 5.0), Vector::new(0.0, 0.0, 1.0));
+        let hit = triangle().intersect(&line).unwrap();
+        assert_eq!(hit.t, -5.0);
+    }
+
+    #[test]
+    fn miss_test() {
+        let outside = Line::new(Vector::new(0.75, 0.75, 5.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(triangle().intersect(&outside).is_none());
+        let parallel = Line::new(Vector::new(0.25, 0.25, 5.0), Vector::new(1.0, 0.0, 0.0));
+        assert!(triangle().intersect(&parallel).is_none());
+    }
+
+    #[test]
+    fn vertex_test() {
+        // rays through the vertices have one barycentric coordinate equal to 1
+        let t = triangle();
+        for (vertex, expected) in [(t.a, (1.0, 0.0, 0.0)), (t.b, (0.0, 1.0, 0.0)), (t.c, (0.0, 0.0, 1.0))] {
+            let line = Line::new(vertex + Vector::new(0.0, 0.0, 1.0), Vector::new(0.0, 0.0, -1.0));
+            assert_eq!(t.intersect(&line).unwrap().barycentric, expected);
+        }
+    }
+
+    #[test]
+    fn area_test() {
+        assert_eq!(triangle().area(), 0.5);
+    }
+
+    #[test]
+    fn transform_test() {
+        let mut t = triangle();
+        let mut m = Mat4::identity();
+        m.scale(Vector::new(2.0, 1.0, 1.0));
+        m.translate(Vector::new(0.0, 0.0, 3.0));
+        t.transform(&m);
+        assert_eq!(t.a, Vector::new(0.0, 0.0, 3.0));
+        assert_eq!(t.area(), triangle().area() * 2.0);
+        assert_eq!(t.normal(), triangle().normal());
+
+        // mirrored along z, the front face turns around with it
+        let mut mirror = Mat4::identity();
+        mirror.scale(Vector::new(1.0, 1.0, -1.0));
+        let mut t = triangle();
+        t.transform(&mirror);
+        assert_eq!(t.normal(), triangle().normal() * -1.0);
+    }
+
+    // the triangle with its normals leaning away from each other, like on a sphere
+    fn smooth_triangle() -> SmoothTriangle {
+        let mut normals = [Vector::new(-1.0, -1.0, 2.0), Vector::new(1.0, 0.0, 1.0), Vector::new(0.0, 1.0, 1.0)];
+        for normal in normals.iter_mut() {
+            normal.normalize();
+        }
+        SmoothTriangle::new(triangle(), normals)
+    }
+
+    #[test]
+    fn smooth_normal_test() {
+        let t = smooth_triangle();
+        // the vertex normals at the vertices, their average in between
+        for (vertex, normal) in [(t.triangle.a, t.normals[0]), (t.triangle.b, t.normals[1]), (t.triangle.c, t.normals[2])] {
+            assert_eq!(t.normal_at(&vertex), normal);
+        }
+        let mut middle = t.normals[1] + t.normals[2];
+        middle.normalize();
+        assert_eq!(t.normal_at(&Vector::new(0.5, 0.5, 0.0)), middle);
+
+        let line = Line::new(Vector::new(0.25, 0.25, 5.0), Vector::new(0.0, 0.0, -1.0));
+        assert_eq!(Shape::intersect(&t, &line), Some(5.0));
+
+        // vertex normals that cancel out give the face normal
+        let flat = SmoothTriangle::new(triangle(), [Vector::new(1.0, 0.0, 0.0), Vector::new(-1.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0)]);
+        assert_eq!(flat.normal_at(&Vector::new(0.5, 0.0, 0.0)), Vector::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn smooth_transform_test() {
+        // stretched along x, normals lean less towards x
+        let mut t = smooth_triangle();
+        let mut m = Mat4::identity();
+        m.scale(Vector::new(2.0, 1.0, 1.0));
+        t.transform(&m);
+        assert_eq!(t.normal_at(&t.triangle.b), Vector::new(1.0, 0.0, 2.0) * (1.0 / 5.0f64.sqrt()));
+
+        // mirrored, the normals stay with their vertices
+        let mut t = smooth_triangle();
+        let mut mirror = Mat4::identity();
+        mirror.scale(Vector::new(-1.0, 1.0, 1.0));
+        t.transform(&mirror);
+        assert_eq!(t.normal_at(&Vector::new(-1.0, 0.0, 0.0)), Vector::new(-1.0, 0.0, 1.0) * (1.0 / 2.0f64.sqrt()));
+    }
+}
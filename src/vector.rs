@@ -94,6 +94,45 @@ impl Vector {
         self.z = vprime.z;
     }
 
+    // returns two unit vectors that together with self (which must be normalized) form an orthonormal basis
+    // algorithm https://graphics.pixar.com/library/OrthonormalB/paper.pdf
+    pub fn orthonormal_basis(&self) -> (Vector, Vector) {
+        let sign = 1.0f64.copysign(self.z);
+        let a = -1.0 / (sign + self.z);
+        let b = self.x * self.y * a;
+        let t = Vector::new(1.0 + sign * self.x * self.x * a, sign * b, -sign * self.x);
+        let s = Vector::new(b, sign + self.y * self.y * a, -self.y);
+        (t, s)
+    }
+
+    // component-wise minimum
+    pub fn min(&self, other: &Vector) -> Vector {
+        Vector::new(self.x.min(other.x), self.y.min(other.y), self.z.min(other.z))
+    }
+
+    // component-wise maximum
+    pub fn max(&self, other: &Vector) -> Vector {
+        Vector::new(self.x.max(other.x), self.y.max(other.y), self.z.max(other.z))
+    }
+
+    // mirrors self around the normal n, n must be normalized
+    pub fn reflect(&self, n: &Vector) -> Vector {
+        *self - *n * (2.0 * self.dot(n))
+    }
+
+    // bends self (normalized) through a surface with normal n facing against it, eta is n1 / n2.
+    // Returns None on total internal reflection
+    // algorithm https://graphics.stanford.edu/courses/cs148-10-summer/docs/2006--degreve--reflection_refraction.pdf
+    pub fn refract(&self, n: &Vector, eta: f64) -> Option<Vector> {
+        let cos_i = -self.dot(n);
+        let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+        if sin2_t > 1.0 {
+            return None;
+        }
+        let cos_t = (1.0 - sin2_t).sqrt();
+        Some(*self * eta + *n * (eta * cos_i - cos_t))
+    }
+
     pub fn distance(&self, other: &Vector) -> f64 {
         ((other.x - self.x).powi(2) + (other.y - self.y).powi(2) + (other.z - self.z).powi(2)).sqrt()
     }
@@ -348,6 +387,19 @@ mod tests {
         assert_eq!(vec2, Vector::new(0.0, 0.0, -1.0));
     }
 
+    #[test]
+    fn orthonormal_basis_test() {
+        for mut n in [Vector::new(0.0, 0.0, 1.0), Vector::new(0.0, 0.0, -1.0), Vector::new(1.0, 2.0, -3.0)] {
+            n.normalize();
+            let (t, s) = n.orthonormal_basis();
+            assert!(t.dot(&n).abs() < 1e-12);
+            assert!(s.dot(&n).abs() < 1e-12);
+            assert!(t.dot(&s).abs() < 1e-12);
+            assert!((t.length() - 1.0).abs() < 1e-12);
+            assert!((s.length() - 1.0).abs() < 1e-12);
+        }
+    }
+
     #[test]
     fn quaternion_rotation_test() {
         use crate::quaternion::*;
@@ -357,4 +409,26 @@ mod tests {
         vec.rotate_by_quaternion(&quat);
         assert_eq!(vec, Vector::new(0.0, 0.0, -1.0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn reflect_refract_test() {
+        let mut d = Vector::new(1.0, -1.0, 0.0);
+        d.normalize();
+        let n = Vector::new(0.0, 1.0, 0.0);
+        let mut expected = Vector::new(1.0, 1.0, 0.0);
+        expected.normalize();
+        assert_eq!(d.reflect(&n), expected);
+
+        // same medium on both sides, the direction doesn't change
+        let t = d.refract(&n, 1.0).unwrap();
+        assert!((t - d).length() < 1e-12);
+
+        // into glass the ray bends towards the normal, sin(t) = sin(i) / 1.5
+        let t = d.refract(&n, 1.0 / 1.5).unwrap();
+        assert!((t.length() - 1.0).abs() < 1e-12);
+        assert!((t.x - (0.5f64.sqrt() / 1.5)).abs() < 1e-12);
+
+        // out of glass at 45 degrees is past the critical angle
+        assert!(d.refract(&n, 1.5).is_none());
+    }
+}